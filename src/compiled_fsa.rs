@@ -0,0 +1,210 @@
+//! # Compiled finite state automaton
+//!
+//! The `compiled_fsa` module defines an immutable, read-optimized form of the finite state automaton, for when all
+//! transformations are done and the automaton is only used. It is built by FiniteStateAutomaton::freeze.
+
+use std::collections::HashMap;
+
+use crate::word::*;
+use crate::regex::*;
+use crate::finite_state_automaton::State;
+use crate::configuration::{self, TransitionLookup, Entered};
+
+/// A finite state automaton stored in flat arrays. The states are numbered densely from 0 in the order of their old
+/// numbers, and the transitions of a state are one slice of a single array, sorted by symbol. Every distinct `Rules`
+/// is stored once, in sorted order, and referred to by its index, so index 0 is the empty `Rules`. A CompiledFsa
+/// cannot be changed, so it is `Send` and `Sync` and can be shared between threads behind the `Arc` returned by
+/// `freeze`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledFsa {
+    pub(crate) start: usize,
+    pub(crate) accepting: Vec<bool>,
+    // the transitions of state i are edges[edge_offsets[i]..edge_offsets[i + 1]], as (symbol, destination, rules)
+    pub(crate) edge_offsets: Vec<usize>,
+    pub(crate) edges: Vec<(Symbol, usize, usize)>,
+    pub(crate) atomic: HashMap<(Nonterminal, Terminal), (usize, Vec<usize>)>,
+    pub(crate) rules: Vec<Rules>,
+}
+
+impl CompiledFsa {
+    pub fn state_count(&self) -> usize {
+        self.accepting.len()
+    }
+
+    pub fn is_accepting(&self, state: usize) -> bool {
+        self.accepting[state]
+    }
+
+    /// Returns the interned rules, which the indices in `parse` refer to.
+    pub fn rules(&self) -> &[Rules] {
+        &self.rules
+    }
+
+    /// Determines whether the input is accepted, in the same way as FiniteStateAutomaton::recognize.
+    pub fn recognize(&self, input: &[Terminal]) -> bool {
+        configuration::recognize(self, input)
+    }
+
+    /// Parses the input, returning the rules collected along one accepting path of the automaton as indices into
+    /// `rules`, or None if the input is not accepted. The rules are collected like in
+    /// FiniteStateAutomaton::shortest_derivation, empty rules are left out. For an ambiguous input only one of its
+    /// derivations is returned, always the same one; use the parser of the grammar to find all of them.
+    pub fn parse(&self, input: &[Terminal]) -> Option<Vec<usize>> {
+        configuration::parse(self, input)
+    }
+
+    /// Recognizes every input, see `recognize`. The configurations are stored one after the other in a single buffer
+    /// of states, which is reused for every terminal and every input, so unlike FiniteStateAutomaton::recognize_batch
+    /// no configuration is allocated on its own. Duplicate configurations are removed by sorting after every terminal.
+    pub fn recognize_batch(&self, inputs: &[Vec<Terminal>]) -> Vec<bool> {
+        let mut configurations: ConfigurationBuffer = ConfigurationBuffer::default();
+        let mut next_configurations: ConfigurationBuffer = ConfigurationBuffer::default();
+        let mut visited: Vec<bool> = vec![false; self.state_count()];
+        let mut reached: Vec<usize> = Vec::new();
+        let mut res: Vec<bool> = Vec::with_capacity(inputs.len());
+
+        for input in inputs {
+            configurations.clear();
+            self.close_into_buffer(&[], &[self.start], &mut configurations, &mut visited, &mut reached);
+            for terminal in input {
+                next_configurations.clear();
+                for index in 0..configurations.len() {
+                    let configuration: &[usize] = configurations.get(index);
+                    let (below, top): (&[usize], usize) = (&configuration[..configuration.len() - 1], configuration[configuration.len() - 1]);
+                    for (symbol, dest, _) in self.edges_of(top) {
+                        match symbol {
+                            Symbol::Terminal(t) if t == terminal => self.close_into_buffer(below, &[*dest], &mut next_configurations, &mut visited, &mut reached),
+                            Symbol::Nonterminal(nt) => if let Some((atomic_state, _)) = self.atomic.get(&(*nt, *terminal)) {
+                                self.close_into_buffer(below, &[*dest, *atomic_state], &mut next_configurations, &mut visited, &mut reached);
+                            },
+                            _ => {},
+                        }
+                    }
+                }
+                next_configurations.dedup();
+                std::mem::swap(&mut configurations, &mut next_configurations);
+                if configurations.len() == 0 {
+                    break;
+                }
+            }
+            res.push((0..configurations.len()).any(|index| {
+                let configuration: &[usize] = configurations.get(index);
+                configuration.len() == 1 && self.accepting[configuration[0]]
+            }));
+        }
+        res
+    }
+
+    // Adds the closure of the configuration `below` followed by `top` to the buffer, like configuration::close. An
+    // epsilon transition only changes the top frame and a return only removes it, so the closure consists of the
+    // states reached by epsilon transitions on top of every prefix of the configuration, where a shorter prefix is only
+    // reached if an accepting state was reached on top of the longer one. `visited` holds a false for every state and
+    // is left like that, `reached` is only used as a buffer.
+    fn close_into_buffer(&self, below: &[usize], top: &[usize], res: &mut ConfigurationBuffer, visited: &mut [bool], reached: &mut Vec<usize>) {
+        let frame = |index: usize| -> usize { if index < below.len() { below[index] } else { top[index - below.len()] } };
+        for level in (0..below.len() + top.len()).rev() {
+            let mut returns: bool = false;
+            reached.clear();
+            reached.push(frame(level));
+            visited[frame(level)] = true;
+            let mut index: usize = 0;
+            while let Some(state) = reached.get(index).cloned() {
+                res.push((0..level).map(frame).chain(std::iter::once(state)));
+                returns |= self.accepting[state];
+                for (_, dest, _) in self.edges_of(state).iter().take_while(|(symbol, _, _)| *symbol == Symbol::Epsilon) {
+                    if !visited[*dest] {
+                        visited[*dest] = true;
+                        reached.push(*dest);
+                    }
+                }
+                index += 1;
+            }
+            for state in reached.iter() {
+                visited[*state] = false;
+            }
+            if !returns {
+                break;
+            }
+        }
+    }
+
+    // The transitions of the state.
+    fn edges_of(&self, state: usize) -> &[(Symbol, usize, usize)] {
+        &self.edges[self.edge_offsets[state]..self.edge_offsets[state + 1]]
+    }
+}
+
+// Configurations stored one after the other in a single buffer of states: configuration i is
+// states[bounds[i].0..bounds[i].1].
+#[derive(Default)]
+struct ConfigurationBuffer {
+    states: Vec<usize>,
+    bounds: Vec<(usize, usize)>,
+}
+
+impl ConfigurationBuffer {
+    fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    fn get(&self, index: usize) -> &[usize] {
+        let (start, end): (usize, usize) = self.bounds[index];
+        &self.states[start..end]
+    }
+
+    fn push(&mut self, configuration: impl Iterator<Item = usize>) {
+        let start: usize = self.states.len();
+        self.states.extend(configuration);
+        self.bounds.push((start, self.states.len()));
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.bounds.clear();
+    }
+
+    // Removes duplicate configurations, by sorting them. The states of the removed ones stay in the buffer until it is
+    // cleared.
+    fn dedup(&mut self) {
+        let states: &[usize] = &self.states;
+        self.bounds.sort_unstable_by(|(start_a, end_a), (start_b, end_b)| states[*start_a..*end_a].cmp(&states[*start_b..*end_b]));
+        self.bounds.dedup_by(|(start_a, end_a), (start_b, end_b)| states[*start_a..*end_a] == states[*start_b..*end_b]);
+    }
+}
+
+impl TransitionLookup for CompiledFsa {
+    // the index of the rules in `rules`
+    type Rules = usize;
+
+    fn start_state(&self) -> State {
+        self.start
+    }
+
+    fn accepting(&self, state: State) -> bool {
+        self.accepting[state]
+    }
+
+    fn carries_rules(&self, rules: &usize) -> bool {
+        *rules != 0
+    }
+
+    fn epsilon_transitions(&self, state: State, mut f: impl FnMut(State, &usize)) {
+        // epsilon is the smallest symbol, so its transitions come first
+        for (_, dest, rules) in self.edges_of(state).iter().take_while(|(symbol, _, _)| *symbol == Symbol::Epsilon) {
+            f(*dest, rules);
+        }
+    }
+
+    // Of the rules of an atomic language the smallest is passed.
+    fn terminal_transitions(&self, state: State, terminal: Terminal, mut f: impl FnMut(State, &usize, Entered<usize>)) {
+        for (symbol, dest, rules) in self.edges_of(state) {
+            match symbol {
+                Symbol::Terminal(t) if *t == terminal => f(*dest, rules, None),
+                Symbol::Nonterminal(nt) => if let Some((atomic_state, rules_set)) = self.atomic.get(&(*nt, terminal)) {
+                    f(*dest, rules, Some((*atomic_state, rules_set.first())));
+                },
+                _ => {},
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! # Configuration
+//!
+//! The `configuration` module moves the different forms of the finite state automaton through their configurations.
+//! A configuration is a stack of states: the last state is the current state, the states below it are the states to
+//! continue from once the current state is accepting. Every form of the automaton offers its transitions through
+//! `TransitionLookup`, so closing and deriving configurations is written once for all of them.
+
+use std::collections::{HashSet, BTreeMap};
+
+use crate::word::*;
+use crate::finite_state_automaton::*;
+
+// The atomic language entered by a nonterminal transition: its start state and its smallest rules, if any.
+pub(crate) type Entered<'a, R> = Option<(State, Option<&'a R>)>;
+
+// The transitions of an automaton, as needed to move it through its configurations.
+pub(crate) trait TransitionLookup {
+    // The rules carried by a transition or an atomic language.
+    type Rules: Clone;
+
+    fn start_state(&self) -> State;
+
+    fn accepting(&self, state: State) -> bool;
+
+    // Whether the rules are part of a derivation, i.e. they are not empty.
+    fn carries_rules(&self, rules: &Self::Rules) -> bool;
+
+    // Passes the destination and rules of every epsilon transition from the state to the function, always in the
+    // same order.
+    fn epsilon_transitions(&self, state: State, f: impl FnMut(State, &Self::Rules));
+
+    // Passes the destination and rules of every transition from the state consuming the terminal to the function,
+    // always in the same order. Nonterminal transitions are taken through the atomic language of the nonterminal and
+    // the terminal, which is passed along, transitions whose atomic language does not exist are left out.
+    fn terminal_transitions(&self, state: State, terminal: Terminal, f: impl FnMut(State, &Self::Rules, Entered<Self::Rules>));
+}
+
+// An element of a configuration, which holds a state and possibly more, like the position at which it was entered.
+pub(crate) trait Frame: Clone {
+    fn state(&self) -> State;
+
+    // The same frame with the state replaced.
+    fn moved_to(&self, state: State) -> Self;
+}
+
+impl Frame for State {
+    fn state(&self) -> State {
+        *self
+    }
+
+    fn moved_to(&self, state: State) -> State {
+        state
+    }
+}
+
+impl Frame for (State, usize) {
+    fn state(&self) -> State {
+        self.0
+    }
+
+    fn moved_to(&self, state: State) -> (State, usize) {
+        (state, self.1)
+    }
+}
+
+// Passes the configuration and every configuration reachable from it by following epsilon transitions, or by returning
+// from an accepting state to the state below it, to `insert` together with its trail, until `insert` returns false
+// for a configuration it has seen before. The trail of a configuration reached by an epsilon transition is made by
+// `follow` from the trail, the top frame and the rules of the transition, a return keeps the trail.
+pub(crate) fn close<A: TransitionLookup, F: Frame, T: Clone>(automaton: &A, configuration: Vec<F>, trail: T, mut insert: impl FnMut(Vec<F>, T) -> bool, follow: impl Fn(&T, &F, &A::Rules) -> T) {
+    let mut to_close: Vec<(Vec<F>, T)> = vec![(configuration, trail)];
+
+    while let Some((curr, curr_trail)) = to_close.pop() {
+        if !insert(curr.clone(), curr_trail.clone()) {
+            continue;
+        }
+        let top: &F = &curr[curr.len() - 1];
+        automaton.epsilon_transitions(top.state(), |dest, rules| {
+            let mut next: Vec<F> = curr.clone();
+            next[curr.len() - 1] = top.moved_to(dest);
+            to_close.push((next, follow(&curr_trail, top, rules)));
+        });
+        if curr.len() > 1 && automaton.accepting(top.state()) {
+            to_close.push((curr[..curr.len() - 1].to_vec(), curr_trail));
+        }
+    }
+}
+
+// Adds the configuration and every configuration reachable from it without consuming a terminal to the closure.
+pub(crate) fn close_into<A: TransitionLookup>(automaton: &A, configuration: Configuration, closure: &mut HashSet<Configuration>) {
+    close(automaton, configuration, (), |next, _| closure.insert(next), |_, _, _| ());
+}
+
+// Adds the configuration and every configuration reachable from it without consuming a terminal to the closure,
+// keeping the first rules found for every configuration. Empty rules are left out.
+pub(crate) fn close_with_rules<A: TransitionLookup>(automaton: &A, configuration: Configuration, trail: Vec<A::Rules>, closure: &mut BTreeMap<Configuration, Vec<A::Rules>>) {
+    close(automaton, configuration, trail, |next, next_trail| {
+        if closure.contains_key(&next) {
+            return false;
+        }
+        closure.insert(next, next_trail);
+        true
+    }, |trail, _, rules| with_rules(automaton, trail, &[rules]));
+}
+
+// Passes every configuration reachable from the given configuration by consuming a terminal, before closing it, to
+// the function, together with the rules of the transition and of the atomic language entered, if any. The atomic
+// language is pushed on top as the frame made by `enter` from its start state.
+pub(crate) fn derive<A: TransitionLookup, F: Frame>(automaton: &A, configuration: &[F], terminal: Terminal, enter: impl Fn(State) -> F, mut f: impl FnMut(Vec<F>, &A::Rules, Option<&A::Rules>)) {
+    let top: &F = &configuration[configuration.len() - 1];
+    automaton.terminal_transitions(top.state(), terminal, |dest, rules, entered| {
+        let mut next: Vec<F> = configuration.to_vec();
+        next[configuration.len() - 1] = top.moved_to(dest);
+        let atomic_rules: Option<&A::Rules> = entered.and_then(|(atomic_state, atomic_rules)| {
+            next.push(enter(atomic_state));
+            atomic_rules
+        });
+        f(next, rules, atomic_rules);
+    });
+}
+
+// Adds the closed configurations reachable from the given configuration by consuming a terminal.
+pub(crate) fn derive_into<A: TransitionLookup>(automaton: &A, configuration: &Configuration, terminal: Terminal, res: &mut HashSet<Configuration>) {
+    derive(automaton, configuration, terminal, |state| state, |next, _, _| close_into(automaton, next, res));
+}
+
+// A configuration accepts when its only state is accepting, i.e. there is nothing left to return to.
+pub(crate) fn is_accepting_configuration<A: TransitionLookup>(automaton: &A, configuration: &Configuration) -> bool {
+    configuration.len() == 1 && automaton.accepting(configuration[0])
+}
+
+// Determines whether the input is accepted, following every configuration of the automaton at once.
+pub(crate) fn recognize<A: TransitionLookup>(automaton: &A, input: &[Terminal]) -> bool {
+    let mut configurations: HashSet<Configuration> = HashSet::new();
+    close_into(automaton, vec![automaton.start_state()], &mut configurations);
+
+    for terminal in input {
+        let mut next_configurations: HashSet<Configuration> = HashSet::new();
+        for configuration in &configurations {
+            derive_into(automaton, configuration, *terminal, &mut next_configurations);
+        }
+        configurations = next_configurations;
+        if configurations.is_empty() {
+            return false;
+        }
+    }
+    configurations.iter().any(|configuration| is_accepting_configuration(automaton, configuration))
+}
+
+// Returns the rules collected along one accepting path for the input, or None if it is not accepted. Of the paths
+// reaching the same configuration only the first is kept, and of the accepting ones the smallest configuration is
+// taken, so the same path is always returned. Empty rules are left out.
+pub(crate) fn parse<A: TransitionLookup>(automaton: &A, input: &[Terminal]) -> Option<Vec<A::Rules>> {
+    let mut configurations: BTreeMap<Configuration, Vec<A::Rules>> = BTreeMap::new();
+    close_with_rules(automaton, vec![automaton.start_state()], Vec::new(), &mut configurations);
+
+    for terminal in input {
+        let mut next_configurations: BTreeMap<Configuration, Vec<A::Rules>> = BTreeMap::new();
+        for (configuration, trail) in &configurations {
+            derive(automaton, configuration, *terminal, |state| state, |next, rules, atomic_rules| {
+                let added: Vec<&A::Rules> = std::iter::once(rules).chain(atomic_rules).collect();
+                close_with_rules(automaton, next, with_rules(automaton, trail, &added), &mut next_configurations);
+            });
+        }
+        configurations = next_configurations;
+        if configurations.is_empty() {
+            return None;
+        }
+    }
+    configurations.into_iter()
+        .find(|(configuration, _)| is_accepting_configuration(automaton, configuration))
+        .map(|(_, trail)| trail)
+}
+
+// Adds the configuration and every configuration reachable from it without consuming a terminal to the closure,
+// keeping for every configuration the rules added on the way with the smallest key, the first found on a tie. Empty
+// rules are left out.
+fn close_by_key<A: TransitionLookup, K: Ord>(automaton: &A, configuration: Configuration, closure: &mut BTreeMap<Configuration, Vec<A::Rules>>, key: &impl Fn(&[A::Rules]) -> K) {
+    close(automaton, configuration, Vec::new(), |next, added| {
+        if closure.get(&next).is_some_and(|kept| key(kept) <= key(&added)) {
+            return false;
+        }
+        closure.insert(next, added);
+        true
+    }, |added, _, rules| with_rules(automaton, added, &[rules]));
+}
+
+// Returns the rules collected along the first accepting path for the input found by a depth-first search, or None if
+// it is not accepted. A move of the search closes the configuration and consumes the next terminal, and the moves from
+// a configuration are tried in the order of the key of the rules they add, smallest first, or in the order in which
+// the automaton offers them on a tie. A configuration reached again at the same position in the input is not searched
+// again, as it cannot lead to acceptance once it did not. Empty rules are left out.
+pub(crate) fn parse_first<A: TransitionLookup, K: Ord>(automaton: &A, input: &[Terminal], key: impl Fn(&[A::Rules]) -> K) -> Option<Vec<A::Rules>> {
+    let mut visited: HashSet<(usize, Configuration)> = HashSet::new();
+    let mut to_visit: Vec<(usize, Configuration, Vec<A::Rules>)> = vec![(0, vec![automaton.start_state()], Vec::new())];
+
+    while let Some((position, configuration, trail)) = to_visit.pop() {
+        if !visited.insert((position, configuration.clone())) {
+            continue;
+        }
+        let mut closure: BTreeMap<Configuration, Vec<A::Rules>> = BTreeMap::new();
+        close_by_key(automaton, configuration, &mut closure, &key);
+
+        let Some(terminal) = input.get(position) else {
+            return closure.into_iter()
+                .filter(|(closed, _)| is_accepting_configuration(automaton, closed))
+                .min_by_key(|(_, added)| key(added))
+                .map(|(_, added)| with_rules(automaton, &trail, &added.iter().collect::<Vec<&A::Rules>>()));
+        };
+        let mut moves: Vec<(Configuration, Vec<A::Rules>)> = Vec::new();
+        for (closed, added) in &closure {
+            derive(automaton, closed, *terminal, |state| state, |next, rules, atomic_rules| {
+                let step: Vec<&A::Rules> = added.iter().chain(std::iter::once(rules)).chain(atomic_rules).collect();
+                moves.push((next, with_rules(automaton, &[], &step)));
+            });
+        }
+        moves.sort_by_key(|(_, added)| key(added));
+        // the stack is visited from its end, so the first move is pushed last
+        for (next, added) in moves.into_iter().rev() {
+            to_visit.push((position + 1, next, trail.iter().cloned().chain(added).collect()));
+        }
+    }
+    None
+}
+
+// Extends the trail by the added rules, leaving out empty rules.
+fn with_rules<A: TransitionLookup>(automaton: &A, trail: &[A::Rules], added: &[&A::Rules]) -> Vec<A::Rules> {
+    trail.iter().chain(added.iter().cloned()).filter(|rules| automaton.carries_rules(rules)).cloned().collect()
+}
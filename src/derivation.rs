@@ -0,0 +1,135 @@
+//! # Derivation
+//!
+//! The `derivation` module contains functions which operate on the derivations produced by the parser, i.e. the
+//! sequences of derivation rules collected in the completed parses.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::word::*;
+use crate::regex::*;
+
+/// An error while rendering a derivation, see `render_derivation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivationError {
+    /// The rule, at the given position among all rules of the derivation, is for a nonterminal which does not occur in
+    /// the sentential form it is applied to.
+    NotApplicable(usize, Rule),
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerivationError::NotApplicable(index, (nonterminal, _)) => write!(f, "rule {} rewrites nonterminal {}, which does not occur in the sentential form", index, nonterminal),
+        }
+    }
+}
+
+/// Renders a derivation as the sentential forms it passes through, e.g. `S -> aSb -> aaSbb -> aabb`.
+/// The rules within a single `Rules` are applied from first to last, after which the rules of the next `Rules` in
+/// the slice are applied. Every rule rewrites the leftmost occurrence of its nonterminal. Epsilon symbols are left out
+/// of the sentential forms, an empty sentential form is rendered as `e`. Fails on the first rule whose nonterminal
+/// does not occur in the sentential form.
+pub fn render_derivation(start_nt: Nonterminal, rules: &[Rules]) -> Result<String, DerivationError> {
+    let mut sentential_form: Word = vec![Symbol::Nonterminal(start_nt)];
+    let mut res: String = sentential_form_to_string(&sentential_form);
+
+    for (index, (nonterminal, word)) in rules.iter().flatten().enumerate() {
+        let pos: usize = sentential_form.iter().position(|symbol| *symbol == Symbol::Nonterminal(*nonterminal))
+            .ok_or_else(|| DerivationError::NotApplicable(index, (*nonterminal, word.clone())))?;
+        rewrite(&mut sentential_form, pos, word);
+        res.push_str(" -> ");
+        res.push_str(&sentential_form_to_string(&sentential_form));
+    }
+    Ok(res)
+}
+
+/// Determines whether the rules form a leftmost derivation of the input from the start nonterminal. The rules are
+/// replayed in the order of `render_derivation`: those within a single `Rules` from first to last, then those of the
+/// next `Rules` in the slice, every rule rewriting the leftmost nonterminal of the sentential form. The replay fails as
+/// soon as a rule is for a different nonterminal than the leftmost one, or there is no nonterminal left to rewrite,
+/// and succeeds if all rules are applied and the sentential form is the input.
+pub fn verify_derivation(start_nt: Nonterminal, rules: &[Rules], input: &[Terminal]) -> bool {
+    let mut sentential_form: Word = vec![Symbol::Nonterminal(start_nt)];
+    for (nonterminal, word) in rules.iter().flatten() {
+        match leftmost_nonterminal(&sentential_form) {
+            Some(pos) if sentential_form[pos] == Symbol::Nonterminal(*nonterminal) => rewrite(&mut sentential_form, pos, word),
+            _ => return false,
+        }
+    }
+    sentential_form.iter().cloned().eq(input.iter().map(|terminal| Symbol::Terminal(*terminal)))
+}
+
+/// Counts how often every rule is applied in a derivation, over the rules of all `Rules` in the slice. The keys are
+/// the rules as stored in `Rules`, i.e. pairs of a nonterminal and the word it is rewritten to.
+pub fn rule_histogram(derivation: &[Rules]) -> HashMap<Rule, usize> {
+    let mut res: HashMap<Rule, usize> = HashMap::new();
+    for rule in derivation.iter().flatten() {
+        *res.entry(rule.clone()).or_insert(0) += 1;
+    }
+    res
+}
+
+// The position of the leftmost nonterminal of the sentential form, if any.
+fn leftmost_nonterminal(sentential_form: &Word) -> Option<usize> {
+    sentential_form.iter().position(|symbol| matches!(symbol, Symbol::Nonterminal(_)))
+}
+
+// Replaces the symbol at the position by the word, leaving out epsilon.
+fn rewrite(sentential_form: &mut Word, pos: usize, word: &Word) {
+    let replacement: Word = word.iter().filter(|symbol| **symbol != Symbol::Epsilon).cloned().collect();
+    sentential_form.splice(pos..pos + 1, replacement);
+}
+
+fn sentential_form_to_string(sentential_form: &Word) -> String {
+    if sentential_form.is_empty() {
+        return Symbol::Epsilon.to_string();
+    }
+    sentential_form.iter().map(|symbol| symbol.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Builds a rule from a string in which uppercase letters are nonterminals, `e` is epsilon and the rest terminals.
+    fn rule(nonterminal: Nonterminal, word: &str) -> Rule {
+        (nonterminal, word.chars().map(|c| match c {
+            'e' => Symbol::Epsilon,
+            c if c.is_uppercase() => Symbol::Nonterminal(c),
+            c => Symbol::Terminal(c),
+        }).collect())
+    }
+
+    #[test]
+    fn render_derivation_test() {
+        assert_eq!(render_derivation('S', &[]), Ok("S".to_string()));
+        assert_eq!(render_derivation('S', &[vec![rule('S', "aSb"), rule('S', "aSb")], vec![rule('S', "e")]]), Ok("S -> aSb -> aaSbb -> aabb".to_string()));
+        assert_eq!(render_derivation('S', &[vec![rule('S', "SbSc"), rule('S', "Sa"), rule('S', "a"), rule('S', "a")]]), Ok("S -> SbSc -> SabSc -> aabSc -> aabac".to_string()));
+        assert_eq!(render_derivation('S', &[vec![rule('S', "e")]]), Ok("S -> e".to_string()));
+        assert_eq!(render_derivation('S', &[vec![rule('A', "a")]]), Err(DerivationError::NotApplicable(0, rule('A', "a"))));
+        assert_eq!(render_derivation('S', &[vec![rule('S', "a")], vec![rule('S', "b")]]), Err(DerivationError::NotApplicable(1, rule('S', "b"))));
+    }
+
+    #[test]
+    fn verify_derivation_test() {
+        let derivation: Vec<Rules> = vec![vec![rule('S', "aSb"), rule('S', "aSb")], vec![rule('S', "e")]];
+        assert!(verify_derivation('S', &derivation, &['a', 'a', 'b', 'b']));
+        assert!(!verify_derivation('S', &derivation, &['a', 'b']));
+        assert!(!verify_derivation('S', &derivation[..1], &['a', 'a', 'b', 'b']));
+        assert!(!verify_derivation('S', &[vec![rule('A', "a")]], &['a']));
+        assert!(verify_derivation('S', &[vec![rule('S', "e")]], &[]));
+        assert!(verify_derivation('S', &[vec![rule('S', "E"), rule('E', "EQF"), rule('E', "F"), rule('F', "a")], vec![rule('Q', "-"), rule('F', "a")]], &['a', '-', 'a']));
+        // the same rules in a different order, which is not a leftmost derivation
+        assert!(!verify_derivation('S', &[vec![rule('E', "EQF"), rule('F', "a"), rule('Q', "-"), rule('S', "E"), rule('E', "F"), rule('F', "a")]], &['a', '-', 'a']));
+        assert!(!verify_derivation('S', &[vec![rule('S', "E"), rule('E', "EQF"), rule('Q', "-"), rule('E', "F"), rule('F', "a"), rule('F', "a")]], &['a', '-', 'a']));
+        assert!(!verify_derivation('S', &[vec![rule('S', "E"), rule('E', "EQF"), rule('E', "F"), rule('F', "a"), rule('Q', "-"), rule('F', "a"), rule('F', "a")]], &['a', '-', 'a']));
+    }
+
+    #[test]
+    fn rule_histogram_test() {
+        assert!(rule_histogram(&[]).is_empty());
+        let histogram: HashMap<Rule, usize> = rule_histogram(&[vec![rule('S', "aSb"), rule('S', "aSb")], vec![rule('S', "e")], vec![]]);
+        assert_eq!(histogram, HashMap::from([(rule('S', "aSb"), 2), (rule('S', "e"), 1)]));
+    }
+}
@@ -1,13 +1,43 @@
-use std::collections::{HashSet, HashMap, VecDeque};
+use std::collections::{HashSet, HashMap, BTreeSet, BTreeMap, VecDeque};
+use std::sync::Arc;
 use std::fmt;
+#[cfg(feature = "std-fs")]
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
 
 use crate::word::*;
 use crate::regex::*;
+use crate::grammar::{unused_terminals, self_embedding_nonterminals, nullable_nonterminals};
+use crate::recognizer::*;
+use crate::compiled_fsa::*;
+use crate::configuration::{self, TransitionLookup, Entered, close_into, derive_into, is_accepting_configuration};
+#[cfg(feature = "std-fs")]
+use crate::grammar::Grammar;
+#[cfg(feature = "std-fs")]
+use crate::grammar_import::parse_grammar_yacc_like;
+#[cfg(feature = "petgraph")]
+use petgraph::graph::{Graph, NodeIndex};
 
 pub type State = usize;
 
+// A configuration of the automaton while consuming terminals. The last state is the current state, the states before it
+// are the states to continue from once the current state is accepting, similar to the edges kept by a Language.
+pub type Configuration = Vec<State>;
+
+// A state of the product with a deterministic automaton, see FiniteStateAutomaton::restrict_by.
+type ProductState = (State, State, Option<State>);
+// An atomic language of the product automaton: its key, its product state and its derivation rules.
+type ProductAtomic = ((Symbol, Terminal), ProductState, HashSet<Rules>);
+// Whether a state is accepting and its outgoing transitions, equal for states which are merged by dedup_atomic.
+type StateSignature = (bool, BTreeSet<(Symbol, State, Rules)>);
+// The block of a state and its transitions into blocks, equal for states which stay in one block in bisimilar.
+type BlockSignature<'a> = (usize, BTreeSet<(Symbol, &'a Rules, usize)>);
+// A configuration of FiniteStateAutomaton::parse_spanned, every state paired with the position in the input at which
+// its atomic language was entered.
+type SpannedConfiguration = Vec<(State, usize)>;
+// The rules collected by parse_spanned, each with the positions of the first and past the last terminal they cover.
+type Trail = Vec<(Rules, usize, usize)>;
+
 #[derive(Debug)]
 pub struct FiniteStateAutomaton {
     states: HashSet<State>,
@@ -17,76 +47,619 @@ pub struct FiniteStateAutomaton {
     atomic_to_state: HashMap<(Symbol, Terminal), (State, HashSet<Rules>)>,
 }
 
-impl fmt::Display for FiniteStateAutomaton {
+/// An error while building an automaton, see FiniteStateAutomaton::build_fsa_regular and
+/// FiniteStateAutomaton::build_fsa_from_file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The nonterminal is self-embedding, so the language need not be regular.
+    NonRegular(Nonterminal),
+    /// The grammar file could not be read, with the path and the message of the underlying error.
+    Io(String, String),
+    /// The grammar is malformed, with the line (starting at 1) at which the error occurred and its message.
+    Parse(usize, String),
+    /// The state is used by the parts of an automaton, but it is not one of its states.
+    MissingState(State),
+    /// A configuration of the automaton got deeper than the given limit, its number of states, so it was not turned into
+    /// a deterministic finite automaton, see FiniteStateAutomaton::difference. This does not mean that its language is
+    /// not regular.
+    ConfigurationDepthExceeded(usize),
+    /// The atomic language of the symbol and terminal was built again while the automaton already has one, see
+    /// OverwritePolicy::Error.
+    AtomicConflict(Symbol, Terminal),
+}
+
+impl BuildError {
+    /// The line of the grammar at which the error occurred, if the error refers to a line.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            BuildError::Parse(line, _) => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "States: ")?;
-        for state in &self.states {
-            write!(f, "{} ", state)?;
+        match self {
+            BuildError::NonRegular(nt) => write!(f, "nonterminal {} is self-embedding, the language need not be regular", nt),
+            BuildError::Io(path, message) => write!(f, "cannot read {}: {}", path, message),
+            BuildError::Parse(line, message) => write!(f, "line {}: {}", line, message),
+            BuildError::MissingState(state) => write!(f, "state {} is used, but it is not a state of the automaton", state),
+            BuildError::ConfigurationDepthExceeded(max_depth) => write!(f, "a configuration of the automaton is deeper than {} states, it was not made deterministic", max_depth),
+            BuildError::AtomicConflict(symbol, terminal) => write!(f, "the atomic language of {} and {} already exists", symbol, terminal),
         }
-        write!(f, "\nAccepting states: ")?;
-        for state in &self.accepting_states {
-            write!(f, "{} ", state)?;
+    }
+}
+
+/// An error while writing an automaton to a DOT file, see FiniteStateAutomaton::to_dot.
+#[derive(Debug)]
+pub enum DotError {
+    /// Writing the file failed.
+    Io(io::Error),
+    /// The transition, given as source, symbol and destination, refers to a state which is not a state of the automaton.
+    MissingTransitionState(State, Symbol, State),
+    /// The atomic language of the symbol and terminal starts in a state which is not a state of the automaton.
+    MissingAtomicState(Symbol, Terminal, State),
+}
+
+impl From<io::Error> for DotError {
+    fn from(err: io::Error) -> DotError {
+        DotError::Io(err)
+    }
+}
+
+impl fmt::Display for DotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DotError::Io(err) => write!(f, "cannot write the DOT file: {}", err),
+            DotError::MissingTransitionState(source, symbol, dest) => write!(f, "transition {} -{}-> {} refers to a state which does not exist", source, symbol, dest),
+            DotError::MissingAtomicState(symbol, terminal, state) => write!(f, "atomic language [{}]^({}) starts in state {}, which does not exist", symbol, terminal, state),
         }
-        write!(f, "\nStart state: {}\n", &self.start)?;
-        write!(f, "Transitions:\n")?;
-        for (state, transition_list) in &self.transitions {
-            write!(f, "{}: ", state)?;
-            for (symbol, destinations) in transition_list {
-                for (state, _) in destinations {
-                    write!(f, "|to {} via {}| ", state, symbol)?;
-                }
-            }
-            write!(f, "\n")?;
+    }
+}
+
+impl std::error::Error for DotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DotError::Io(err) => Some(err),
+            _ => None,
         }
-        write!(f, "Transition to rules:\n")?;
-        for (state, transition_list) in &self.transitions {
+    }
+}
+
+/// An example for which recognition did not give the expected result, see FiniteStateAutomaton::check_examples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExampleFailure {
+    pub input: Vec<Terminal>,
+    pub expected: bool,
+    pub actual: bool,
+}
+
+/// The size of an automaton, see FiniteStateAutomaton::stats. A transition is counted once for every set of rules it
+/// carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FsaStats {
+    pub states: usize,
+    pub accepting_states: usize,
+    pub transitions: usize,
+    pub atomic_entries: usize,
+}
+
+/// The change in size between two automata, see `size_delta`. A negative field means the second automaton is smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FsaStatsDelta {
+    pub states: isize,
+    pub accepting_states: isize,
+    pub transitions: isize,
+    pub atomic_entries: isize,
+}
+
+/// Returns the change in size from `before` to `after`, e.g. the result of a transformation of the automaton.
+pub fn size_delta(before: &FsaStats, after: &FsaStats) -> FsaStatsDelta {
+    let delta = |before: usize, after: usize| -> isize { after as isize - before as isize };
+    FsaStatsDelta {
+        states: delta(before.states, after.states),
+        accepting_states: delta(before.accepting_states, after.accepting_states),
+        transitions: delta(before.transitions, after.transitions),
+        atomic_entries: delta(before.atomic_entries, after.atomic_entries),
+    }
+}
+
+/// Writes a DOT graph of the differences between the automata `a` and `b`. The states of both automata are numbered
+/// canonically, in the order in which a breadth-first search finds them from the start state and then from the atomic
+/// languages sorted by key, following the transitions sorted by symbol, rules and the bisimulation block of their
+/// destination, see `bisimilar`. States with the same number are drawn as one node, which is only an approximate
+/// correspondence: a change early in the search shifts the numbers of the states found after it. Transitions and atomic languages only in `b` are drawn green, those only in `a` red,
+/// and those in both black, where rules are not compared. A state only in one of the automata is colored likewise,
+/// and drawn as accepting if it is accepting in `b`, or only in `a` and accepting there.
+pub fn write_dot_diff<W: Write>(a: &FiniteStateAutomaton, b: &FiniteStateAutomaton, w: &mut W) -> io::Result<()> {
+    let (numbers_a, numbers_b): (HashMap<State, usize>, HashMap<State, usize>) = (a.canonical_numbering(), b.canonical_numbering());
+    let states = |fsa: &FiniteStateAutomaton, numbers: &HashMap<State, usize>| -> BTreeMap<usize, bool> {
+        numbers.iter().map(|(state, number)| (*number, fsa.is_accepting(state))).collect()
+    };
+    let edges = |fsa: &FiniteStateAutomaton, numbers: &HashMap<State, usize>| -> BTreeSet<(usize, Symbol, usize)> {
+        let mut res: BTreeSet<(usize, Symbol, usize)> = BTreeSet::new();
+        for (source, transition_list) in &fsa.transitions {
             for (symbol, destinations) in transition_list {
-                for (_, rules) in destinations {
-                    write!(f, "{}: {}: ", state, symbol)?;
-                    for (nonterminal, rule) in rules {
-                        write!(f, "[{} -> ", nonterminal)?;
-                        for symbol in rule {
-                            write!(f, "{}", symbol)?;
-                        }
-                        write!(f, "] ")?;
+                for (dest, _) in destinations {
+                    if let (Some(source), Some(dest)) = (numbers.get(source), numbers.get(dest)) {
+                        res.insert((*source, *symbol, *dest));
                     }
-                    write!(f, "\n")?;
                 }
             }
         }
-        write!(f, "Atomic to state:\n")?;
-        for ((symbol, terminal), (state, rule_set)) in &self.atomic_to_state {
-            write!(f, "[{}]^({}) {} ", symbol, terminal, state)?;
-            for rules in rule_set {
-                write!(f, "| ")?;
-                for (nonterminal, rule) in rules {
-                    write!(f, "[{} -> ", nonterminal)?;
-                    for symbol in rule {
-                        write!(f, "{}", symbol)?;
-                    }
-                    write!(f, "] ")?;
+        res
+    };
+    let atomics = |fsa: &FiniteStateAutomaton, numbers: &HashMap<State, usize>| -> BTreeSet<(Symbol, Terminal, usize)> {
+        fsa.atomic_to_state.iter().filter_map(|((symbol, terminal), (state, _))| numbers.get(state).map(|number| (*symbol, *terminal, *number))).collect()
+    };
+    let color = |in_a: bool, in_b: bool| -> &str {
+        match (in_a, in_b) {
+            (true, false) => " color=red fontcolor=red",
+            (false, true) => " color=green fontcolor=green",
+            _ => "",
+        }
+    };
+
+    let (states_a, states_b): (BTreeMap<usize, bool>, BTreeMap<usize, bool>) = (states(a, &numbers_a), states(b, &numbers_b));
+    let (edges_a, edges_b) = (edges(a, &numbers_a), edges(b, &numbers_b));
+    let (atomics_a, atomics_b) = (atomics(a, &numbers_a), atomics(b, &numbers_b));
+
+    writeln!(w, "digraph G {{")?;
+    let all_states: BTreeSet<&usize> = states_a.keys().chain(states_b.keys()).collect();
+    for number in all_states {
+        let accepting: bool = states_b.get(number).or(states_a.get(number)).cloned().unwrap_or(false);
+        let shape: &str = if accepting { "doublecircle" } else { "circle" };
+        writeln!(w, "{} [ shape={}{} ]", number, shape, color(states_a.contains_key(number), states_b.contains_key(number)))?;
+    }
+    for (symbol, terminal, number) in atomics_a.union(&atomics_b) {
+        let key: (Symbol, Terminal, usize) = (*symbol, *terminal, *number);
+        let style: &str = color(atomics_a.contains(&key), atomics_b.contains(&key));
+        writeln!(w, "\"[{}]^({})\" [ shape=rectangle ]", symbol, terminal)?;
+        writeln!(w, "\"[{}]^({})\" -> {} [{} ]", symbol, terminal, number, style)?;
+    }
+    for (source, symbol, dest) in edges_a.union(&edges_b) {
+        let edge: (usize, Symbol, usize) = (*source, *symbol, *dest);
+        writeln!(w, "{} -> {} [ label=\"{}\"{} ]", source, dest, symbol, color(edges_a.contains(&edge), edges_b.contains(&edge)))?;
+    }
+    writeln!(w, "}}")
+}
+
+/// Visits the parts of an automaton, see FiniteStateAutomaton::accept. All methods do nothing by default, so a visitor
+/// only needs to implement the methods for the parts it is interested in.
+pub trait FsaVisitor {
+    fn visit_state(&mut self, _state: &State, _accepting: bool) {}
+    fn visit_transition(&mut self, _source: &State, _symbol: &Symbol, _dest: &State, _rules: &Rules) {}
+    fn visit_atomic(&mut self, _symbol: &Symbol, _terminal: &Terminal, _state: &State, _rules_set: &HashSet<Rules>) {}
+}
+
+/// Determines how the rules carried by transitions and atomic languages are labeled when rendering the automaton.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuleLabels {
+    /// All rules are shown.
+    #[default]
+    Full,
+    /// Only the first given number of rules are shown, followed by an ellipsis if there are more.
+    Truncate(usize),
+    /// Only the number of rules is shown.
+    Count,
+}
+
+/// Determines how atomic languages which are built again are combined with the ones the automaton already has, see
+/// FiniteStateAutomaton::rebuild_atomic_with. Building an automaton from scratch never builds an atomic language twice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// The new atomic language replaces the existing one, like `rebuild_atomic`.
+    #[default]
+    Replace,
+    /// Both atomic languages are kept: the atomic language starts in a new state with an epsilon transition to each of
+    /// them, so either can be taken, and it carries the rules of both.
+    Merge,
+    /// The rebuild is aborted with BuildError::AtomicConflict, leaving the automaton as is.
+    Error,
+}
+
+/// Options used when rendering the automaton, either through `display_with` or `to_dot_with`.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub rule_labels: RuleLabels,
+    /// Only used by `to_dot_with`: draw the rules of a transition as a separate box between its source and destination,
+    /// instead of in the label of the transition.
+    pub rules_as_nodes: bool,
+}
+
+/// Options used when building the automaton through `build_fsa_with`. The default options build the same automaton as
+/// `build_fsa`. There is no OverwritePolicy among them, as building inserts every atomic language exactly once and
+/// a terminal override only moves the start of the existing ones, see `rebuild_atomic_with` for rebuilding them.
+#[derive(Debug, Clone, Default)]
+pub struct BuildConfig {
+    /// Gives terminals a sub-structure: after the terminal, the automaton has to consume the given terminals before it
+    /// continues. By default the atomic language of a terminal is the epsilon state, with an override it starts in a
+    /// non-accepting state from which the terminals lead to the epsilon state. The terminals of a sub-structure are
+    /// not given a sub-structure themselves, an empty sub-structure leaves the terminal as is.
+    pub terminal_overrides: HashMap<Terminal, Vec<Terminal>>,
+    /// The expected number of states, used to allocate the maps holding states up front instead of growing them while
+    /// building. This only affects the performance of building, not the automaton.
+    pub estimated_states: Option<usize>,
+    /// Classes of terminals excluding the given ones: the placeholder terminal, when used in the rules, stands for any
+    /// terminal of the alphabet except the excluded terminals and the placeholders themselves. The placeholders are
+    /// not part of the alphabet of the automaton, each class is lowered into a transition on every terminal of the
+    /// class instead, while the derivations contain the rules with the placeholder.
+    pub terminal_classes: HashMap<Terminal, HashSet<Terminal>>,
+    /// Skips the atomic languages of terminals which do not appear in any rule, as no input containing them is
+    /// accepted anyway. By default they are kept like in `build_fsa`, as the epsilon state like any other terminal.
+    pub skip_unused_terminals: bool,
+}
+
+impl RenderOptions {
+    // Produces the labels for a sequence of rules, one label per rule that should be shown.
+    fn rule_labels(&self, rules: &Rules) -> Vec<String> {
+        match self.rule_labels {
+            RuleLabels::Full => rules.iter().map(rule_to_string).collect(),
+            RuleLabels::Truncate(max) => {
+                let mut res: Vec<String> = rules.iter().take(max).map(rule_to_string).collect();
+                if rules.len() > max {
+                    res.push(String::from("..."));
+                }
+                res
+            },
+            RuleLabels::Count => {
+                if rules.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![format!("{} rules", rules.len())]
+                }
+            },
+        }
+    }
+}
+
+// Displays an automaton with the given render options, see FiniteStateAutomaton::display_with.
+pub struct FsaDisplay<'a> {
+    finite_state_automaton: &'a FiniteStateAutomaton,
+    options: &'a RenderOptions,
+}
+
+impl fmt::Display for FsaDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.finite_state_automaton.fmt_with(f, self.options)
+    }
+}
+
+/// A deterministic finite state automaton, which recognizes inputs with a single configuration, see
+/// FiniteStateAutomaton::as_deterministic.
+pub struct DeterministicFsa<'a> {
+    finite_state_automaton: &'a FiniteStateAutomaton,
+}
+
+impl DeterministicFsa<'_> {
+    /// Recognizes the input like FiniteStateAutomaton::recognize. Since there is only one way to consume every
+    /// terminal, no sets of configurations are needed, which makes recognizing long inputs considerably faster.
+    pub fn recognize(&self, input: &[Terminal]) -> bool {
+        let fsa: &FiniteStateAutomaton = self.finite_state_automaton;
+        let mut configuration: Configuration = vec![fsa.start];
+        // the states reachable from the current state by epsilon transitions, reused for every terminal
+        let mut closure: Vec<State> = Vec::new();
+
+        for terminal in input {
+            loop {
+                fsa.epsilon_closure_into(configuration[configuration.len() - 1], &mut closure);
+                let next: Option<(State, Option<State>)> = closure.iter()
+                    .flat_map(|curr| fsa.transitions.get(curr).into_iter().flatten())
+                    .find_map(|(symbol, destinations)| {
+                        let atomic_state: Option<State> = match symbol {
+                            Symbol::Terminal(t) if t == terminal => None,
+                            Symbol::Nonterminal(_) => Some(fsa.atomic_to_state.get(&(*symbol, *terminal))?.0),
+                            _ => return None,
+                        };
+                        destinations.iter().next().map(|(dest, _)| (*dest, atomic_state))
+                    });
+                match next {
+                    Some((dest, atomic_state)) => {
+                        let top: usize = configuration.len() - 1;
+                        configuration[top] = dest;
+                        configuration.extend(atomic_state);
+                        break;
+                    },
+                    None if configuration.len() > 1 && closure.iter().any(|curr| fsa.is_accepting(curr)) => {
+                        configuration.pop();
+                    },
+                    None => return false,
                 }
-                write!(f, "|")?;
             }
-            write!(f, "\n")?;
         }
-        Ok(())
+
+        loop {
+            fsa.epsilon_closure_into(configuration[configuration.len() - 1], &mut closure);
+            let accepting: bool = closure.iter().any(|curr| fsa.is_accepting(curr));
+            if configuration.len() == 1 || !accepting {
+                return configuration.len() == 1 && accepting;
+            }
+            configuration.pop();
+        }
+    }
+}
+
+impl fmt::Display for FiniteStateAutomaton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, &RenderOptions::default())
     }
 }
 
 impl FiniteStateAutomaton {
+
+    /// Returns a wrapper which displays the automaton using the given render options.
+    pub fn display_with<'a>(&'a self, options: &'a RenderOptions) -> FsaDisplay<'a> {
+        FsaDisplay{finite_state_automaton: self, options}
+    }
+
+    // Lists the states in sorted order, with the outgoing transitions of every state and their rules directly below it.
+    fn fmt_with(&self, f: &mut fmt::Formatter, options: &RenderOptions) -> fmt::Result {
+        let states: BTreeSet<&State> = self.states.iter().collect();
+        let accepting_states: BTreeSet<&State> = self.accepting_states.iter().collect();
+        write!(f, "States:")?;
+        for state in &states {
+            write!(f, " {}", state)?;
+        }
+        write!(f, "\nAccepting states:")?;
+        for state in &accepting_states {
+            write!(f, " {}", state)?;
+        }
+        writeln!(f, "\nStart state: {}", &self.start)?;
+        writeln!(f, "Transitions:")?;
+        for state in &states {
+            let Some(transition_list) = self.transitions.get(state) else {
+                continue;
+            };
+            writeln!(f, "{}:", state)?;
+            let transitions: BTreeSet<(&Symbol, &State, &Rules)> = transition_list.iter()
+                .flat_map(|(symbol, destinations)| destinations.iter().map(move |(dest, rules)| (symbol, dest, rules)))
+                .collect();
+            for (symbol, dest, rules) in transitions {
+                write!(f, "  --{}--> {}", symbol, dest)?;
+                for label in options.rule_labels(rules) {
+                    write!(f, " {}", label)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "Atomic to state:")?;
+        let atomics: BTreeMap<&(Symbol, Terminal), &(State, HashSet<Rules>)> = self.atomic_to_state.iter().collect();
+        for ((symbol, terminal), (state, rules_set)) in atomics {
+            write!(f, "[{}]^({}) {}", symbol, terminal, state)?;
+            for rules in rules_set.iter().collect::<BTreeSet<&Rules>>() {
+                write!(f, " |")?;
+                for label in options.rule_labels(rules) {
+                    write!(f, " {}", label)?;
+                }
+                write!(f, " |")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
     
     pub fn build_fsa(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> FiniteStateAutomaton {
+        FiniteStateAutomaton::build_fsa_for(terminals, start_nt, rules, None, true, None)
+    }
+
+    /// Builds the automaton like `build_fsa` and renumbers its states canonically, in the order in which the
+    /// breadth-first search of `write_dot_diff` finds them, so the start state becomes 0 and the states are numbered
+    /// densely. The numbers only depend on the structure of the automaton, not on the order in which it was built, which
+    /// makes the result suitable for comparing against golden output. States reached from one state by several
+    /// transitions with the same symbol and rules are numbered in the order of their bisimulation blocks, see
+    /// `bisimilar`, and only bisimilar ones in the order of their old numbers.
+    #[cfg(feature = "test-utils")]
+    pub fn build_fsa_canonical(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> FiniteStateAutomaton {
+        let fsa: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa(terminals, start_nt, rules);
+        let numbers: HashMap<State, usize> = fsa.canonical_numbering();
+        // every state of a built automaton is reached from the start state or an atomic language, so every state is numbered
+        let unnumbered: Vec<&State> = fsa.states.iter().chain(&fsa.accepting_states).chain(fsa.transitions.keys()).filter(|state| !numbers.contains_key(state)).collect();
+        assert!(unnumbered.is_empty(), "states not reached from the start state or an atomic language: {:?}", unnumbered);
+        let renumber = |state: &State| -> State { numbers[state] };
+        FiniteStateAutomaton{
+            states: fsa.states.iter().map(renumber).collect(),
+            accepting_states: fsa.accepting_states.iter().map(renumber).collect(),
+            start: renumber(&fsa.start),
+            transitions: fsa.transitions.iter()
+                .map(|(state, transition_list)| (renumber(state), transition_list.iter()
+                    .map(|(symbol, destinations)| (*symbol, destinations.iter().map(|(dest, rules)| (renumber(dest), rules.clone())).collect()))
+                    .collect()))
+                .collect(),
+            atomic_to_state: fsa.atomic_to_state.iter().map(|(key, (state, rules_set))| (*key, (renumber(state), rules_set.clone()))).collect(),
+        }
+    }
+
+    /// Builds the automaton like `build_fsa`, but without derivation rules: all transitions and atomic languages carry
+    /// empty rules, so they are not concatenated while building. The automaton has the same states and recognizes the
+    /// same language, but it cannot be used to parse. Unlike `to_recognizer`, the rules are never built in the first
+    /// place, and the result is still a FiniteStateAutomaton.
+    pub fn build_recognizer(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> FiniteStateAutomaton {
+        FiniteStateAutomaton::build_fsa_for(terminals, start_nt, rules, None, false, None)
+    }
+
+    /// Builds the automaton like `build_fsa`, applying the given options.
+    pub fn build_fsa_with(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, config: &BuildConfig) -> FiniteStateAutomaton {
+        let mut res: FiniteStateAutomaton = if config.terminal_classes.is_empty() {
+            FiniteStateAutomaton::build_fsa_for(terminals, start_nt, rules, None, true, config.estimated_states)
+        } else {
+            let alphabet: HashSet<Terminal> = terminals.iter().filter(|terminal| !config.terminal_classes.contains_key(terminal)).cloned().collect();
+            let atomic_regex: Regex = Regex::new_with_classes(&alphabet, rules, &config.terminal_classes);
+            FiniteStateAutomaton::build_fsa_from_regex(&atomic_regex, &alphabet, start_nt, rules, None, true, config.estimated_states)
+        };
+        if config.skip_unused_terminals {
+            // the terminals of a class used in the rules are used as well, though they do not appear in any rule
+            let used_classes: Vec<&HashSet<Terminal>> = config.terminal_classes.iter()
+                .filter(|(placeholder, _)| unused_terminals(&HashSet::from([**placeholder]), rules).is_empty())
+                .map(|(_, excluded)| excluded)
+                .collect();
+            for terminal in unused_terminals(terminals, rules) {
+                if !config.terminal_classes.contains_key(&terminal) && used_classes.iter().all(|excluded| excluded.contains(&terminal)) {
+                    res.atomic_to_state.remove(&(Symbol::Terminal(terminal), terminal));
+                }
+            }
+        }
+        let mut overrides: Vec<(&Terminal, &Vec<Terminal>)> = config.terminal_overrides.iter().filter(|(_, structure)| !structure.is_empty()).collect();
+        overrides.sort();
+        for (terminal, structure) in overrides {
+            res.override_terminal(*terminal, structure);
+        }
+        res
+    }
+
+    // Inserts the sub-structure after every transition on the terminal and before every atomic language of the
+    // terminal, including the atomic language of the terminal itself, see BuildConfig::terminal_overrides.
+    fn override_terminal(&mut self, terminal: Terminal, structure: &[Terminal]) {
+        let mut highest_state: State = self.states.iter().max().map_or(0, |state| *state);
+        let mut chains: HashMap<State, State> = HashMap::new();
+        let mut chain_to = |fsa: &mut FiniteStateAutomaton, end: State| -> State {
+            *chains.entry(end).or_insert_with(|| {
+                let mut dest: State = end;
+                for structure_terminal in structure.iter().rev() {
+                    highest_state += 1;
+                    fsa.states.insert(highest_state);
+                    fsa.transitions.entry(highest_state).or_default().entry(Symbol::Terminal(*structure_terminal)).or_default().insert((dest, Vec::new()));
+                    dest = highest_state;
+                }
+                dest
+            })
+        };
+
+        let mut sources: Vec<State> = self.transitions.iter()
+            .filter(|(_, transition_list)| transition_list.contains_key(&Symbol::Terminal(terminal)))
+            .map(|(state, _)| *state)
+            .collect();
+        sources.sort();
+        for source in sources {
+            let destinations: HashSet<(State, Rules)> = self.transitions.get_mut(&source).unwrap().remove(&Symbol::Terminal(terminal)).unwrap();
+            let mut new_destinations: HashSet<(State, Rules)> = HashSet::new();
+            for (dest, rules) in destinations {
+                new_destinations.insert((chain_to(self, dest), rules));
+            }
+            self.transitions.get_mut(&source).unwrap().insert(Symbol::Terminal(terminal), new_destinations);
+        }
+
+        let mut keys: Vec<(Symbol, Terminal)> = self.atomic_to_state.keys().filter(|(_, atomic_terminal)| *atomic_terminal == terminal).cloned().collect();
+        keys.sort();
+        for key in keys {
+            let state: State = self.atomic_to_state[&key].0;
+            let chain: State = chain_to(self, state);
+            self.atomic_to_state.get_mut(&key).unwrap().0 = chain;
+        }
+    }
+
+    /// Builds the automaton for the strings of the grammar followed by one of the stop terminals, like a line followed
+    /// by a line break. Rather than returning to the accepting state 1 after the start nonterminal, the start state
+    /// continues at a fresh state from which every stop terminal leads to a fresh accepting state, so an input is only
+    /// accepted once a string of the grammar is followed by a stop terminal. The start state itself is not accepting,
+    /// if the start nonterminal is nullable it has an epsilon transition to the fresh state instead. The derivations
+    /// are the same as those of `build_fsa`. Without stop terminals no input is accepted.
+    pub fn build_fsa_with_stops(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, stops: &HashSet<Terminal>) -> FiniteStateAutomaton {
+        let mut res: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa(terminals, start_nt, rules);
+        let highest_state: State = res.states.iter().max().map_or(0, |state| *state);
+        let (before_stop, after_stop): (State, State) = (highest_state + 1, highest_state + 2);
+        res.states.extend([before_stop, after_stop]);
+        res.accepting_states.insert(after_stop);
+
+        let mut start_transitions: HashMap<Symbol, HashSet<(State, Rules)>> = HashMap::from([(Symbol::Nonterminal(start_nt), HashSet::from([(before_stop, Vec::new())]))]);
+        if res.accepting_states.remove(&res.start) {
+            start_transitions.insert(Symbol::Epsilon, HashSet::from([(before_stop, Vec::new())]));
+        }
+        res.transitions.insert(res.start, start_transitions);
+        if !stops.is_empty() {
+            res.transitions.insert(before_stop, stops.iter().map(|stop| (Symbol::Terminal(*stop), HashSet::from([(after_stop, Vec::new())]))).collect());
+        }
+        res
+    }
+
+    /// Builds the automaton like `build_fsa`, but fails fast when the rules contain a self-embedding nonterminal,
+    /// returning the smallest one. The automaton itself handles self-embedding rules like `S -> aSb` by keeping a
+    /// stack of states while recognizing; this is for users who need a language which is guaranteed to be regular.
+    pub fn build_fsa_regular(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> Result<FiniteStateAutomaton, BuildError> {
+        match self_embedding_nonterminals(rules).first() {
+            Some(nt) => Err(BuildError::NonRegular(*nt)),
+            None => Ok(FiniteStateAutomaton::build_fsa(terminals, start_nt, rules)),
+        }
+    }
+
+    /// Reads a grammar in the syntax of `parse_grammar_yacc_like` from the file and builds its automaton. The automaton
+    /// is built separately from the one of the returned grammar, so either can be changed without affecting the other.
+    #[cfg(feature = "std-fs")]
+    pub fn build_fsa_from_file(path: &str) -> Result<(FiniteStateAutomaton, Grammar), BuildError> {
+        let input: String = std::fs::read_to_string(path).map_err(|err| BuildError::Io(path.to_string(), err.to_string()))?;
+        let grammar: Grammar = parse_grammar_yacc_like(&input).map_err(|err| BuildError::Parse(err.line, err.message))?;
+        Ok((FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &grammar.rules), grammar))
+    }
+
+    /// Builds the automaton like `build_fsa`, along with the provenance of its states: every state of an atomic
+    /// language is mapped to the key `(A, t)` of the atomic language `[A]^(t)` it belongs to. States which are shared by
+    /// several atomic languages are mapped to the smallest key. The start state and the shared final state 1 belong to
+    /// no atomic language and are left out.
+    pub fn build_fsa_with_provenance(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> (FiniteStateAutomaton, HashMap<State, (Nonterminal, Terminal)>) {
+        let finite_state_automaton: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa(terminals, start_nt, rules);
+        let mut provenance: HashMap<State, (Nonterminal, Terminal)> = HashMap::new();
+
+        let atomic_keys: BTreeMap<(Nonterminal, Terminal), State> = finite_state_automaton.atomic_to_state.iter()
+            .filter_map(|((symbol, terminal), (state, _))| match symbol {
+                Symbol::Nonterminal(nt) => Some(((*nt, *terminal), *state)),
+                _ => None,
+            })
+            .collect();
+        for (key, atomic_state) in atomic_keys {
+            let mut to_visit: Vec<State> = vec![atomic_state];
+            while let Some(curr) = to_visit.pop() {
+                if curr == 0 || curr == 1 || provenance.get(&curr).is_some_and(|owner| *owner <= key) {
+                    continue;
+                }
+                provenance.insert(curr, key);
+                for destinations in finite_state_automaton.transitions.get(&curr).into_iter().flat_map(|trans_list| trans_list.values()) {
+                    to_visit.extend(destinations.iter().map(|(dest, _)| *dest));
+                }
+            }
+        }
+        (finite_state_automaton, provenance)
+    }
+
+    /// Builds only the atomic languages of a single nonterminal. Returns the atomic entries of the nonterminal per
+    /// terminal, along with the automaton containing their states. The atomic languages are still calculated from
+    /// all derivation rules, but the atomic languages of other nonterminals are not turned into states.
+    pub fn build_atomic(terminals: &HashSet<Terminal>, nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> (HashMap<Terminal, (State, HashSet<Rules>)>, FiniteStateAutomaton) {
+        let finite_state_automaton: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa_for(terminals, nt, rules, Some(&HashSet::from([nt])), true, None);
+        let mut atomic_entries: HashMap<Terminal, (State, HashSet<Rules>)> = HashMap::new();
+        for ((symbol, terminal), entry) in &finite_state_automaton.atomic_to_state {
+            if *symbol == Symbol::Nonterminal(nt) {
+                atomic_entries.insert(*terminal, entry.clone());
+            }
+        }
+        (atomic_entries, finite_state_automaton)
+    }
+
+    // Builds the automaton, optionally turning only the atomic languages of the given nonterminals into states. Without
+    // tracking rules, every transition and atomic language is given empty rules instead. The maps holding states are
+    // allocated for the estimated number of states up front, if given.
+    fn build_fsa_for(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, only_nts: Option<&HashSet<Nonterminal>>, track_rules: bool, estimated_states: Option<usize>) -> FiniteStateAutomaton {
+        let atomic_regex: Regex = Regex::new(terminals, rules);
+        FiniteStateAutomaton::build_fsa_from_regex(&atomic_regex, terminals, start_nt, rules, only_nts, track_rules, estimated_states)
+    }
+
+    // Builds the automaton like build_fsa_for from atomic languages which have already been calculated from the rules.
+    pub(crate) fn build_fsa_from_regex(atomic_regex: &Regex, terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, only_nts: Option<&HashSet<Nonterminal>>, track_rules: bool, estimated_states: Option<usize>) -> FiniteStateAutomaton {
+        let concat_rules = |first: &Rules, second: Rules| -> Rules {
+            if track_rules { [first.clone(), second].concat() } else { Vec::new() }
+        };
         let start: State = 0;
         let epsilon: State = 1;
-        let mut states: HashSet<State> = HashSet::from([start, epsilon]);
+        let capacity: usize = estimated_states.unwrap_or(0);
+        let mut states: HashSet<State> = HashSet::with_capacity(capacity);
+        states.extend([start, epsilon]);
         let mut accepting_states: HashSet<State> = HashSet::from([epsilon]);
-        let mut transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::new();
+        let mut transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::with_capacity(capacity);
         let mut atomic_to_state: HashMap<(Symbol, Terminal), (State, HashSet<Rules>)> = HashMap::new();
 
         transitions.insert(start, HashMap::from([(Symbol::Nonterminal(start_nt), HashSet::from([(epsilon, Vec::new())]))]));
 
-        if rules.get(&start_nt).unwrap().contains(&vec![Symbol::Epsilon]) {
+        // the start nonterminal may also be nullable through other nonterminals, e.g. S -> A and A -> e
+        if nullable_nonterminals(rules).contains(&start_nt) {
             accepting_states.insert(start);
         }
 
@@ -95,236 +668,909 @@ impl FiniteStateAutomaton {
             atomic_to_state.insert((Symbol::Terminal(*terminal), *terminal), (epsilon, HashSet::new()));
         }
 
-        let atomic_regex: Regex = Regex::new(terminals, rules);
-        let mut regex_to_state: HashMap<VecDeque<WordNode>, (State, State)> = HashMap::new();
+        let mut regex_to_state: HashMap<VecDeque<WordNode>, (State, State)> = HashMap::with_capacity(capacity);
         let mut highest_state: State = 1;
 
-        for ((nonterminal, terminal), node) in atomic_regex.regex {
-            if let (true, rules) = node.is_e_node_get_rules() {
-                atomic_to_state.insert((Symbol::Nonterminal(nonterminal), terminal), (epsilon, rules));
+        // the keys of the atomic regex are unique and the atomic languages of terminals use terminal symbols, so every
+        // atomic language is inserted exactly once and never overwrites another one
+        for ((nonterminal, terminal), node) in &atomic_regex.regex {
+            let (nonterminal, terminal): (Nonterminal, Terminal) = (*nonterminal, *terminal);
+            debug_assert!(!atomic_to_state.contains_key(&(Symbol::Nonterminal(nonterminal), terminal)));
+            if only_nts.is_some_and(|only_nts| !only_nts.contains(&nonterminal)) {
                 continue;
             }
 
-            let mut wordnode_queue: Vec<WordNode> = node.nodes.clone();
-            let mut regex_to_state_key: VecDeque<WordNode> = VecDeque::with_capacity(wordnode_queue.len());
-            let mut atomic_rules: HashSet<Rules> = HashSet::new();
+            let mut entries: Vec<(State, HashSet<Rules>)> = Vec::new();
+            for node in std::iter::once(node).chain(atomic_regex.alternatives.get(&(nonterminal, terminal)).into_iter().flatten()) {
+                if let (true, rules) = node.is_e_node_get_rules() {
+                    entries.push((epsilon, if track_rules { rules } else { HashSet::new() }));
+                    continue;
+                }
+
+                let mut wordnode_queue: Vec<WordNode> = node.nodes.clone();
+                let mut regex_to_state_key: VecDeque<WordNode> = VecDeque::with_capacity(wordnode_queue.len());
+                let mut atomic_rules: HashSet<Rules> = HashSet::new();
 
-            let mut node_end: State;
+                let mut node_end: State;
+                let mut regex_end: State;
 
-            while let Some(wordnode) = wordnode_queue.pop() {
+                while let Some(wordnode) = wordnode_queue.pop() {
 
-                let prev_key = regex_to_state_key.clone();
-                regex_to_state_key.push_front(wordnode.clone());
+                    let prev_key = regex_to_state_key.clone();
+                    regex_to_state_key.push_front(wordnode.clone());
 
-                let node_start: State;
+                    let node_start: State;
 
-                if let Some((dest, end)) = regex_to_state.get(&regex_to_state_key) {
-                    let (dest, end) = (*dest, *end);
-                    if let (true, rule_set) = wordnode.is_e_node_get_rules() {
-                        atomic_rules = rule_set;
-                        regex_to_state_key.push_front(wordnode.clone());
-                        regex_to_state.insert(regex_to_state_key.clone(), (dest, end));
-                        continue;
-                    } 
-                    continue;
-                } else {
-                    if let Some((dest, end)) = regex_to_state.get(&prev_key) {
-                        node_end = *dest;
-                        let end = *end;
+                    if let Some((dest, end)) = regex_to_state.get(&regex_to_state_key) {
+                        let (dest, end) = (*dest, *end);
                         if let (true, rule_set) = wordnode.is_e_node_get_rules() {
                             atomic_rules = rule_set;
-                            regex_to_state.insert(regex_to_state_key.clone(), (node_end, end));
+                            regex_to_state_key.push_front(wordnode.clone());
+                            regex_to_state.insert(regex_to_state_key.clone(), (dest, end));
                             continue;
-                        }
-                        highest_state += 1;
-                        node_start = highest_state;
+                        } 
+                        continue;
                     } else {
-                        highest_state += 1;
-                        node_start = highest_state;
-                        if let (true, rule_set) = wordnode.is_e_node_get_rules() {
-                            atomic_rules = rule_set;
-                            regex_to_state.insert(regex_to_state_key.clone(), (node_start, node_start));
-                            continue;
-                        }
-                        if wordnode.kleene_star {
-                            node_end = node_start;
+                        if let Some((dest, end)) = regex_to_state.get(&prev_key) {
+                            node_end = *dest;
+                            let end = *end;
+                            if let (true, rule_set) = wordnode.is_e_node_get_rules() {
+                                atomic_rules = rule_set;
+                                regex_to_state.insert(regex_to_state_key.clone(), (node_end, end));
+                                continue;
+                            }
+                            highest_state += 1;
+                            node_start = highest_state;
+                            // a starred node followed by other nodes loops on its own state, from which the following nodes are reached
+                            if wordnode.kleene_star {
+                                transitions.entry(node_start).or_default().entry(Symbol::Epsilon).or_default().insert((node_end, Vec::new()));
+                                node_end = node_start;
+                            }
+                            regex_end = end;
                         } else {
                             highest_state += 1;
-                            node_end = highest_state;
+                            node_start = highest_state;
+                            if let (true, rule_set) = wordnode.is_e_node_get_rules() {
+                                atomic_rules = rule_set;
+                                regex_to_state.insert(regex_to_state_key.clone(), (node_start, node_start));
+                                continue;
+                            }
+                            if wordnode.kleene_star {
+                                node_end = node_start;
+                            } else {
+                                highest_state += 1;
+                                node_end = highest_state;
+                            }
+                            regex_end = node_end;
                         }
                     }
-                }
-                regex_to_state.insert(regex_to_state_key.clone(), (node_start, node_end));
+                    regex_to_state.insert(regex_to_state_key.clone(), (node_start, regex_end));
 
-                for (rules, wordnodeword_set) in wordnode.get_by_base_rules() {
-                    let mut sub_states: Vec<State> = vec![node_start];
-                    let mut opt_penultimate_state: Option<State> = None;
+                    for (rules, wordnodeword_set) in wordnode.get_by_base_rules() {
+                        let mut sub_states: Vec<State> = vec![node_start];
+                        let mut opt_penultimate_state: Option<State> = None;
 
-                    for wordnodeword in wordnodeword_set {
-                        let mut source: State = node_start;
-                        let mut target: State;
-                        let mut carried_rules: Rules = Vec::new();
+                        for wordnodeword in wordnodeword_set {
+                            let mut source: State = node_start;
+                            let mut target: State;
+                            let mut carried_rules: Rules = Vec::new();
 
-                        for index in 0..wordnodeword.len() {
-                            if index == sub_states.len() - 1 {
-                                if index == wordnodeword.len() - 1 {
-                                    sub_states.push(node_end);
-                                } else {
-                                    highest_state += 1;
-                                    sub_states.push(highest_state);
+                            for index in 0..wordnodeword.len() {
+                                if index == sub_states.len() - 1 {
+                                    if index == wordnodeword.len() - 1 {
+                                        sub_states.push(node_end);
+                                    } else {
+                                        highest_state += 1;
+                                        sub_states.push(highest_state);
+                                    }
                                 }
-                            }
 
-                            if let WordNodeSymbol::Rules(word_rules) = &wordnodeword[index] {
-                                carried_rules = [word_rules.clone(), carried_rules].concat();
-                                target = sub_states[index+1];
-                                if target == node_end {
-                                    let entry = transitions.entry(source).or_default().entry(Symbol::Epsilon).or_default();
+                                if let WordNodeSymbol::Rules(word_rules) = &wordnodeword[index] {
+                                    carried_rules = concat_rules(word_rules, carried_rules);
+                                    target = sub_states[index+1];
+                                    if target == node_end {
+                                        let entry = transitions.entry(source).or_default().entry(Symbol::Epsilon).or_default();
                                     
-                                    entry.insert((target, [rules.clone(), carried_rules].concat()));
+                                        entry.insert((target, concat_rules(&rules, carried_rules)));
+                                        carried_rules = Vec::new();
+                                    }
+                                } else if let WordNodeSymbol::TerminalExcept(excluded) = &wordnodeword[index] {
+                                    // a class of terminals is lowered into the same transition on each of its terminals
+                                    target = sub_states[index+1];
+                                    let class_rules: Rules = if target == node_end { concat_rules(&rules, carried_rules) } else { carried_rules };
                                     carried_rules = Vec::new();
-                                }
-                            } else {
-                                let entry = transitions.entry(source).or_default().entry(
-                                    match wordnodeword[index] {
-                                        WordNodeSymbol::Nonterminal(nt) => Symbol::Nonterminal(nt),
-                                        WordNodeSymbol::Terminal(t) => Symbol::Terminal(t),
-                                        WordNodeSymbol::Epsilon => Symbol::Epsilon,
-                                        _ => continue,
+                                    for terminal in terminals.iter().filter(|terminal| !excluded.contains(terminal)) {
+                                        transitions.entry(source).or_default().entry(Symbol::Terminal(*terminal)).or_default().insert((target, class_rules.clone()));
                                     }
-                                ).or_default();
-
-                                target = sub_states[index+1];
-                                if target == node_end {
-                                    if let WordNodeSymbol::Nonterminal(_) = wordnodeword[index] {
-                                        let penultimate_state;
-                                        if let Some(state) = opt_penultimate_state {
-                                            penultimate_state = state;
-                                        } else {
-                                            highest_state += 1;
-                                            penultimate_state = highest_state;
-                                            opt_penultimate_state = Some(penultimate_state);
+                                    source = sub_states[index+1];
+                                } else {
+                                    let entry = transitions.entry(source).or_default().entry(
+                                        match wordnodeword[index] {
+                                            WordNodeSymbol::Nonterminal(nt) => Symbol::Nonterminal(nt),
+                                            WordNodeSymbol::Terminal(t) => Symbol::Terminal(t),
+                                            WordNodeSymbol::Epsilon => Symbol::Epsilon,
+                                            _ => continue,
                                         }
-                                        if carried_rules.len() > 0 {
-                                            entry.insert((penultimate_state, carried_rules));
+                                    ).or_default();
+
+                                    target = sub_states[index+1];
+                                    if target == node_end {
+                                        if let WordNodeSymbol::Nonterminal(_) = wordnodeword[index] {
+                                            let penultimate_state;
+                                            if let Some(state) = opt_penultimate_state {
+                                                penultimate_state = state;
+                                            } else {
+                                                highest_state += 1;
+                                                penultimate_state = highest_state;
+                                                opt_penultimate_state = Some(penultimate_state);
+                                            }
+                                            if carried_rules.len() > 0 {
+                                                entry.insert((penultimate_state, carried_rules));
+                                            } else {
+                                                entry.insert((penultimate_state, Vec::new()));
+                                            }
+                                            transitions.entry(penultimate_state).or_default().entry(Symbol::Epsilon).or_default().insert((target, concat_rules(&rules, Vec::new())));
                                         } else {
-                                            entry.insert((penultimate_state, Vec::new()));
+                                            entry.insert((target, concat_rules(&rules, carried_rules)));
                                         }
-                                        transitions.entry(penultimate_state).or_default().entry(Symbol::Epsilon).or_default().insert((target, rules.clone()));
+                                        carried_rules = Vec::new();
+                                    }
+                                    else if carried_rules.len() > 0 {
+                                        entry.insert((target, carried_rules));
+                                        carried_rules = Vec::new();
                                     } else {
-                                        entry.insert((target, [rules.clone(), carried_rules].concat()));
+                                        entry.insert((target, Vec::new()));
                                     }
-                                    carried_rules = Vec::new();
-                                }
-                                else if carried_rules.len() > 0 {
-                                    entry.insert((target, carried_rules));
-                                    carried_rules = Vec::new();
-                                } else {
-                                    entry.insert((target, Vec::new()));
+                                    source = sub_states[index+1];
                                 }
-                                source = sub_states[index+1];
                             }
                         }
+                        states.extend(sub_states);
+                        if let Some(state) = opt_penultimate_state {
+                            states.insert(state);
+                        }
                     }
-                    states.extend(sub_states);
-                    if let Some(state) = opt_penultimate_state {
-                        states.insert(state);
-                    }
-                }
 
 
+                }
+                accepting_states.insert(regex_to_state.get(&regex_to_state_key).unwrap().1);
+                if !track_rules {
+                    atomic_rules = HashSet::new();
+                }
+                entries.push((regex_to_state.get(&regex_to_state_key).unwrap().0, atomic_rules));
             }
-            accepting_states.insert(regex_to_state.get(&regex_to_state_key).unwrap().1);
-            atomic_to_state.insert((Symbol::Nonterminal(nonterminal), terminal), (regex_to_state.get(&regex_to_state_key).unwrap().0, atomic_rules));
+
+            // the alternatives of an atomic language are joined by epsilon transitions from a new state, like merged atomic languages
+            let entry: (State, HashSet<Rules>) = if entries.len() == 1 {
+                entries.pop().unwrap()
+            } else {
+                highest_state += 1;
+                states.insert(highest_state);
+                let mut rules_set: HashSet<Rules> = HashSet::new();
+                for (state, rules) in entries {
+                    transitions.entry(highest_state).or_default().entry(Symbol::Epsilon).or_default().insert((state, Vec::new()));
+                    rules_set.extend(rules);
+                }
+                (highest_state, rules_set)
+            };
+            atomic_to_state.insert((Symbol::Nonterminal(nonterminal), terminal), entry);
         }
 
         FiniteStateAutomaton{states, accepting_states, start, transitions, atomic_to_state}
     }
 
-    pub fn to_dot(&self, filename: &str) -> std::io::Result<()> {
-        let mut file = File::create(format!("{}.dot", filename))?;
-        write!(file, "digraph G {{\n")?;
-        let mut state_to_shape: HashMap<State, &str> = HashMap::new();
-        for state in &self.states {
-            if self.accepting_states.contains(state) {
-                state_to_shape.insert(*state, "doublecircle");
-            } else {
-                state_to_shape.insert(*state, "circle");
+    /// Rebuilds the atomic languages of the given nonterminals from the derivation rules, leaving the rest of the
+    /// automaton as is. The new states are added after the existing ones, after which the states which can no longer
+    /// be reached are removed. This is only correct if the atomic languages of the other nonterminals are unaffected
+    /// by the changes to the rules, see Grammar::add_production.
+    pub fn rebuild_atomic(&mut self, terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, nts: &HashSet<Nonterminal>) {
+        let partial: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa_for(terminals, start_nt, rules, Some(nts), true, None);
+        self.merge_atomic(partial, nts, OverwritePolicy::Replace);
+    }
+
+    /// Rebuilds the atomic languages of the given nonterminals like `rebuild_atomic`, where the policy determines what
+    /// happens to an atomic language of the same nonterminal and terminal which the automaton already has. With
+    /// OverwritePolicy::Error the first such atomic language, in sorted order, is returned as the error.
+    pub fn rebuild_atomic_with(&mut self, terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>, nts: &HashSet<Nonterminal>, policy: OverwritePolicy) -> Result<(), BuildError> {
+        let partial: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa_for(terminals, start_nt, rules, Some(nts), true, None);
+        if policy == OverwritePolicy::Error {
+            let conflict: Option<&(Symbol, Terminal)> = partial.atomic_to_state.keys()
+                .filter(|key| matches!(key.0, Symbol::Nonterminal(_)) && self.atomic_to_state.contains_key(key))
+                .min();
+            if let Some((symbol, terminal)) = conflict {
+                return Err(BuildError::AtomicConflict(*symbol, *terminal));
             }
         }
-        for state in &self.states {
-            write!(file, "{} [ shape={} ]\n", state, state_to_shape.get(state).unwrap())?;
+        self.merge_atomic(partial, nts, policy);
+        Ok(())
+    }
+
+    // Adds the atomic languages of the partial automaton, which was built for only the given nonterminals, combining
+    // them with the existing atomic languages of these nonterminals according to the policy, see rebuild_atomic_with.
+    pub(crate) fn merge_atomic(&mut self, mut partial: FiniteStateAutomaton, nts: &HashSet<Nonterminal>, policy: OverwritePolicy) {
+        // the start and epsilon state are shared, the other states of the partial automaton are moved past the existing ones
+        let offset: State = self.states.iter().max().map_or(0, |state| *state).saturating_sub(1);
+        let renumber = |state: &State| -> State {
+            if *state <= 1 { *state } else { state + offset }
+        };
+
+        if policy == OverwritePolicy::Replace {
+            self.atomic_to_state.retain(|(symbol, _), _| !matches!(symbol, Symbol::Nonterminal(nt) if nts.contains(nt)));
         }
-        for ((symbol, terminal), (state, rule_set)) in &self.atomic_to_state {
-            match symbol {
-                Symbol::Nonterminal(nonterm) => {
-                    write!(file, "\"[{}]^({})\" [ shape=rectangle ]\n\"[{}]^({})\" -> {}", nonterm, terminal, nonterm, terminal, state)?;
-                    if rule_set.len() > 0 {
-                        write!(file, "[ label=\"")?;
-                        for rules in rule_set {
-                            write!(file, "(")?;
-                            for rule in rules {
-                                write!(file, "[{} -> ", &rule.0)?;
-                                for symbol in &rule.1 {
-                                    write!(file, "{}", symbol)?;
-                                }
-                                write!(file, "]")?;
-                            }
-                            write!(file, ")")?;
-                        }
-                        write!(file, "\" ]")?;
-                    }
-                    write!(file, "\n")?;
+        let mut highest_state: State = offset + partial.states.iter().max().map_or(0, |state| *state);
+        let mut keys: Vec<(Symbol, Terminal)> = partial.atomic_to_state.keys().cloned().collect();
+        keys.sort();
+        for key in keys {
+            let (state, rules_set): (State, HashSet<Rules>) = partial.atomic_to_state.remove(&key).unwrap();
+            let new_state: State = renumber(&state);
+            match self.atomic_to_state.get_mut(&key) {
+                // the atomic languages of terminals are the same in both automata
+                Some((old_state, old_rules_set)) if matches!(key.0, Symbol::Nonterminal(_)) => {
+                    highest_state += 1;
+                    self.states.insert(highest_state);
+                    self.transitions.entry(highest_state).or_default().entry(Symbol::Epsilon).or_default()
+                        .extend([(*old_state, Vec::new()), (new_state, Vec::new())]);
+                    *old_state = highest_state;
+                    old_rules_set.extend(rules_set);
+                },
+                Some(_) => {},
+                None => {
+                    self.atomic_to_state.insert(key, (new_state, rules_set));
                 },
-                Symbol::Terminal(term) => write!(file, "\"[{}]^({})\" [ shape=rectangle ]\n\"[{}]^({})\" -> {}\n", term, terminal, term, terminal, state)?,
-                _ => {},
             }
         }
-        for (source, transition_list) in &self.transitions {
+        for (state, transition_list) in partial.transitions {
+            if state == partial.start {
+                continue;
+            }
+            let new_transition_list: &mut HashMap<Symbol, HashSet<(State, Rules)>> = self.transitions.entry(renumber(&state)).or_default();
             for (symbol, destinations) in transition_list {
-                for (dest, rules) in destinations {
-                    match symbol {
-                        Symbol::Epsilon => write!(file, "{} -> {} [ label=\"e ", source, dest)?,
-                        Symbol::Nonterminal(nonterminal) => write!(file, "{} -> {} [ label=\"{} ", source, dest, nonterminal)?,
-                        Symbol::Terminal(terminal) => write!(file, "{} -> {} [ label=\"{} ", source, dest, terminal)?,
-                    }
-                    for rule in rules {
-                        write!(file, "[{} -> ", &rule.0)?;
-                        for symbol in &rule.1 {
-                            write!(file, "{}", symbol)?;
-                        }
-                        write!(file, "] ")?;
-                    }
-                    write!(file, "\" ]\n")?;
-                }
+                new_transition_list.entry(symbol).or_default().extend(destinations.into_iter().map(|(dest, rules)| (renumber(&dest), rules)));
             }
         }
-        write!(file, "}}")
+        self.states.extend(partial.states.iter().filter(|state| **state != partial.start).map(renumber));
+        self.accepting_states.extend(partial.accepting_states.iter().filter(|state| **state != partial.start).map(renumber));
+        self.remove_unreachable_states();
     }
 
-    pub fn simulate(&self, curr_state: &State, symbol: Symbol) -> Option<HashSet<(&State, &Rules, bool)>> {
-        self.transitions.get(&curr_state)?
-            .get(&symbol)
-            .map(|destinations| {
-                let mut res: HashSet<(&State, &Rules, bool)> = HashSet::new();
-                for (dest, rules) in destinations {
-                    res.insert((dest, rules, self.is_accepting(dest)));
+    /// Collapses atomic entries whose states are equivalent, i.e. both accepting or both not, with the same
+    /// transitions under the same rules to equivalent states. Equivalent states are merged throughout the automaton,
+    /// the states which can no longer be reached are removed. Every atomic entry keeps its symbol, terminal and
+    /// rules, only its state may be replaced by an equivalent one, so the same terminals still initiate the same
+    /// nonterminals. Returns the number of atomic entries whose state was replaced.
+    pub fn dedup_atomic(&mut self) -> usize {
+        // repeatedly merge states which are equivalent given the merges so far, until nothing changes
+        let mut representative: HashMap<State, State> = HashMap::new();
+        loop {
+            let find = |state: &State| -> State { *representative.get(state).unwrap_or(state) };
+            let mut by_signature: BTreeMap<StateSignature, State> = BTreeMap::new();
+            let mut merges: Vec<(State, State)> = Vec::new();
+            let mut states: Vec<State> = self.states.iter().cloned().filter(|state| find(state) == *state).collect();
+            states.sort();
+            for state in states {
+                let transitions: BTreeSet<(Symbol, State, Rules)> = self.transitions.get(&state).into_iter().flatten()
+                    .flat_map(|(symbol, destinations)| destinations.iter().map(|(dest, rules)| (*symbol, find(dest), rules.clone())))
+                    .collect();
+                match by_signature.get(&(self.is_accepting(&state), transitions.clone())) {
+                    Some(existing) => merges.push((state, *existing)),
+                    None => {
+                        by_signature.insert((self.is_accepting(&state), transitions), state);
+                    },
                 }
-                res
-            })
-    }
+            }
+            if merges.is_empty() {
+                break;
+            }
+            for (state, existing) in merges {
+                for target in representative.values_mut() {
+                    if *target == state {
+                        *target = existing;
+                    }
+                }
+                representative.insert(state, existing);
+            }
+        }
 
-    pub fn is_accepting(&self, curr_state: &State) -> bool {
-        self.accepting_states.contains(curr_state)
+        let mut res: usize = 0;
+        for (state, _) in self.atomic_to_state.values_mut() {
+            if let Some(existing) = representative.get(state) {
+                *state = *existing;
+                res += 1;
+            }
+        }
+        self.start = *representative.get(&self.start).unwrap_or(&self.start);
+        for transition_list in self.transitions.values_mut() {
+            for destinations in transition_list.values_mut() {
+                *destinations = destinations.drain().map(|(dest, rules)| (*representative.get(&dest).unwrap_or(&dest), rules)).collect();
+            }
+        }
+        self.remove_unreachable_states();
+        res
     }
 
-    pub fn get_start(&self) -> (State, bool) {
-        (self.start, self.is_accepting(&self.start))
-    }
+    /// Merges the states of the atomic languages which form identical sub-automata: states which are both accepting or
+    /// both not, and whose transitions under the same symbols and rules lead to identical states again, see
+    /// `bisimilar`. Unlike `dedup_atomic`, which merges from the states without transitions backwards, this also shares
+    /// sub-automata containing cycles, as built for left recursion. Only the states reachable from the atomic languages
+    /// are merged, each into the smallest state it is identical to, and the transitions and atomic entries into merged
+    /// states are redirected. The merged states behave exactly like the states they are merged into, so the accepted
+    /// language and the rules collected along every path stay the same. Returns the number of states saved.
+    pub fn share_atomic_subautomata(&mut self) -> usize {
+        let region: HashSet<State> = self.reachable_from(self.atomic_to_state.values().map(|(state, _)| *state).collect());
+        let block_of: HashMap<State, usize> = self.bisimulation_blocks(&region);
+        let mut smallest: HashMap<usize, State> = HashMap::new();
+        for (state, block) in &block_of {
+            let entry: &mut State = smallest.entry(*block).or_insert(*state);
+            *entry = (*entry).min(*state);
+        }
+        let representative: HashMap<State, State> = block_of.iter()
+            .map(|(state, block)| (*state, smallest[block]))
+            .filter(|(state, existing)| state != existing)
+            .collect();
 
-    pub fn get_atomic(&self, symbol: Symbol, terminal: Terminal) -> Option<(&State, &HashSet<Rules>, bool)> {
-        self.atomic_to_state.get(&(symbol, terminal))
-            .map(|(dest, rules_set)| (dest, rules_set, self.is_accepting(dest)))
+        for (state, _) in self.atomic_to_state.values_mut() {
+            *state = *representative.get(state).unwrap_or(state);
+        }
+        self.start = *representative.get(&self.start).unwrap_or(&self.start);
+        for transition_list in self.transitions.values_mut() {
+            for destinations in transition_list.values_mut() {
+                *destinations = destinations.drain().map(|(dest, rules)| (*representative.get(&dest).unwrap_or(&dest), rules)).collect();
+            }
+        }
+        self.states.retain(|state| !representative.contains_key(state));
+        self.accepting_states.retain(|state| !representative.contains_key(state));
+        self.transitions.retain(|state, _| !representative.contains_key(state));
+        representative.len()
+    }
+
+    /// Produces an equivalent automaton in which nonterminal transitions are replaced by copies of the atomic languages
+    /// they jump into, so that recognizing only has to match terminals. A transition `src -A-> dest` becomes a
+    /// transition `src -t-> c` for every atomic language of `A` and `t`, where `c` is the start of a copy of the states
+    /// of the atomic language. Instead of returning to `dest` from its accepting states, the copy has epsilon
+    /// transitions from them to `dest`.
+    ///
+    /// Inlining is only finite for atomic languages which do not contain nonterminal transitions themselves, so the
+    /// transitions are inlined innermost first until no further transition can be inlined. A nonterminal transition is
+    /// kept if one of its atomic languages still contains a nonterminal transition at that point, which is the case when
+    /// the atomic languages of the nonterminal refer to the nonterminal again, as for self-embedding `S -> aSb` and right
+    /// recursive `S -> aS`, or to a nonterminal for which this is the case. Left recursion is already resolved within the
+    /// atomic languages and can be inlined. The atomic languages of the kept nonterminals are kept as well. Since every
+    /// transition gets its own copies, the automaton may grow considerably for deeply nested grammars. The rules of the
+    /// atomic language are appended to the rules of the replaced transition, which is only meant for recognizing: the
+    /// result should not be used to parse.
+    pub fn inline_nonterminals(&self) -> FiniteStateAutomaton {
+        let mut res: FiniteStateAutomaton = FiniteStateAutomaton{states: self.states.clone(), accepting_states: self.accepting_states.clone(), start: self.start, transitions: self.transitions.clone(), atomic_to_state: self.atomic_to_state.clone()};
+        let mut highest_state: State = self.states.iter().max().map_or(0, |state| *state);
+        let mut copies: HashMap<(State, State), State> = HashMap::new();
+
+        while let Some((src, symbol, dest, rules)) = res.next_inlinable_transition() {
+            let transition_list: &mut HashMap<Symbol, HashSet<(State, Rules)>> = res.transitions.get_mut(&src).unwrap();
+            transition_list.get_mut(&symbol).unwrap().remove(&(dest, rules.clone()));
+            if transition_list[&symbol].is_empty() {
+                transition_list.remove(&symbol);
+            }
+
+            let atomics: BTreeMap<Terminal, (State, HashSet<Rules>)> = res.atomic_to_state.iter()
+                .filter(|((atomic_symbol, _), _)| *atomic_symbol == symbol)
+                .map(|((_, terminal), atomic)| (*terminal, atomic.clone()))
+                .collect();
+            for (terminal, (atomic_state, rules_set)) in atomics {
+                let copy: State = match copies.get(&(atomic_state, dest)) {
+                    Some(copy) => *copy,
+                    None => {
+                        let copy: State = res.copy_atomic_language(atomic_state, dest, &mut highest_state);
+                        copies.insert((atomic_state, dest), copy);
+                        copy
+                    },
+                };
+                let destinations: &mut HashSet<(State, Rules)> = res.transitions.entry(src).or_default().entry(Symbol::Terminal(terminal)).or_default();
+                if rules_set.is_empty() {
+                    destinations.insert((copy, rules.clone()));
+                }
+                for atomic_rules in rules_set {
+                    destinations.insert((copy, [rules.clone(), atomic_rules].concat()));
+                }
+            }
+        }
+
+        let kept: HashSet<Symbol> = res.transitions.values().flat_map(|transition_list| transition_list.keys().cloned()).collect();
+        res.atomic_to_state.retain(|(symbol, _), _| !matches!(symbol, Symbol::Nonterminal(_)) || kept.contains(symbol));
+        res.remove_unreachable_states();
+        res
+    }
+
+    // Finds the smallest nonterminal transition for which none of the atomic languages of its nonterminal contains a
+    // nonterminal transition, see inline_nonterminals.
+    fn next_inlinable_transition(&self) -> Option<(State, Symbol, State, Rules)> {
+        let nonterminal_transitions: BTreeSet<(State, Symbol, State, Rules)> = self.transitions.iter()
+            .flat_map(|(state, transition_list)| transition_list.iter()
+                .filter(|(symbol, _)| matches!(symbol, Symbol::Nonterminal(_)))
+                .flat_map(move |(symbol, destinations)| destinations.iter().map(move |(dest, rules)| (*state, *symbol, *dest, rules.clone()))))
+            .collect();
+        nonterminal_transitions.into_iter().find(|(_, symbol, _, _)| {
+            self.atomic_states_of(symbol).into_iter().all(|atomic_state| self.atomic_language_states(atomic_state).iter()
+                .all(|state| !self.transitions.get(state).is_some_and(|transition_list| transition_list.keys().any(|symbol| matches!(symbol, Symbol::Nonterminal(_))))))
+        })
+    }
+
+    // The states of the atomic language starting in the given state, i.e. the states reachable from it without
+    // jumping into the atomic language of a nonterminal.
+    fn atomic_language_states(&self, atomic_state: State) -> HashSet<State> {
+        let mut res: HashSet<State> = HashSet::from([atomic_state]);
+        let mut to_visit: Vec<State> = vec![atomic_state];
+        while let Some(state) = to_visit.pop() {
+            for (dest, _) in self.transitions.get(&state).into_iter().flat_map(|transition_list| transition_list.values().flatten()) {
+                if res.insert(*dest) {
+                    to_visit.push(*dest);
+                }
+            }
+        }
+        res
+    }
+
+    // Copies the states of the atomic language starting in the given state, with epsilon transitions from the copies of
+    // its accepting states to the return state, so the copy continues there instead of returning to the state below it.
+    // Nonterminal transitions are copied as well and still enter the original atomic languages. The copies are not
+    // accepting themselves. Returns the copy of the given state.
+    fn copy_atomic_language(&mut self, atomic_state: State, return_state: State, highest_state: &mut State) -> State {
+        let mut originals: Vec<State> = self.atomic_language_states(atomic_state).into_iter().collect();
+        originals.sort();
+        let mut copy_of: HashMap<State, State> = HashMap::new();
+        for state in &originals {
+            *highest_state += 1;
+            copy_of.insert(*state, *highest_state);
+            self.states.insert(*highest_state);
+        }
+        for state in &originals {
+            let mut transition_list: HashMap<Symbol, HashSet<(State, Rules)>> = self.transitions.get(state).into_iter().flatten()
+                .map(|(symbol, destinations)| (*symbol, destinations.iter().map(|(dest, rules)| (copy_of[dest], rules.clone())).collect()))
+                .collect();
+            if self.is_accepting(state) {
+                transition_list.entry(Symbol::Epsilon).or_default().insert((return_state, Vec::new()));
+            }
+            if !transition_list.is_empty() {
+                self.transitions.insert(copy_of[state], transition_list);
+            }
+        }
+        copy_of[&atomic_state]
+    }
+
+    // Removes the states which can be reached neither from the start state nor from the state of an atomic language.
+    fn remove_unreachable_states(&mut self) {
+        let mut reachable: HashSet<State> = HashSet::from([self.start]);
+        reachable.extend(self.atomic_to_state.values().map(|(state, _)| *state));
+        let mut to_visit: Vec<State> = reachable.iter().cloned().collect();
+        while let Some(state) = to_visit.pop() {
+            if let Some(transition_list) = self.transitions.get(&state) {
+                for (dest, _) in transition_list.values().flatten() {
+                    if reachable.insert(*dest) {
+                        to_visit.push(*dest);
+                    }
+                }
+            }
+        }
+        self.states.retain(|state| reachable.contains(state));
+        self.accepting_states.retain(|state| reachable.contains(state));
+        self.transitions.retain(|state, _| reachable.contains(state));
+    }
+
+    // Numbers the states in the order of a breadth-first search, see write_dot_diff. Transitions with the same symbol
+    // and rules are followed in the order of the bisimulation blocks of their destinations, which only depend on the
+    // structure of the automaton. States which cannot be reached from the start state or the atomic languages get no
+    // number.
+    fn canonical_numbering(&self) -> HashMap<State, usize> {
+        let block_of: &HashMap<State, usize> = &self.bisimulation_blocks(&self.states);
+        let mut atomic_keys: Vec<(&(Symbol, Terminal), &State)> = self.atomic_to_state.iter().map(|(key, (state, _))| (key, state)).collect();
+        atomic_keys.sort();
+        let mut res: HashMap<State, usize> = HashMap::new();
+        let mut to_visit: VecDeque<State> = VecDeque::new();
+        for root in std::iter::once(self.start).chain(atomic_keys.into_iter().map(|(_, state)| *state)) {
+            if !self.states.contains(&root) || res.contains_key(&root) {
+                continue;
+            }
+            res.insert(root, res.len());
+            to_visit.push_back(root);
+            while let Some(state) = to_visit.pop_front() {
+                let mut outgoing: Vec<(&Symbol, &Rules, usize, State)> = self.transitions.get(&state).into_iter().flatten()
+                    .flat_map(|(symbol, destinations)| destinations.iter().map(move |(dest, rules)| (symbol, rules, block_of[dest], *dest)))
+                    .collect();
+                outgoing.sort();
+                for (_, _, _, dest) in outgoing {
+                    if !res.contains_key(&dest) {
+                        res.insert(dest, res.len());
+                        to_visit.push_back(dest);
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Writes the transitions as CSV, one row per transition and set of rules with the columns
+    /// `src,symbol,dest,rules,accepting_dest`, preceded by a header row. The rows are sorted by source, symbol,
+    /// destination and rules, so the output of equal automata is identical. Fields containing a comma, quote or line
+    /// break are quoted.
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut rows: BTreeSet<(State, Symbol, State, &Rules)> = BTreeSet::new();
+        for (source, transition_list) in &self.transitions {
+            for (symbol, destinations) in transition_list {
+                for (dest, rules) in destinations {
+                    rows.insert((*source, *symbol, *dest, rules));
+                }
+            }
+        }
+        writeln!(w, "src,symbol,dest,rules,accepting_dest")?;
+        for (source, symbol, dest, rules) in rows {
+            writeln!(w, "{},{},{},{},{}", source, csv_field(&symbol.to_string()), dest, csv_field(&rules_to_string(rules)), self.is_accepting(&dest))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the automaton in a line-based format in which every rule is written once. The output starts with the
+    /// start state, the states and the accepting states, followed by the dictionary of rules, one `rule` line per rule
+    /// numbered from 0 in order, after which the transitions and atomic languages refer to rules by their number. For
+    /// the rules `S -> aSb | e` this is:
+    ///
+    /// ```text
+    /// start 0
+    /// states 0 1 2 3 4
+    /// accepting 0 1 3
+    /// rule n83 e
+    /// rule n83 t97 n83 t98
+    /// transition 0 n83 1 -
+    /// transition 2 t98 3 1,0
+    /// transition 2 n83 4 -
+    /// transition 4 t98 3 1
+    /// atomic t97 97 1
+    /// atomic t98 98 1
+    /// atomic n83 97 2
+    /// ```
+    ///
+    /// Characters are written as their code points, prefixed by `t` for terminals and `n` for nonterminals, and `e` is
+    /// epsilon. A sequence of rules is a comma separated list of numbers, or `-` when it is empty. An atomic language
+    /// lists the symbol, the terminal, its state and its alternative sequences of rules. All lines are sorted, so the
+    /// output of equal automata is identical. See `read_interned` for reading it back.
+    pub fn write_interned<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let sorted = |states: &HashSet<State>| -> String {
+            let states: BTreeSet<&State> = states.iter().collect();
+            states.iter().map(|state| format!(" {}", state)).collect()
+        };
+        let dictionary: BTreeSet<&Rule> = self.transitions.values().flat_map(|transition_list| transition_list.values()).flatten()
+            .flat_map(|(_, rules)| rules)
+            .chain(self.atomic_to_state.values().flat_map(|(_, rule_set)| rule_set).flatten())
+            .collect();
+        let index_of: HashMap<&Rule, usize> = dictionary.iter().enumerate().map(|(index, rule)| (*rule, index)).collect();
+        let rules_field = |rules: &Rules| -> String {
+            if rules.is_empty() {
+                String::from("-")
+            } else {
+                rules.iter().map(|rule| index_of[rule].to_string()).collect::<Vec<String>>().join(",")
+            }
+        };
+
+        writeln!(w, "start {}", self.start)?;
+        writeln!(w, "states{}", sorted(&self.states))?;
+        writeln!(w, "accepting{}", sorted(&self.accepting_states))?;
+        for (nonterminal, word) in &dictionary {
+            let symbols: String = word.iter().map(|symbol| format!(" {}", interned_symbol(symbol))).collect();
+            writeln!(w, "rule n{}{}", *nonterminal as u32, symbols)?;
+        }
+        let mut transitions: BTreeSet<(State, Symbol, State, &Rules)> = BTreeSet::new();
+        for (source, transition_list) in &self.transitions {
+            for (symbol, destinations) in transition_list {
+                for (dest, rules) in destinations {
+                    transitions.insert((*source, *symbol, *dest, rules));
+                }
+            }
+        }
+        for (source, symbol, dest, rules) in transitions {
+            writeln!(w, "transition {} {} {} {}", source, interned_symbol(&symbol), dest, rules_field(rules))?;
+        }
+        let atomics: BTreeMap<&(Symbol, Terminal), &(State, HashSet<Rules>)> = self.atomic_to_state.iter().collect();
+        for ((symbol, terminal), (state, rule_set)) in atomics {
+            let rule_set: BTreeSet<&Rules> = rule_set.iter().collect();
+            let fields: String = rule_set.into_iter().map(|rules| format!(" {}", rules_field(rules))).collect();
+            writeln!(w, "atomic {} {} {}{}", interned_symbol(symbol), *terminal as u32, state, fields)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an automaton written by `write_interned`, replacing the numbers of rules by the rules of the dictionary.
+    /// A malformed line is reported with its number (starting at 1), and the states are checked like in `from_parts`.
+    pub fn read_interned(input: &str) -> Result<FiniteStateAutomaton, BuildError> {
+        let mut start: Option<State> = None;
+        let mut states: HashSet<State> = HashSet::new();
+        let mut accepting_states: HashSet<State> = HashSet::new();
+        let mut dictionary: Vec<Rule> = Vec::new();
+        let mut transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::new();
+        let mut atomic_map: HashMap<(Symbol, Terminal), (State, HashSet<Rules>)> = HashMap::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let line_number: usize = index + 1;
+            let error = |message: &str| -> BuildError { BuildError::Parse(line_number, message.to_string()) };
+            let state = |field: Option<&str>| -> Result<State, BuildError> {
+                field.and_then(|field| field.parse().ok()).ok_or_else(|| error("expected a state"))
+            };
+            let symbol = |field: Option<&str>| -> Result<Symbol, BuildError> {
+                field.and_then(parse_interned_symbol).ok_or_else(|| error("expected a symbol"))
+            };
+            let rules = |field: &str, dictionary: &[Rule]| -> Result<Rules, BuildError> {
+                if field == "-" {
+                    return Ok(Vec::new());
+                }
+                field.split(',')
+                    .map(|number| number.parse::<usize>().ok().and_then(|number| dictionary.get(number)).cloned().ok_or_else(|| error("unknown rule")))
+                    .collect()
+            };
+
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                None => {},
+                Some("start") => start = Some(state(fields.next())?),
+                Some("states") => states.extend(fields.map(|field| state(Some(field))).collect::<Result<Vec<State>, BuildError>>()?),
+                Some("accepting") => accepting_states.extend(fields.map(|field| state(Some(field))).collect::<Result<Vec<State>, BuildError>>()?),
+                Some("rule") => {
+                    let nonterminal: Nonterminal = match symbol(fields.next())? {
+                        Symbol::Nonterminal(nonterminal) => nonterminal,
+                        _ => return Err(error("expected a nonterminal")),
+                    };
+                    let word: Word = fields.map(|field| symbol(Some(field))).collect::<Result<Word, BuildError>>()?;
+                    dictionary.push((nonterminal, word));
+                },
+                Some("transition") => {
+                    let source: State = state(fields.next())?;
+                    let transition_symbol: Symbol = symbol(fields.next())?;
+                    let dest: State = state(fields.next())?;
+                    let transition_rules: Rules = rules(fields.next().ok_or_else(|| error("expected rules"))?, &dictionary)?;
+                    transitions.entry(source).or_default().entry(transition_symbol).or_default().insert((dest, transition_rules));
+                },
+                Some("atomic") => {
+                    let atomic_symbol: Symbol = symbol(fields.next())?;
+                    let terminal: Terminal = fields.next().and_then(|field| field.parse().ok()).and_then(char::from_u32).ok_or_else(|| error("expected a terminal"))?;
+                    let atomic_state: State = state(fields.next())?;
+                    let rule_set: HashSet<Rules> = fields.map(|field| rules(field, &dictionary)).collect::<Result<HashSet<Rules>, BuildError>>()?;
+                    atomic_map.insert((atomic_symbol, terminal), (atomic_state, rule_set));
+                },
+                Some(kind) => return Err(error(&format!("unknown line '{}'", kind))),
+            }
+        }
+
+        let start: State = start.ok_or_else(|| BuildError::Parse(input.lines().count().max(1), String::from("missing start state")))?;
+        FiniteStateAutomaton::from_parts(states, accepting_states, start, transitions, atomic_map)
+    }
+
+    /// Writes the automaton with `write_interned`, reads it back with `read_interned` and panics unless the result has
+    /// the same start state, states, accepting states, transitions and atomic languages. The panic message lists the
+    /// parts only found before and only found after the round trip. Meant to be called from tests, to guard against
+    /// encoding bugs, e.g. in the keys of the atomic languages.
+    #[cfg(feature = "test-utils")]
+    pub fn assert_interned_roundtrip(&self) {
+        let mut written: Vec<u8> = Vec::new();
+        self.write_interned(&mut written).unwrap();
+        let written: String = String::from_utf8(written).unwrap();
+        let read: FiniteStateAutomaton = FiniteStateAutomaton::read_interned(&written).unwrap_or_else(|err| panic!("cannot read back the written automaton: {}", err));
+
+        // every part of the automaton as a line, sorted
+        let parts = |fsa: &FiniteStateAutomaton| -> BTreeSet<String> {
+            let mut res: BTreeSet<String> = BTreeSet::from([format!("start {}", fsa.start)]);
+            res.extend(fsa.states.iter().map(|state| format!("state {}", state)));
+            res.extend(fsa.accepting_states.iter().map(|state| format!("accepting {}", state)));
+            for (source, transition_list) in &fsa.transitions {
+                for (symbol, destinations) in transition_list {
+                    res.extend(destinations.iter().map(|(dest, rules)| format!("transition {} {:?} {} {:?}", source, symbol, dest, rules)));
+                }
+            }
+            for ((symbol, terminal), (state, rules_set)) in &fsa.atomic_to_state {
+                let rules_set: BTreeSet<&Rules> = rules_set.iter().collect();
+                res.insert(format!("atomic {:?} {:?} {} {:?}", symbol, terminal, state, rules_set));
+            }
+            res
+        };
+        let (before, after): (BTreeSet<String>, BTreeSet<String>) = (parts(self), parts(&read));
+        if before != after {
+            let only_before: Vec<&String> = before.difference(&after).collect();
+            let only_after: Vec<&String> = after.difference(&before).collect();
+            panic!("the automaton changed in the round trip\nonly before: {:#?}\nonly after: {:#?}", only_before, only_after);
+        }
+    }
+
+    #[cfg(feature = "std-fs")]
+    pub fn to_dot(&self, filename: &str) -> Result<(), DotError> {
+        self.to_dot_with(filename, &RenderOptions::default())
+    }
+
+    /// Writes the automaton to the DOT file `filename`.dot, labeling rules according to the given render options.
+    /// The automaton is checked before the file is created, a transition or atomic language referring to a state
+    /// which does not exist is reported instead of being written.
+    #[cfg(feature = "std-fs")]
+    pub fn to_dot_with(&self, filename: &str, options: &RenderOptions) -> Result<(), DotError> {
+        self.check_states()?;
+        let mut file = File::create(format!("{}.dot", filename))?;
+        self.write_dot_states(&mut file, options, &self.states)?;
+        Ok(())
+    }
+
+    /// Writes only the given states of the automaton as a DOT graph, with the transitions leaving them and the atomic
+    /// languages starting in them, so an editor can redraw just the part of the automaton affected by a change. A
+    /// transition to a state outside of the region ends in a dashed stub node for that state. Rules are labeled in full,
+    /// the output is otherwise the same as that of `to_dot` restricted to the region.
+    pub fn write_dot_region<W: Write>(&self, w: &mut W, states: &HashSet<State>) -> io::Result<()> {
+        self.write_dot_states(w, &RenderOptions::default(), states)
+    }
+
+    // Writes the states of the region as a DOT graph, see `to_dot_with` and `write_dot_region`.
+    fn write_dot_states<W: Write>(&self, w: &mut W, options: &RenderOptions, region: &HashSet<State>) -> io::Result<()> {
+        write!(w, "digraph G {{\n")?;
+        let mut state_to_shape: HashMap<State, &str> = HashMap::new();
+        for state in self.states.iter().filter(|state| region.contains(state)) {
+            if self.accepting_states.contains(state) {
+                state_to_shape.insert(*state, "doublecircle");
+            } else {
+                state_to_shape.insert(*state, "circle");
+            }
+        }
+        for state in self.states.iter().filter(|state| region.contains(state)) {
+            write!(w, "{} [ shape={} ]\n", state, state_to_shape.get(state).unwrap())?;
+        }
+        for ((symbol, terminal), (state, rule_set)) in self.atomic_to_state.iter().filter(|(_, (state, _))| region.contains(state)) {
+            match symbol {
+                Symbol::Nonterminal(nonterm) => {
+                    write!(w, "\"[{}]^({})\" [ shape=rectangle ]\n\"[{}]^({})\" -> {}", nonterm, terminal, nonterm, terminal, state)?;
+                    if rule_set.len() > 0 {
+                        write!(w, "[ label=\"")?;
+                        for rules in rule_set {
+                            write!(w, "({})", options.rule_labels(rules).concat())?;
+                        }
+                        write!(w, "\" ]")?;
+                    }
+                    write!(w, "\n")?;
+                },
+                Symbol::Terminal(term) => write!(w, "\"[{}]^({})\" [ shape=rectangle ]\n\"[{}]^({})\" -> {}\n", term, terminal, term, terminal, state)?,
+                _ => {},
+            }
+        }
+        let mut rule_nodes: usize = 0;
+        let mut stubs: BTreeSet<State> = BTreeSet::new();
+        for (source, transition_list) in self.transitions.iter().filter(|(source, _)| region.contains(source)) {
+            for (symbol, destinations) in transition_list {
+                for (dest, rules) in destinations {
+                    if !region.contains(dest) {
+                        stubs.insert(*dest);
+                    }
+                    if options.rules_as_nodes && !rules.is_empty() {
+                        rule_nodes += 1;
+                        writeln!(w, "\"rules {}\" [ shape=box style=rounded label=\"{}\" ]", rule_nodes, options.rule_labels(rules).join("\\n"))?;
+                        writeln!(w, "{} -> \"rules {}\" [ label=\"{}\" ]", source, rule_nodes, symbol)?;
+                        writeln!(w, "\"rules {}\" -> {}", rule_nodes, dest)?;
+                        continue;
+                    }
+                    match symbol {
+                        Symbol::Epsilon => write!(w, "{} -> {} [ label=\"e ", source, dest)?,
+                        Symbol::Nonterminal(nonterminal) => write!(w, "{} -> {} [ label=\"{} ", source, dest, nonterminal)?,
+                        Symbol::Terminal(terminal) => write!(w, "{} -> {} [ label=\"{} ", source, dest, terminal)?,
+                    }
+                    for label in options.rule_labels(rules) {
+                        write!(w, "{} ", label)?;
+                    }
+                    write!(w, "\" ]\n")?;
+                }
+            }
+        }
+        for stub in stubs {
+            writeln!(w, "{} [ shape=circle style=dashed ]", stub)?;
+        }
+        write!(w, "}}")
+    }
+
+    // Checks that every transition and atomic language only refers to states of the automaton, in sorted order.
+    #[cfg(feature = "std-fs")]
+    fn check_states(&self) -> Result<(), DotError> {
+        let transitions: BTreeSet<(State, Symbol, State)> = self.transitions.iter()
+            .flat_map(|(source, trans_list)| trans_list.iter()
+                .flat_map(move |(symbol, destinations)| destinations.iter().map(move |(dest, _)| (*source, *symbol, *dest))))
+            .collect();
+        if let Some((source, symbol, dest)) = transitions.into_iter().find(|(source, _, dest)| !self.states.contains(source) || !self.states.contains(dest)) {
+            return Err(DotError::MissingTransitionState(source, symbol, dest));
+        }
+        let atomics: BTreeSet<(Symbol, Terminal, State)> = self.atomic_to_state.iter().map(|((symbol, terminal), (state, _))| (*symbol, *terminal, *state)).collect();
+        match atomics.into_iter().find(|(_, _, state)| !self.states.contains(state)) {
+            Some((symbol, terminal, state)) => Err(DotError::MissingAtomicState(symbol, terminal, state)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn simulate(&self, curr_state: &State, symbol: Symbol) -> Option<HashSet<(&State, &Rules, bool)>> {
+        self.transitions.get(&curr_state)?
+            .get(&symbol)
+            .map(|destinations| {
+                let mut res: HashSet<(&State, &Rules, bool)> = HashSet::new();
+                for (dest, rules) in destinations {
+                    res.insert((dest, rules, self.is_accepting(dest)));
+                }
+                res
+            })
+    }
+
+    /// Returns the number of destinations `simulate` would return for the state and symbol, 0 if there are none,
+    /// without collecting them.
+    pub fn transition_count(&self, curr_state: &State, symbol: &Symbol) -> usize {
+        self.transitions.get(curr_state)
+            .and_then(|trans_list| trans_list.get(symbol))
+            .map_or(0, |destinations| destinations.len())
+    }
+
+    /// Returns the state and symbol pairs with more than one destination, i.e. with more than one destination state or
+    /// more than one set of rules, sorted by state and symbol. These are the points at which alternative derivations
+    /// split, which guides to the derivation rules responsible for an ambiguity.
+    pub fn ambiguous_transitions(&self) -> Vec<(State, Symbol)> {
+        let res: BTreeSet<(State, Symbol)> = self.transitions.iter()
+            .flat_map(|(state, trans_list)| trans_list.iter()
+                .filter(|(_, destinations)| destinations.len() > 1)
+                .map(move |(symbol, _)| (*state, *symbol)))
+            .collect();
+        res.into_iter().collect()
+    }
+
+    /// Returns the transitions, as source, symbol and destination, which carry more than one set of rules, sorted by
+    /// source, symbol and destination. Unlike `ambiguous_transitions`, alternatives leading to different destinations
+    /// are not reported, only the alternatives which were merged into the same transition.
+    pub fn rule_conflicts(&self) -> Vec<(State, Symbol, State)> {
+        let mut rules_per_transition: BTreeMap<(State, Symbol, State), usize> = BTreeMap::new();
+        for (state, trans_list) in &self.transitions {
+            for (symbol, destinations) in trans_list {
+                for (dest, _) in destinations {
+                    *rules_per_transition.entry((*state, *symbol, *dest)).or_default() += 1;
+                }
+            }
+        }
+        rules_per_transition.into_iter().filter(|(_, count)| *count > 1).map(|(transition, _)| transition).collect()
+    }
+
+    pub fn is_accepting(&self, curr_state: &State) -> bool {
+        self.accepting_states.contains(curr_state)
+    }
+
+    pub fn get_start(&self) -> (State, bool) {
+        (self.start, self.is_accepting(&self.start))
+    }
+
+    /// Returns the atomic languages of the automaton, mapping every symbol and terminal to the state the atomic language
+    /// starts in and its rules.
+    pub fn atomic_map(&self) -> &HashMap<(Symbol, Terminal), (State, HashSet<Rules>)> {
+        &self.atomic_to_state
+    }
+
+    /// Assembles an automaton from its parts, e.g. from states and atomic languages built once with `build_atomic` and
+    /// reused for related grammars, or collected through `accept`. The start state, the accepting states, the sources
+    /// and destinations of the transitions and the states of the atomic map must all be states of the automaton,
+    /// otherwise the first missing state is returned as an error.
+    pub fn from_parts(states: HashSet<State>, accepting_states: HashSet<State>, start: State, transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>>, atomic_map: HashMap<(Symbol, Terminal), (State, HashSet<Rules>)>) -> Result<FiniteStateAutomaton, BuildError> {
+        let mut used: BTreeSet<State> = BTreeSet::from([start]);
+        used.extend(accepting_states.iter().cloned());
+        for (state, trans_list) in &transitions {
+            used.insert(*state);
+            used.extend(trans_list.values().flatten().map(|(dest, _)| *dest));
+        }
+        used.extend(atomic_map.values().map(|(state, _)| *state));
+        match used.into_iter().find(|state| !states.contains(state)) {
+            Some(state) => Err(BuildError::MissingState(state)),
+            None => Ok(FiniteStateAutomaton{states, accepting_states, start, transitions, atomic_to_state: atomic_map}),
+        }
+    }
+
+    pub fn get_atomic(&self, symbol: Symbol, terminal: Terminal) -> Option<(&State, &HashSet<Rules>, bool)> {
+        self.atomic_to_state.get(&(symbol, terminal))
+            .map(|(dest, rules_set)| (dest, rules_set, self.is_accepting(dest)))
+    }
+
+    /// Returns the alternative sequences of rules of the atomic language of the symbol and terminal in sorted order,
+    /// or an empty list when there is no such atomic language.
+    pub fn atomic_rule_options(&self, symbol: Symbol, terminal: Terminal) -> Vec<Rules> {
+        let mut res: Vec<Rules> = self.atomic_to_state.get(&(symbol, terminal))
+            .map(|(_, rules_set)| rules_set.iter().cloned().collect())
+            .unwrap_or_default();
+        res.sort();
+        res
     }
 
     pub fn has_transition(&self, curr_state: &State) -> bool {
@@ -332,4 +1578,1670 @@ impl FiniteStateAutomaton {
             .map_or(false, |trans_list| !trans_list.is_empty())
     }
 
-}
\ No newline at end of file
+    /// Returns the nonterminals with a transition from the state, i.e. the nonterminals whose atomic languages can be
+    /// entered there, in sorted order. Empty for a state without nonterminal transitions or which is not a state.
+    pub fn startable_nonterminals(&self, state: &State) -> BTreeSet<Nonterminal> {
+        self.transitions.get(state).into_iter().flat_map(|trans_list| trans_list.keys()).filter_map(|symbol| symbol.as_nonterminal()).collect()
+    }
+
+    /// Returns the terminals which can be consumed right after a transition on the nonterminal, i.e. right after the
+    /// nonterminal is completed, as the automaton is built. From the destination of every transition on the nonterminal,
+    /// the epsilon transitions are followed to the terminal transitions, whose terminals can follow, and to the
+    /// nonterminal transitions, whose atomic languages give the terminals they start with. If an accepting state is
+    /// reached, the atomic language the transition belongs to is completed as well, so the terminals following the
+    /// nonterminal of that atomic language can follow too. Completing the language of the start state ends the input,
+    /// after which no terminal follows.
+    pub fn automaton_follow(&self, nt: Nonterminal) -> HashSet<Terminal> {
+        let owners: HashMap<State, HashSet<Nonterminal>> = self.atomic_owners();
+
+        // the transitions on every nonterminal, with the terminals following their destinations and the nonterminals
+        // whose following terminals are added when their destinations complete an atomic language
+        let mut edges: HashMap<Nonterminal, (HashSet<Terminal>, HashSet<Nonterminal>)> = HashMap::new();
+        for (src, trans_list) in &self.transitions {
+            for (symbol, destinations) in trans_list {
+                let Symbol::Nonterminal(edge_nt) = symbol else {
+                    continue;
+                };
+                for (dest, _) in destinations {
+                    let (terminals, completes): (HashSet<Terminal>, bool) = self.first_terminals(*dest);
+                    let entry: &mut (HashSet<Terminal>, HashSet<Nonterminal>) = edges.entry(*edge_nt).or_default();
+                    entry.0.extend(terminals);
+                    if completes {
+                        entry.1.extend(owners.get(src).into_iter().flatten());
+                    }
+                }
+            }
+        }
+
+        let mut res: HashSet<Terminal> = HashSet::new();
+        let mut visited: HashSet<Nonterminal> = HashSet::from([nt]);
+        let mut to_visit: Vec<Nonterminal> = vec![nt];
+        while let Some(curr) = to_visit.pop() {
+            if let Some((terminals, completed)) = edges.get(&curr) {
+                res.extend(terminals);
+                to_visit.extend(completed.iter().filter(|owner| visited.insert(**owner)));
+            }
+        }
+        res
+    }
+
+    /// Maps every state to the terminals which can be consumed next from it, following epsilon transitions first. A
+    /// nonterminal transition can consume the terminals its atomic languages start with. The terminals which can only
+    /// be consumed after returning from an accepting state are not included, as they depend on the configuration the
+    /// state is in, see `automaton_follow`. The table is the same for equal automata, but it holds a set of terminals
+    /// for every state, so for large automata it takes memory proportional to the number of states times the size of
+    /// the alphabet, and computing it takes time proportional to the number of states times the number of transitions
+    /// in the worst case.
+    pub fn lookahead_table(&self) -> HashMap<State, HashSet<Terminal>> {
+        self.states.iter().map(|state| (*state, self.first_terminals(*state).0)).collect()
+    }
+
+    /// Determines whether the automaton is deterministic, i.e. whether every configuration has at most one way to
+    /// consume any terminal, so recognition never needs more than a single configuration. Epsilon transitions are
+    /// followed first, then from every state a terminal must be consumable by at most one transition, counting a
+    /// nonterminal transition if there is an atomic language of its nonterminal for the terminal, and every atomic
+    /// language must have a single sequence of rules. A state of an atomic language from which an accepting state can
+    /// be reached with epsilon transitions must not consume any terminal, as returning from the atomic language would be
+    /// a second choice. The start state is not part of an atomic language, so the automaton may continue after it has
+    /// accepted.
+    pub fn is_deterministic(&self) -> bool {
+        if self.atomic_to_state.iter().any(|((symbol, _), (_, rules_set))| matches!(symbol, Symbol::Nonterminal(_)) && rules_set.len() > 1) {
+            return false;
+        }
+        let owners: HashMap<State, HashSet<Nonterminal>> = self.atomic_owners();
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+
+        for state in &self.states {
+            let mut closure: HashSet<State> = HashSet::from([*state]);
+            let mut to_visit: Vec<State> = vec![*state];
+            while let Some(curr) = to_visit.pop() {
+                for (dest, _) in self.transitions.get(&curr).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)).into_iter().flatten() {
+                    if closure.insert(*dest) {
+                        to_visit.push(*dest);
+                    }
+                }
+            }
+            let may_return: bool = owners.contains_key(state) && closure.iter().any(|curr| self.is_accepting(curr));
+
+            for terminal in &alphabet {
+                let mut moves: usize = 0;
+                for (symbol, destinations) in closure.iter().flat_map(|curr| self.transitions.get(curr).into_iter().flatten()) {
+                    let consumes: bool = match symbol {
+                        Symbol::Terminal(t) => t == terminal,
+                        Symbol::Nonterminal(_) => self.atomic_to_state.contains_key(&(*symbol, *terminal)),
+                        Symbol::Epsilon => false,
+                    };
+                    if consumes {
+                        moves += destinations.len();
+                    }
+                }
+                if moves > 1 || (moves == 1 && may_return) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns a handle to recognize inputs with a single configuration if the automaton is deterministic, see
+    /// `is_deterministic`, or None otherwise. The check is made once, when the handle is created, and the handle
+    /// borrows the automaton, so it cannot change while the handle is used.
+    pub fn as_deterministic(&self) -> Option<DeterministicFsa<'_>> {
+        if !self.is_deterministic() {
+            return None;
+        }
+        Some(DeterministicFsa{finite_state_automaton: self})
+    }
+
+
+    /// Walks the automaton with the given visitor. States are visited first in ascending order, followed by the
+    /// transitions ordered by source, symbol, destination and rules, and finally the atomic languages ordered by key.
+    pub fn accept<V: FsaVisitor>(&self, visitor: &mut V) {
+        let states: BTreeSet<&State> = self.states.iter().collect();
+        for state in states {
+            visitor.visit_state(state, self.is_accepting(state));
+        }
+
+        let mut transitions: Vec<(&State, &Symbol, &State, &Rules)> = Vec::new();
+        for (source, transition_list) in &self.transitions {
+            for (symbol, destinations) in transition_list {
+                for (dest, rules) in destinations {
+                    transitions.push((source, symbol, dest, rules));
+                }
+            }
+        }
+        transitions.sort();
+        for (source, symbol, dest, rules) in transitions {
+            visitor.visit_transition(source, symbol, dest, rules);
+        }
+
+        let mut atomics: Vec<_> = self.atomic_to_state.iter().collect();
+        atomics.sort_by_key(|(key, _)| *key);
+        for ((symbol, terminal), (state, rules_set)) in atomics {
+            visitor.visit_atomic(symbol, terminal, state, rules_set);
+        }
+    }
+
+    /// Makes the transition relation total by adding a non-accepting trap state, which loops on every terminal, and
+    /// adding a transition to it for every state and terminal without a direct transition. If a trap state already
+    /// exists it is reused. The accepted language does not change, since no accepting state is reachable from the
+    /// trap state. Returns the trap state.
+    pub fn make_total(&mut self) -> State {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let trap: State = match self.find_trap_state(&alphabet) {
+            Some(trap) => trap,
+            None => {
+                let trap: State = self.states.iter().max().map_or(0, |state| state + 1);
+                self.states.insert(trap);
+                trap
+            },
+        };
+
+        for state in &self.states {
+            let transition_list: &mut HashMap<Symbol, HashSet<(State, Rules)>> = self.transitions.entry(*state).or_default();
+            for terminal in &alphabet {
+                transition_list.entry(Symbol::Terminal(*terminal)).or_insert_with(|| HashSet::from([(trap, Vec::new())]));
+            }
+        }
+        trap
+    }
+
+    // Finds a non-accepting state whose only transitions are loops on every terminal of the alphabet.
+    fn find_trap_state(&self, alphabet: &BTreeSet<Terminal>) -> Option<State> {
+        self.transitions.iter()
+            .find(|(state, transition_list)| {
+                !self.is_accepting(state) && transition_list.len() == alphabet.len() && alphabet.iter().all(|terminal| {
+                    transition_list.get(&Symbol::Terminal(*terminal)).is_some_and(|destinations| *destinations == HashSet::from([(**state, Vec::new())]))
+                })
+            })
+            .map(|(state, _)| *state)
+    }
+
+    /// Builds the product of the automaton with a deterministic automaton over the terminals, given by its start state,
+    /// its accepting states and its transition function, where None means the transition function is undefined. The
+    /// result accepts exactly the strings accepted by both. Nonterminal transitions are relabelled with a fresh
+    /// nonterminal per pair of states the deterministic automaton is in before and after the nonterminal, so only the
+    /// derivation rules on the transitions keep referring to the original nonterminals.
+    pub fn restrict_by<D: Fn(&State, Terminal) -> Option<State>>(&self, dfa_start: State, dfa_accept: &HashSet<State>, step: D) -> FiniteStateAutomaton {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut dfa_states: BTreeSet<State> = BTreeSet::from([dfa_start]);
+        let mut to_explore: Vec<State> = vec![dfa_start];
+        while let Some(dfa_state) = to_explore.pop() {
+            for terminal in &alphabet {
+                if let Some(next) = step(&dfa_state, *terminal) {
+                    if dfa_states.insert(next) {
+                        to_explore.push(next);
+                    }
+                }
+            }
+        }
+
+        let used_nonterminals: BTreeSet<Nonterminal> = self.nonterminals();
+        let mut fresh_nonterminals = (0xE000..).filter_map(char::from_u32).filter(|c| !used_nonterminals.contains(c));
+
+        // A product state is a state of this automaton, the state of the deterministic automaton, and the state the
+        // deterministic automaton has to be in when returning from the current atomic language, or None at the top.
+        let mut product_states: HashMap<ProductState, State> = HashMap::from([((self.start, dfa_start, None), 0)]);
+        let mut relabelled: HashMap<(Nonterminal, State, State), Nonterminal> = HashMap::new();
+        let mut to_visit: Vec<ProductState> = vec![(self.start, dfa_start, None)];
+        let mut res: FiniteStateAutomaton = FiniteStateAutomaton{states: HashSet::from([0]), accepting_states: HashSet::new(), start: 0,
+            transitions: HashMap::new(), atomic_to_state: HashMap::new()};
+
+        while let Some(product_state) = to_visit.pop() {
+            let (state, dfa_state, goal) = product_state;
+            let source: State = product_states[&product_state];
+            let accepting: bool = match goal {
+                Some(goal_state) => dfa_state == goal_state,
+                None => dfa_accept.contains(&dfa_state),
+            };
+            if accepting && self.is_accepting(&state) {
+                res.accepting_states.insert(source);
+            }
+
+            let Some(transition_list) = self.transitions.get(&state) else {
+                continue;
+            };
+            for (symbol, destinations) in transition_list {
+                // The new symbol, the state of the deterministic automaton after it and the atomic languages it leads to.
+                let mut targets: Vec<(Symbol, State, Vec<ProductAtomic>)> = Vec::new();
+                match symbol {
+                    Symbol::Terminal(t) => {
+                        if let Some(next) = step(&dfa_state, *t) {
+                            targets.push((*symbol, next, Vec::new()));
+                        }
+                    },
+                    Symbol::Epsilon => {
+                        targets.push((*symbol, dfa_state, Vec::new()));
+                    },
+                    Symbol::Nonterminal(nt) => {
+                        for dfa_return in &dfa_states {
+                            let new_nt: Nonterminal = *relabelled.entry((*nt, dfa_state, *dfa_return)).or_insert_with(|| fresh_nonterminals.next().unwrap());
+                            let mut atomics: Vec<ProductAtomic> = Vec::new();
+                            for terminal in &alphabet {
+                                if let (Some((atomic_state, rules_set)), Some(next)) = (self.atomic_to_state.get(&(*symbol, *terminal)), step(&dfa_state, *terminal)) {
+                                    atomics.push(((Symbol::Nonterminal(new_nt), *terminal), (*atomic_state, next, Some(*dfa_return)), rules_set.clone()));
+                                }
+                            }
+                            if !atomics.is_empty() {
+                                targets.push((Symbol::Nonterminal(new_nt), *dfa_return, atomics));
+                            }
+                        }
+                    },
+                }
+
+                for (new_symbol, next, atomics) in targets {
+                    for (dest, rules) in destinations {
+                        let dest_state: State = res.product_state(&mut product_states, &mut to_visit, (*dest, next, goal));
+                        res.transitions.entry(source).or_default().entry(new_symbol).or_default().insert((dest_state, rules.clone()));
+                    }
+                    for (key, atomic_product, rules_set) in atomics {
+                        let atomic_state: State = res.product_state(&mut product_states, &mut to_visit, atomic_product);
+                        res.atomic_to_state.insert(key, (atomic_state, rules_set));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Builds an automaton accepting the strings accepted by this automaton but not by the other one, so `self` accepts
+    /// a subset of the language of `other` exactly if the difference `is_empty_language`. The other automaton is made
+    /// deterministic by the subset construction over its sets of configurations, after which its complement is
+    /// intersected with this automaton through `restrict_by`. The difference of two context-free languages need not be
+    /// context-free, so the subset construction gives up as soon as one of the configurations of the other automaton
+    /// gets deeper than its number of states, returning BuildError::ConfigurationDepthExceeded: a state repeats on such
+    /// a configuration, so the configurations may grow without bound. Before comparing, the tail frames are removed
+    /// from the configurations, see `collapse_tail_frames`, so right recursion like `S -> aS | b` keeps them bounded.
+    /// Left recursion is resolved within the atomic languages and is fine as well, which leaves self-embedding
+    /// grammars, for which the error is returned.
+    pub fn difference(&self, other: &FiniteStateAutomaton) -> Result<FiniteStateAutomaton, BuildError> {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let max_depth: usize = other.states.len();
+        let sorted = |configurations: HashSet<Configuration>| -> Vec<Configuration> {
+            let mut res: Vec<Configuration> = configurations.into_iter().map(|configuration| other.collapse_tail_frames(configuration)).collect();
+            res.sort();
+            res.dedup();
+            res
+        };
+
+        // every set of configurations of the other automaton is a state of the deterministic automaton, the empty set
+        // being the state it is in once it rejects every continuation
+        let start_set: Vec<Configuration> = sorted(other.start_configurations());
+        let mut set_numbers: HashMap<Vec<Configuration>, State> = HashMap::from([(start_set.clone(), 0)]);
+        let mut to_explore: Vec<Vec<Configuration>> = vec![start_set];
+        let mut step_table: HashMap<(State, Terminal), State> = HashMap::new();
+        let mut complement_accept: HashSet<State> = HashSet::new();
+        while let Some(configurations) = to_explore.pop() {
+            if configurations.iter().any(|configuration| configuration.len() > max_depth) {
+                return Err(BuildError::ConfigurationDepthExceeded(max_depth));
+            }
+            let number: State = set_numbers[&configurations];
+            if !configurations.iter().any(|configuration| is_accepting_configuration(other, configuration)) {
+                complement_accept.insert(number);
+            }
+            let configuration_set: HashSet<Configuration> = configurations.iter().cloned().collect();
+            for terminal in &alphabet {
+                let next: Vec<Configuration> = sorted(other.derive_configurations(&configuration_set, *terminal));
+                let new_number: State = set_numbers.len();
+                let next_number: State = *set_numbers.entry(next.clone()).or_insert_with(|| {
+                    to_explore.push(next);
+                    new_number
+                });
+                step_table.insert((number, *terminal), next_number);
+            }
+        }
+
+        Ok(self.restrict_by(0, &complement_accept, |state: &State, terminal: Terminal| step_table.get(&(*state, terminal)).cloned()))
+    }
+
+    // Removes the tail frames below the top of the configuration: frames from whose state only epsilon transitions
+    // lead, to an accepting state among others, like the end of a right recursive rule. Returning to such a frame can
+    // only be followed by returning further, or accepting if it is the last frame, so the configuration behaves the
+    // same without it.
+    fn collapse_tail_frames(&self, mut configuration: Configuration) -> Configuration {
+        let top: State = configuration.pop().unwrap();
+        let mut closure: Vec<State> = Vec::new();
+        configuration.retain(|state| {
+            self.epsilon_closure_into(*state, &mut closure);
+            let only_epsilon: bool = closure.iter().all(|curr| self.transitions.get(curr).into_iter().flatten()
+                .all(|(symbol, destinations)| *symbol == Symbol::Epsilon || destinations.is_empty()));
+            !(only_epsilon && closure.iter().any(|curr| self.is_accepting(curr)))
+        });
+        configuration.push(top);
+        configuration
+    }
+
+    // Looks up the state of the product automaton, adding it and scheduling it for a visit when it is new.
+    fn product_state(&mut self, product_states: &mut HashMap<ProductState, State>, to_visit: &mut Vec<ProductState>, product_state: ProductState) -> State {
+        let new_state: State = product_states.len();
+        let res: State = *product_states.entry(product_state).or_insert_with(|| {
+            to_visit.push(product_state);
+            new_state
+        });
+        self.states.insert(res);
+        res
+    }
+
+    /// Returns the number of states, accepting states, transitions and atomic languages of the automaton.
+    pub fn stats(&self) -> FsaStats {
+        FsaStats {
+            states: self.states.len(),
+            accepting_states: self.accepting_states.len(),
+            transitions: self.transitions.values().flat_map(|transition_list| transition_list.values()).map(|destinations| destinations.len()).sum(),
+            atomic_entries: self.atomic_to_state.len(),
+        }
+    }
+
+    /// Returns every set of rules carried by a transition or an atomic language, leaving out empty ones. Comparing the
+    /// rules in them with the rules of the grammar shows which derivation rules the automaton does not use.
+    pub fn used_rules(&self) -> HashSet<Rules> {
+        let transition_rules = self.transitions.values().flat_map(|trans_list| trans_list.values().flatten()).map(|(_, rules)| rules);
+        let atomic_rules = self.atomic_to_state.values().flat_map(|(_, rules_set)| rules_set);
+        transition_rules.chain(atomic_rules).filter(|rules| !rules.is_empty()).cloned().collect()
+    }
+
+    /// Returns the outgoing transitions of every state without their derivation rules, sorted by symbol and
+    /// destination. A transition which is present for several sets of rules is listed once. States without outgoing
+    /// transitions are mapped to an empty list.
+    pub fn adjacency(&self) -> HashMap<State, Vec<(Symbol, State)>> {
+        let mut res: HashMap<State, Vec<(Symbol, State)>> = self.states.iter().map(|state| (*state, Vec::new())).collect();
+        for (state, transition_list) in &self.transitions {
+            let edges: BTreeSet<(Symbol, State)> = transition_list.iter()
+                .flat_map(|(symbol, destinations)| destinations.iter().map(|(dest, _)| (*symbol, *dest)))
+                .collect();
+            res.entry(*state).or_default().extend(edges);
+        }
+        res
+    }
+
+    /// Converts the automaton into a petgraph graph, so the algorithms of petgraph can be run on it. Every state is a
+    /// node weighted by the state, added in sorted order, and every transition is an edge weighted by its symbol. Like
+    /// in `adjacency` the derivation rules are dropped, so a transition which is present for several sets of rules is
+    /// a single edge. The map gives the node of every state.
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> (Graph<State, Symbol>, HashMap<State, NodeIndex>) {
+        let mut graph: Graph<State, Symbol> = Graph::with_capacity(self.states.len(), 0);
+        let mut states: Vec<State> = self.states.iter().cloned().collect();
+        states.sort();
+        let nodes: HashMap<State, NodeIndex> = states.iter().map(|state| (*state, graph.add_node(*state))).collect();
+        let adjacency: HashMap<State, Vec<(Symbol, State)>> = self.adjacency();
+        for state in &states {
+            for (symbol, dest) in &adjacency[state] {
+                graph.add_edge(nodes[state], nodes[dest], *symbol);
+            }
+        }
+        (graph, nodes)
+    }
+
+    /// Lists the cycles consisting of epsilon transitions only, each as the states along it starting at its smallest
+    /// state, sorted. A self-loop is a cycle of a single state. `build_fsa` only creates epsilon self-loops, for the
+    /// words of a Kleene star which are nulled, as the star starts and ends in the same state; any longer cycle points
+    /// at a construction which introduced it by accident. Every elementary cycle is listed once, which can be
+    /// exponentially many for a dense epsilon graph, so this is meant as a check rather than for large automata.
+    pub fn epsilon_cycles(&self) -> Vec<Vec<State>> {
+        let epsilon_successors = |state: State| -> BTreeSet<State> {
+            self.transitions.get(&state).and_then(|transition_list| transition_list.get(&Symbol::Epsilon))
+                .map(|destinations| destinations.iter().map(|(dest, _)| *dest).collect())
+                .unwrap_or_default()
+        };
+        let sorted_states: BTreeSet<State> = self.states.iter().cloned().collect();
+        let mut res: Vec<Vec<State>> = Vec::new();
+        // every cycle is found from its smallest state, only visiting larger states on the way
+        for first in &sorted_states {
+            let mut path: Vec<State> = vec![*first];
+            let mut to_visit: Vec<Vec<State>> = vec![epsilon_successors(*first).into_iter().rev().collect()];
+            while let Some(successors) = to_visit.last_mut() {
+                match successors.pop() {
+                    Some(succ) if succ == *first => res.push(path.clone()),
+                    Some(succ) if succ > *first && !path.contains(&succ) => {
+                        path.push(succ);
+                        to_visit.push(epsilon_successors(succ).into_iter().rev().collect());
+                    },
+                    Some(_) => {},
+                    None => {
+                        to_visit.pop();
+                        path.pop();
+                    },
+                }
+            }
+        }
+        res.sort();
+        res
+    }
+
+    /// Returns the strongly connected components of the transition graph, epsilon transitions included, using Tarjan's
+    /// algorithm. Every state is in exactly one component and the components are in reverse topological order: no
+    /// component has a transition to a component after it. A component is cyclic if it has more than one state, or a
+    /// single state with a transition to itself.
+    pub fn sccs(&self) -> Vec<HashSet<State>> {
+        let adjacency: HashMap<State, Vec<(Symbol, State)>> = self.adjacency();
+        let mut index_of: HashMap<State, usize> = HashMap::new();
+        let mut low_link: HashMap<State, usize> = HashMap::new();
+        let mut stack: Vec<State> = Vec::new();
+        let mut on_stack: HashSet<State> = HashSet::new();
+        let mut res: Vec<HashSet<State>> = Vec::new();
+
+        let sorted_states: BTreeSet<State> = self.states.iter().cloned().collect();
+        for root in sorted_states {
+            if index_of.contains_key(&root) {
+                continue;
+            }
+            // every entry is a state together with the position of the next successor to visit
+            let mut call_stack: Vec<(State, usize)> = vec![(root, 0)];
+            while let Some((state, next)) = call_stack.pop() {
+                if next == 0 {
+                    index_of.insert(state, index_of.len());
+                    low_link.insert(state, index_of[&state]);
+                    stack.push(state);
+                    on_stack.insert(state);
+                } else {
+                    // returning from the successor visited last
+                    let (_, succ) = adjacency[&state][next - 1];
+                    if on_stack.contains(&succ) {
+                        low_link.insert(state, low_link[&state].min(low_link[&succ]));
+                    }
+                }
+
+                let mut pos: usize = next;
+                while pos < adjacency[&state].len() {
+                    let (_, succ) = adjacency[&state][pos];
+                    if !index_of.contains_key(&succ) {
+                        break;
+                    }
+                    if on_stack.contains(&succ) {
+                        low_link.insert(state, low_link[&state].min(index_of[&succ]));
+                    }
+                    pos += 1;
+                }
+                if pos < adjacency[&state].len() {
+                    call_stack.push((state, pos + 1));
+                    call_stack.push((adjacency[&state][pos].1, 0));
+                    continue;
+                }
+
+                if low_link[&state] == index_of[&state] {
+                    let mut component: HashSet<State> = HashSet::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(&member);
+                        component.insert(member);
+                        if member == state {
+                            break;
+                        }
+                    }
+                    res.push(component);
+                }
+            }
+        }
+        res
+    }
+
+    /// Determines whether a state can reach itself by following only epsilon transitions. Such cycles arise from
+    /// nullable recursive nonterminals, e.g. `A -> AA | e`.
+    pub fn has_epsilon_cycle(&self) -> bool {
+        self.states.iter().any(|state| {
+            let mut visited: HashSet<State> = HashSet::new();
+            let mut to_visit: Vec<State> = vec![*state];
+            while let Some(curr) = to_visit.pop() {
+                if let Some(destinations) = self.transitions.get(&curr).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)) {
+                    for (dest, _) in destinations {
+                        if dest == state {
+                            return true;
+                        }
+                        if visited.insert(*dest) {
+                            to_visit.push(*dest);
+                        }
+                    }
+                }
+            }
+            false
+        })
+    }
+
+    /// Produces an equivalent automaton without epsilon transitions. Every state gets the transitions of the states it
+    /// reaches by epsilon transitions, and becomes accepting if one of them is accepting. The rules of the epsilon
+    /// transitions are folded into the transition which follows them, in the order in which the parser collects them:
+    /// the rules of the later transition come first. Epsilon paths are followed without visiting a state twice, so
+    /// epsilon cycles only contribute the rules of their simple paths. The rules of an epsilon path which ends in
+    /// acceptance have no transition to be folded into and are dropped, so the result is meant for recognizing. States
+    /// which can no longer be reached are removed.
+    pub fn remove_epsilon(&self) -> FiniteStateAutomaton {
+        let mut transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::new();
+        let mut accepting_states: HashSet<State> = HashSet::new();
+        for state in &self.states {
+            for (reached, path_rules) in self.epsilon_paths(*state) {
+                if self.is_accepting(&reached) {
+                    accepting_states.insert(*state);
+                }
+                for (symbol, destinations) in self.transitions.get(&reached).into_iter().flatten() {
+                    if *symbol == Symbol::Epsilon {
+                        continue;
+                    }
+                    let new_destinations: &mut HashSet<(State, Rules)> = transitions.entry(*state).or_default().entry(*symbol).or_default();
+                    for (dest, rules) in destinations {
+                        new_destinations.insert((*dest, [rules.clone(), path_rules.clone()].concat()));
+                    }
+                }
+            }
+        }
+        let mut res: FiniteStateAutomaton = FiniteStateAutomaton{states: self.states.clone(), accepting_states, start: self.start, transitions, atomic_to_state: self.atomic_to_state.clone()};
+        res.remove_unreachable_states();
+        res
+    }
+
+    // Returns the states reachable from the state by simple paths of epsilon transitions, the state itself included,
+    // each with the rules collected along the path, the rules of later transitions first.
+    fn epsilon_paths(&self, state: State) -> HashSet<(State, Rules)> {
+        let mut res: HashSet<(State, Rules)> = HashSet::new();
+        let mut to_visit: Vec<(State, Rules, Vec<State>)> = vec![(state, Vec::new(), vec![state])];
+        while let Some((curr, rules, path)) = to_visit.pop() {
+            if let Some(destinations) = self.transitions.get(&curr).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)) {
+                for (dest, dest_rules) in destinations {
+                    if !path.contains(dest) {
+                        to_visit.push((*dest, [dest_rules.clone(), rules.clone()].concat(), [&path[..], &[*dest]].concat()));
+                    }
+                }
+            }
+            res.insert((curr, rules));
+        }
+        res
+    }
+
+    /// Returns all terminals the automaton can consume, either directly or through an atomic language.
+    pub fn alphabet(&self) -> BTreeSet<Terminal> {
+        let mut res: BTreeSet<Terminal> = BTreeSet::new();
+        for (_, terminal) in self.atomic_to_state.keys() {
+            res.insert(*terminal);
+        }
+        for transition_list in self.transitions.values() {
+            for symbol in transition_list.keys() {
+                if let Symbol::Terminal(terminal) = symbol {
+                    res.insert(*terminal);
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns all nonterminals appearing in the automaton, either on a transition or as key of an atomic language.
+    pub fn nonterminals(&self) -> BTreeSet<Nonterminal> {
+        let mut res: BTreeSet<Nonterminal> = BTreeSet::new();
+        for symbol in self.transitions.values().flat_map(|transition_list| transition_list.keys()).chain(self.atomic_to_state.keys().map(|(symbol, _)| symbol)) {
+            if let Symbol::Nonterminal(nonterminal) = symbol {
+                res.insert(*nonterminal);
+            }
+        }
+        res
+    }
+
+    /// Returns the accepting states which cannot be reached from the start state, where a nonterminal transition also
+    /// reaches the states of the atomic languages of its nonterminal. `build_fsa` builds the atomic languages of every
+    /// nonterminal, so the accepting states of nonterminals which are never entered from the start are reported, e.g.
+    /// of `B` for `A -> Ba | a` and `B -> Ab | b`, whose left recursion is resolved within the atomic languages of `A`.
+    /// Any other accepting state that is reported points to a bug in the construction.
+    pub fn unreachable_accepting(&self) -> HashSet<State> {
+        let reachable: HashSet<State> = self.reachable_states();
+        self.accepting_states.iter().filter(|state| !reachable.contains(state)).cloned().collect()
+    }
+
+    // Calculates the states reachable from the start state, entering atomic languages through nonterminal transitions.
+    fn reachable_states(&self) -> HashSet<State> {
+        self.reachable_from(vec![self.start])
+    }
+
+    // Determines the states reachable from the given states, including the states of the atomic languages entered
+    // through nonterminal transitions.
+    fn reachable_from(&self, states: Vec<State>) -> HashSet<State> {
+        let mut res: HashSet<State> = states.iter().cloned().collect();
+        let mut to_visit: Vec<State> = states;
+        while let Some(state) = to_visit.pop() {
+            for (symbol, destinations) in self.transitions.get(&state).into_iter().flatten() {
+                let atomic_states: Vec<State> = match symbol {
+                    Symbol::Nonterminal(_) => self.atomic_states_of(symbol),
+                    _ => Vec::new(),
+                };
+                for dest in destinations.iter().map(|(dest, _)| *dest).chain(atomic_states) {
+                    if res.insert(dest) {
+                        to_visit.push(dest);
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Determines whether the automaton accepts no string at all, i.e. whether no accepting state can be reached from the
+    /// start state, where a nonterminal transition can only be taken if one of its atomic languages is not empty. This
+    /// is cheaper than `shortest_accepted`, as no configurations are explored.
+    pub fn is_empty_language(&self) -> bool {
+        !self.productive_states().contains(&self.start)
+    }
+
+    /// Returns a shortest terminal string accepted by the automaton, or None if it accepts no string at all.
+    /// Configurations are explored breadth-first, so the empty string is returned as Some(vec![]) if it is accepted.
+    pub fn shortest_accepted(&self) -> Option<Vec<Terminal>> {
+        let productive: HashSet<State> = self.productive_states();
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut visited: HashSet<Configuration> = HashSet::new();
+        let mut queue: VecDeque<(Configuration, Vec<Terminal>)> = VecDeque::new();
+
+        for configuration in self.start_configurations() {
+            if configuration.iter().all(|state| productive.contains(state)) {
+                visited.insert(configuration.clone());
+                queue.push_back((configuration, Vec::new()));
+            }
+        }
+
+        while let Some((configuration, word)) = queue.pop_front() {
+            if is_accepting_configuration(self, &configuration) {
+                return Some(word);
+            }
+            for terminal in &alphabet {
+                let mut next_configurations: HashSet<Configuration> = HashSet::new();
+                derive_into(self, &configuration, *terminal, &mut next_configurations);
+                for next_configuration in next_configurations {
+                    if next_configuration.iter().all(|state| productive.contains(state)) && visited.insert(next_configuration.clone()) {
+                        queue.push_back((next_configuration, [&word[..], &[*terminal]].concat()));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns a shortest accepted string together with the rules collected while recognizing it, or None if the
+    /// automaton accepts no string at all. Like `shortest_accepted`, configurations are explored breadth-first, but
+    /// every configuration keeps the rules of the transitions taken to reach it and of the atomic languages entered on
+    /// the way, in that order, leaving out empty rules. When an atomic language has several sequences of rules, the
+    /// smallest is taken, so the result is the same for equal automata. If the empty string is accepted, it is returned
+    /// with the rules of the epsilon transitions leading to acceptance. These are usually none, as the automaton does not
+    /// record how the start nonterminal derives the empty word, just like the parser reports no rules for empty input.
+    pub fn shortest_derivation(&self) -> Option<(Vec<Terminal>, Vec<Rules>)> {
+        let productive: HashSet<State> = self.productive_states();
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let sorted_transitions = |state: State| -> Vec<(Symbol, State, &Rules)> {
+            let mut res: Vec<(Symbol, State, &Rules)> = self.transitions.get(&state).into_iter().flatten()
+                .flat_map(|(symbol, destinations)| destinations.iter().map(move |(dest, rules)| (*symbol, *dest, rules)))
+                .collect();
+            res.sort();
+            res
+        };
+        let with_rules = |trail: &[Rules], added: &[&Rules]| -> Vec<Rules> {
+            trail.iter().chain(added.iter().cloned()).filter(|rules| !rules.is_empty()).cloned().collect()
+        };
+
+        // configurations are only marked as visited once taken from the queue, as one reached without consuming a
+        // terminal may already be queued behind the ones which do
+        let mut visited: HashSet<Configuration> = HashSet::new();
+        let mut queue: VecDeque<(Configuration, Vec<Terminal>, Vec<Rules>)> = VecDeque::from([(vec![self.start], Vec::new(), Vec::new())]);
+        while let Some((configuration, word, trail)) = queue.pop_front() {
+            if !visited.insert(configuration.clone()) {
+                continue;
+            }
+            if is_accepting_configuration(self, &configuration) {
+                return Some((word, trail));
+            }
+            // following an epsilon transition or returning from an accepting state does not consume a terminal, so these
+            // configurations are explored before the ones which do
+            let top: State = configuration[configuration.len() - 1];
+            let mut closed: Vec<(Configuration, Vec<Rules>)> = Vec::new();
+            if configuration.len() > 1 && self.is_accepting(&top) {
+                closed.push((configuration[..configuration.len() - 1].to_vec(), trail.clone()));
+            }
+            for (symbol, dest, rules) in sorted_transitions(top) {
+                if symbol == Symbol::Epsilon {
+                    closed.push(([&configuration[..configuration.len() - 1], &[dest]].concat(), with_rules(&trail, &[rules])));
+                }
+            }
+            for (next, next_trail) in closed {
+                if next.iter().all(|state| productive.contains(state)) && !visited.contains(&next) {
+                    queue.push_front((next, word.clone(), next_trail));
+                }
+            }
+
+            for terminal in &alphabet {
+                for (symbol, dest, rules) in sorted_transitions(top) {
+                    let (next, next_trail): (Configuration, Vec<Rules>) = match symbol {
+                        Symbol::Terminal(t) if t == *terminal => ([&configuration[..configuration.len() - 1], &[dest]].concat(), with_rules(&trail, &[rules])),
+                        Symbol::Nonterminal(_) => match self.atomic_to_state.get(&(symbol, *terminal)) {
+                            Some((atomic_state, rules_set)) => {
+                                let atomic_rules: Option<&Rules> = rules_set.iter().min();
+                                let added: Vec<&Rules> = std::iter::once(rules).chain(atomic_rules).collect();
+                                ([&configuration[..configuration.len() - 1], &[dest, *atomic_state]].concat(), with_rules(&trail, &added))
+                            },
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    if next.iter().all(|state| productive.contains(state)) && !visited.contains(&next) {
+                        queue.push_back((next, [&word[..], &[*terminal]].concat(), next_trail));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns every accepted string of at most `max_len` terminals, shortest first and strings of the same length
+    /// ordered by the priority of their terminals in `order`, so the result does not depend on hashing. Terminals in
+    /// `order` which the automaton cannot consume are ignored, terminals missing from it come after the given ones in
+    /// sorted order.
+    pub fn enumerate_ordered(&self, max_len: usize, order: &[Terminal]) -> Vec<Vec<Terminal>> {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut priority: Vec<Terminal> = Vec::with_capacity(alphabet.len());
+        for terminal in order.iter().chain(alphabet.iter()) {
+            if alphabet.contains(terminal) && !priority.contains(terminal) {
+                priority.push(*terminal);
+            }
+        }
+
+        let productive: HashSet<State> = self.productive_states();
+        let mut res: Vec<Vec<Terminal>> = Vec::new();
+        let mut level: Vec<(HashSet<Configuration>, Vec<Terminal>)> = vec![(self.start_configurations(), Vec::new())];
+        for len in 0..=max_len {
+            let mut next_level: Vec<(HashSet<Configuration>, Vec<Terminal>)> = Vec::new();
+            for (configurations, word) in level {
+                if configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)) {
+                    res.push(word.clone());
+                }
+                if len == max_len {
+                    continue;
+                }
+                for terminal in &priority {
+                    let mut next_configurations: HashSet<Configuration> = self.derive_configurations(&configurations, *terminal);
+                    next_configurations.retain(|configuration| configuration.iter().all(|state| productive.contains(state)));
+                    if !next_configurations.is_empty() {
+                        next_level.push((next_configurations, [&word[..], &[*terminal]].concat()));
+                    }
+                }
+            }
+            level = next_level;
+        }
+        res
+    }
+
+    /// Determines whether the automaton accepts infinitely many strings, i.e. whether a cycle consuming terminals can be
+    /// reached from the start and can still reach acceptance, see `length_bounds`. If it does not, enumerating the
+    /// strings up to the longest accepted length is exhaustive. An empty language is finite.
+    pub fn is_infinite_language(&self) -> bool {
+        matches!(self.length_bounds(), Some((_, None)))
+    }
+
+    /// Returns the length of the shortest accepted string and, if the language is finite, the length of the longest
+    /// accepted string, or None if the automaton accepts no string at all. The language is infinite when a cycle which
+    /// consumes terminals can be taken on the way to acceptance, either within a level of the automaton or by
+    /// repeatedly entering atomic languages, like for `S -> aSb`.
+    pub fn length_bounds(&self) -> Option<(usize, Option<usize>)> {
+        let shortest: HashMap<State, usize> = self.lengths_to_accept(usize::min);
+        let shortest_len: usize = *shortest.get(&self.start)?;
+
+        // the steps between useful states, marked with whether they consume a terminal
+        let mut steps: HashMap<State, Vec<(State, bool)>> = HashMap::new();
+        for (source, transition_list) in &self.transitions {
+            for (symbol, destinations) in transition_list {
+                for (dest, _) in destinations {
+                    if !shortest.contains_key(dest) {
+                        continue;
+                    }
+                    match symbol {
+                        Symbol::Epsilon => steps.entry(*source).or_default().push((*dest, false)),
+                        Symbol::Terminal(_) => steps.entry(*source).or_default().push((*dest, true)),
+                        Symbol::Nonterminal(_) => {
+                            for atomic_state in self.atomic_states_of(symbol) {
+                                if shortest.contains_key(&atomic_state) {
+                                    steps.entry(*source).or_default().extend([(atomic_state, true), (*dest, true)]);
+                                }
+                            }
+                        },
+                    }
+                }
+            }
+        }
+        let reachable_from = |state: State| -> HashSet<State> {
+            let mut res: HashSet<State> = HashSet::from([state]);
+            let mut to_visit: Vec<State> = vec![state];
+            while let Some(curr) = to_visit.pop() {
+                for (next, _) in steps.get(&curr).into_iter().flatten() {
+                    if res.insert(*next) {
+                        to_visit.push(*next);
+                    }
+                }
+            }
+            res
+        };
+
+        let infinite: bool = reachable_from(self.start).iter().any(|state| {
+            steps.get(state).into_iter().flatten().any(|(next, consuming)| *consuming && reachable_from(*next).contains(state))
+        });
+        if infinite {
+            return Some((shortest_len, None));
+        }
+        Some((shortest_len, self.lengths_to_accept(usize::max).get(&self.start).cloned()))
+    }
+
+    // Calculates for every state from which an accepting state can be reached the shortest or longest length of the
+    // strings which take it there, combining the lengths with the given function. For the longest lengths the language
+    // must be finite, otherwise the lengths keep growing.
+    fn lengths_to_accept(&self, combine: fn(usize, usize) -> usize) -> HashMap<State, usize> {
+        let mut res: HashMap<State, usize> = self.accepting_states.iter().map(|state| (*state, 0)).collect();
+        let mut changed: bool = true;
+
+        while changed {
+            changed = false;
+            for (state, transition_list) in &self.transitions {
+                for (symbol, destinations) in transition_list {
+                    for (dest, _) in destinations {
+                        let Some(dest_len) = res.get(dest).cloned() else {
+                            continue;
+                        };
+                        let lengths: Vec<usize> = match symbol {
+                            Symbol::Epsilon => vec![dest_len],
+                            Symbol::Terminal(_) => vec![dest_len + 1],
+                            Symbol::Nonterminal(_) => self.atomic_states_of(symbol).iter()
+                                .filter_map(|atomic_state| res.get(atomic_state).map(|atomic_len| 1 + atomic_len + dest_len))
+                                .collect(),
+                        };
+                        for len in lengths {
+                            let new_len: usize = res.get(state).map_or(len, |curr| combine(*curr, len));
+                            if res.get(state) != Some(&new_len) {
+                                res.insert(*state, new_len);
+                                changed = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    // Returns the terminals which can be consumed first from the state after following epsilon transitions, where a
+    // nonterminal transition can consume the terminals its atomic languages start with, and whether an accepting state
+    // can be reached without consuming any.
+    fn first_terminals(&self, state: State) -> (HashSet<Terminal>, bool) {
+        let mut terminals: HashSet<Terminal> = HashSet::new();
+        let mut completes: bool = false;
+        let mut visited: HashSet<State> = HashSet::from([state]);
+        let mut to_visit: Vec<State> = vec![state];
+        while let Some(curr) = to_visit.pop() {
+            completes |= self.is_accepting(&curr);
+            for (symbol, destinations) in self.transitions.get(&curr).into_iter().flatten() {
+                match symbol {
+                    Symbol::Terminal(t) => {
+                        terminals.insert(*t);
+                    },
+                    Symbol::Nonterminal(_) => terminals.extend(self.atomic_to_state.keys().filter(|(atomic_symbol, _)| atomic_symbol == symbol).map(|(_, t)| *t)),
+                    Symbol::Epsilon => to_visit.extend(destinations.iter().map(|(dest, _)| *dest).filter(|dest| visited.insert(*dest))),
+                }
+            }
+        }
+        (terminals, completes)
+    }
+
+    // Replaces the contents of the closure by the states reachable from the state by epsilon transitions, including
+    // the state itself.
+    fn epsilon_closure_into(&self, state: State, closure: &mut Vec<State>) {
+        closure.clear();
+        closure.push(state);
+        let mut index: usize = 0;
+        while index < closure.len() {
+            for (dest, _) in self.transitions.get(&closure[index]).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)).into_iter().flatten() {
+                if !closure.contains(dest) {
+                    closure.push(*dest);
+                }
+            }
+            index += 1;
+        }
+    }
+
+    // Maps every state of an atomic language of a nonterminal to the nonterminals of the atomic languages it belongs to,
+    // following the transitions from the states of the atomic languages without entering other atomic languages.
+    fn atomic_owners(&self) -> HashMap<State, HashSet<Nonterminal>> {
+        let mut res: HashMap<State, HashSet<Nonterminal>> = HashMap::new();
+        for ((symbol, _), (atomic_state, _)) in &self.atomic_to_state {
+            let Symbol::Nonterminal(owner) = symbol else {
+                continue;
+            };
+            let mut to_visit: Vec<State> = vec![*atomic_state];
+            while let Some(state) = to_visit.pop() {
+                if res.entry(state).or_default().insert(*owner) {
+                    to_visit.extend(self.transitions.get(&state).into_iter().flat_map(|trans_list| trans_list.values().flatten().map(|(dest, _)| *dest)));
+                }
+            }
+        }
+        res
+    }
+
+    // The states of the atomic languages of the nonterminal symbol, one for every terminal it has an atomic language for.
+    fn atomic_states_of(&self, symbol: &Symbol) -> Vec<State> {
+        self.atomic_to_state.iter()
+            .filter(|((atomic_symbol, _), _)| atomic_symbol == symbol)
+            .map(|(_, (atomic_state, _))| *atomic_state)
+            .collect()
+    }
+
+    /// Determines whether the automaton accepts the input, keeping track of all configurations the automaton can be
+    /// in after consuming each terminal. Unlike `parse`, this does not collect the derivation rules that were applied.
+    pub fn recognize(&self, input: &[Terminal]) -> bool {
+        self.configurations_after(input).iter().any(|configuration| is_accepting_configuration(self, configuration))
+    }
+
+    /// Parses the input, where every terminal comes with a span given by the caller, e.g. its position in a source file.
+    /// Returns the rules collected along one accepting path like `shortest_derivation`, each together with the spans of
+    /// the terminals it covers, or None if the input is not accepted. The automaton collects the rules of a derivation
+    /// step once the step is complete, so the rules cover the terminals from the one which entered the atomic language
+    /// they are collected in, up to the last terminal consumed when collecting them. Rules collected outside of any
+    /// atomic language cover the input from its start. Of several accepting paths the same one is always returned.
+    pub fn parse_spanned<S: Clone>(&self, input: &[(Terminal, S)]) -> Option<Vec<(Rules, Vec<S>)>> {
+        let with_rules = |trail: &Trail, added: &[(&Rules, usize, usize)]| -> Trail {
+            let mut res: Trail = trail.clone();
+            res.extend(added.iter().filter(|(rules, _, _)| !rules.is_empty()).map(|(rules, start, end)| ((*rules).clone(), *start, *end)));
+            res
+        };
+        // adds the configuration and the ones reachable from it without consuming a terminal, keeping the first trail
+        // found for every configuration
+        let close = |configuration: SpannedConfiguration, trail: Trail, consumed: usize, closure: &mut BTreeMap<SpannedConfiguration, Trail>| {
+            configuration::close(self, configuration, trail, |next, next_trail| {
+                if closure.contains_key(&next) {
+                    return false;
+                }
+                closure.insert(next, next_trail);
+                true
+            }, |trail, (_, entered), rules| with_rules(trail, &[(rules, *entered, consumed)]));
+        };
+
+        let mut configurations: BTreeMap<SpannedConfiguration, Trail> = BTreeMap::new();
+        close(vec![(self.start, 0)], Vec::new(), 0, &mut configurations);
+        for (position, (terminal, _)) in input.iter().enumerate() {
+            let mut next_configurations: BTreeMap<SpannedConfiguration, Trail> = BTreeMap::new();
+            for (configuration, trail) in &configurations {
+                let entered: usize = configuration[configuration.len() - 1].1;
+                configuration::derive(self, configuration, *terminal, |atomic_state| (atomic_state, position), |next, rules, atomic_rules| {
+                    let added: Vec<(&Rules, usize, usize)> = std::iter::once((rules, entered, position + 1))
+                        .chain(atomic_rules.map(|atomic_rules| (atomic_rules, position, position + 1)))
+                        .collect();
+                    close(next, with_rules(trail, &added), position + 1, &mut next_configurations);
+                });
+            }
+            configurations = next_configurations;
+            if configurations.is_empty() {
+                return None;
+            }
+        }
+
+        let (_, trail): (SpannedConfiguration, Trail) = configurations.into_iter()
+            .find(|(configuration, _)| configuration.len() == 1 && self.is_accepting(&configuration[0].0))?;
+        Some(trail.into_iter().map(|(rules, start, end)| (rules, input[start..end].iter().map(|(_, span)| span.clone()).collect())).collect())
+    }
+
+    /// Recognizes the input like `recognize`, and also returns the size of the largest set of configurations the
+    /// automaton is in at once, starting with the closure of the start state. Every configuration is a separate way of
+    /// having consumed the input so far, so the peak shows how much work ambiguity in the grammar causes.
+    pub fn recognize_with_peak(&self, input: &[Terminal]) -> (bool, usize) {
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        let mut peak: usize = configurations.len();
+        for terminal in input {
+            configurations = self.derive_configurations(&configurations, *terminal);
+            peak = peak.max(configurations.len());
+            if configurations.is_empty() {
+                break;
+            }
+        }
+        (configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)), peak)
+    }
+
+    /// Determines whether the input is accepted, where `None` is a wildcard matching any terminal: the configurations
+    /// advance along every terminal of the alphabet of the automaton, so the input is accepted if some choice of
+    /// terminals for the wildcards is.
+    pub fn recognize_with_wildcard(&self, input: &[Option<Terminal>]) -> bool {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        for opt_terminal in input {
+            configurations = match opt_terminal {
+                Some(terminal) => self.derive_configurations(&configurations, *terminal),
+                None => alphabet.iter().flat_map(|terminal| self.derive_configurations(&configurations, *terminal)).collect(),
+            };
+            if configurations.is_empty() {
+                return false;
+            }
+        }
+        configurations.iter().any(|configuration| is_accepting_configuration(self, configuration))
+    }
+
+    /// Determines whether the input is within edit distance k of an accepted string, i.e. whether at most k insertions,
+    /// deletions and substitutions of terminals turn it into an accepted string. Like a Levenshtein automaton, every
+    /// configuration is paired with the least number of edits needed to reach it: consuming an input terminal keeps the
+    /// number, while substituting it by another terminal of the alphabet, skipping it, or consuming a terminal of the
+    /// alphabet which is not in the input costs one edit. Configurations which cannot reach acceptance are dropped.
+    pub fn recognize_within_distance(&self, input: &[Terminal], k: usize) -> bool {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let productive: HashSet<State> = self.productive_states();
+        let derive = |configuration: &Configuration, terminal: Terminal| -> HashSet<Configuration> {
+            let mut res: HashSet<Configuration> = HashSet::new();
+            derive_into(self, configuration, terminal, &mut res);
+            res.retain(|configuration| configuration.iter().all(|state| productive.contains(state)));
+            res
+        };
+        // lowers the distance of the configuration, returning whether it was lowered
+        let improve = |distances: &mut HashMap<Configuration, usize>, configuration: Configuration, distance: usize| -> bool {
+            match distances.get(&configuration) {
+                Some(known) if *known <= distance => false,
+                _ => {
+                    distances.insert(configuration, distance);
+                    true
+                },
+            }
+        };
+        // adds the configurations reached by inserting terminals
+        let insert_terminals = |distances: &mut HashMap<Configuration, usize>| {
+            let mut to_visit: Vec<(Configuration, usize)> = distances.iter().map(|(configuration, distance)| (configuration.clone(), *distance)).collect();
+            while let Some((configuration, distance)) = to_visit.pop() {
+                if distance >= k || distances[&configuration] < distance {
+                    continue;
+                }
+                for terminal in &alphabet {
+                    for next in derive(&configuration, *terminal) {
+                        if improve(distances, next.clone(), distance + 1) {
+                            to_visit.push((next, distance + 1));
+                        }
+                    }
+                }
+            }
+        };
+
+        let mut distances: HashMap<Configuration, usize> = HashMap::new();
+        for configuration in self.start_configurations() {
+            improve(&mut distances, configuration, 0);
+        }
+        insert_terminals(&mut distances);
+        for terminal in input {
+            let mut next_distances: HashMap<Configuration, usize> = HashMap::new();
+            for (configuration, distance) in &distances {
+                for next in derive(configuration, *terminal) {
+                    improve(&mut next_distances, next, *distance);
+                }
+                if *distance < k {
+                    improve(&mut next_distances, configuration.clone(), distance + 1);
+                    for substitute in alphabet.iter().filter(|substitute| *substitute != terminal) {
+                        for next in derive(configuration, *substitute) {
+                            improve(&mut next_distances, next, distance + 1);
+                        }
+                    }
+                }
+            }
+            insert_terminals(&mut next_distances);
+            if next_distances.is_empty() {
+                return false;
+            }
+            distances = next_distances;
+        }
+        distances.keys().any(|configuration| is_accepting_configuration(self, configuration))
+    }
+
+    /// Determines whether some prefix of the input is accepted, the empty prefix and the input itself included.
+    pub fn matches_prefix(&self, input: &[Terminal]) -> bool {
+        self.matches_from(input, false)
+    }
+
+    /// Determines whether some substring of the input is accepted. Rather than recognizing from every offset
+    /// separately, the start configurations are added again after every terminal, so the input is consumed once.
+    pub fn matches_anywhere(&self, input: &[Terminal]) -> bool {
+        self.matches_from(input, true)
+    }
+
+    /// Returns the length of the longest accepted prefix of the input, or None if no prefix is accepted, not even the
+    /// empty one. The input is consumed until no configuration survives.
+    pub fn longest_accepted_prefix(&self, input: &[Terminal]) -> Option<usize> {
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        let mut res: Option<usize> = None;
+        for (pos, terminal) in input.iter().enumerate() {
+            if configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)) {
+                res = Some(pos);
+            }
+            configurations = self.derive_configurations(&configurations, *terminal);
+            if configurations.is_empty() {
+                return res;
+            }
+        }
+        if configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)) {
+            res = Some(input.len());
+        }
+        res
+    }
+
+    /// Splits the input into successive tokens, where every token is the longest accepted prefix of the rest of the
+    /// input. Empty tokens are not produced, so when no non-empty prefix of the rest is accepted, the position at which
+    /// the rest starts is yielded as an error, after which the iterator ends.
+    pub fn tokenize<'a>(&'a self, input: &'a [Terminal]) -> impl Iterator<Item = Result<&'a [Terminal], usize>> + 'a {
+        let mut pos: usize = 0;
+        let mut failed: bool = false;
+        std::iter::from_fn(move || {
+            if failed || pos == input.len() {
+                return None;
+            }
+            match self.longest_accepted_prefix(&input[pos..]) {
+                Some(len) if len > 0 => {
+                    pos += len;
+                    Some(Ok(&input[pos - len..pos]))
+                },
+                _ => {
+                    failed = true;
+                    Some(Err(pos))
+                },
+            }
+        })
+    }
+
+    // Consumes the input until an accepting configuration is found, optionally starting anew at every offset.
+    fn matches_from(&self, input: &[Terminal], every_offset: bool) -> bool {
+        let start_configurations: HashSet<Configuration> = self.start_configurations();
+        let mut configurations: HashSet<Configuration> = start_configurations.clone();
+        for terminal in input {
+            if configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)) {
+                return true;
+            }
+            configurations = self.derive_configurations(&configurations, *terminal);
+            if every_offset {
+                configurations.extend(start_configurations.iter().cloned());
+            } else if configurations.is_empty() {
+                return false;
+            }
+        }
+        configurations.iter().any(|configuration| is_accepting_configuration(self, configuration))
+    }
+
+    /// Returns the current states of all configurations the automaton can be in after consuming the prefix from the
+    /// start, with epsilon transitions already followed. The set is empty when no configuration survives the prefix.
+    pub fn states_after(&self, prefix: &[Terminal]) -> HashSet<State> {
+        self.configurations_after(prefix).iter().map(|configuration| configuration[configuration.len() - 1]).collect()
+    }
+
+    /// Determines whether some accepted string starts with the prefix, i.e. whether a configuration the automaton can be
+    /// in after consuming the prefix can still reach acceptance. This is the case when all of its states are productive,
+    /// so no continuations have to be enumerated. Returns false on a prefix after which no configuration survives.
+    pub fn has_accepted_extension(&self, prefix: &[Terminal]) -> bool {
+        let productive: HashSet<State> = self.productive_states();
+        self.configurations_after(prefix).iter().any(|configuration| configuration.iter().all(|state| productive.contains(state)))
+    }
+
+    /// Produces the automaton accepting exactly the suffixes which complete the prefix to an accepted string, i.e. the
+    /// left quotient of the language by the prefix. Its fresh start state has an epsilon transition for every
+    /// configuration after consuming the prefix. A configuration is a stack of states, so the states above the lowest
+    /// one are entered through copies of what can be reached from them, which continue at the state below instead of
+    /// returning to it. After a prefix on which no configuration survives, the language is empty.
+    pub fn left_quotient(&self, prefix: &[Terminal]) -> FiniteStateAutomaton {
+        let mut res: FiniteStateAutomaton = FiniteStateAutomaton{states: self.states.clone(), accepting_states: self.accepting_states.clone(), start: self.start, transitions: self.transitions.clone(), atomic_to_state: self.atomic_to_state.clone()};
+        let mut highest_state: State = self.states.iter().max().map_or(0, |state| *state);
+        let entries: HashSet<(State, Rules)> = res.enter_configurations(self.configurations_after(prefix), &mut highest_state);
+
+        highest_state += 1;
+        res.start = highest_state;
+        res.states.insert(res.start);
+        if !entries.is_empty() {
+            res.transitions.insert(res.start, HashMap::from([(Symbol::Epsilon, entries)]));
+        }
+        res.remove_unreachable_states();
+        res
+    }
+
+    /// Splits the automaton into the components of its language, such that the languages of the components are disjoint
+    /// and together form the language of the automaton. This recovers the sublanguages of an automaton accepting a union
+    /// of unrelated languages, e.g. the alternatives of `S : 'a' A | 'b' B ;` when A and B share no nonterminals.
+    ///
+    /// The split is a heuristic based on the first terminal of the accepted strings. For every terminal which starts an
+    /// accepted string, the region of the automaton reachable after consuming it is determined, entering atomic
+    /// languages through nonterminal transitions. States without outgoing transitions are left out of the regions, as the
+    /// accepting state which all atomic languages end in would otherwise connect every region. Two first terminals
+    /// belong to the same component when their regions share a state, and this is extended transitively. So strings
+    /// with different first terminals end up in different components unless what follows them uses common states, and
+    /// the components are not split any further than by their first terminal.
+    ///
+    /// Every component has a fresh start state, with a transition on each of its first terminals to the configurations
+    /// after consuming it, like in `left_quotient`. The rules of these first transitions are not kept. When the empty
+    /// string is accepted, the start state of the first component is accepting, and if the empty string is the only
+    /// accepted string it forms a component of its own. The components are ordered by their smallest first terminal,
+    /// and an automaton with an empty language has no components.
+    pub fn decompose(&self) -> Vec<FiniteStateAutomaton> {
+        let productive: HashSet<State> = self.productive_states();
+        let mut firsts: Vec<(Terminal, HashSet<Configuration>, HashSet<State>)> = Vec::new();
+        for terminal in self.alphabet() {
+            let configurations: HashSet<Configuration> = self.configurations_after(&[terminal]).into_iter()
+                .filter(|configuration| configuration.iter().all(|state| productive.contains(state)))
+                .collect();
+            if configurations.is_empty() {
+                continue;
+            }
+            let region: HashSet<State> = self.reachable_from(configurations.iter().flatten().cloned().collect())
+                .into_iter()
+                .filter(|state| self.transitions.get(state).is_some_and(|transitions| !transitions.is_empty()))
+                .collect();
+            firsts.push((terminal, configurations, region));
+        }
+
+        // merges the first terminals with overlapping regions, every group is identified by its first member
+        let mut group_of: Vec<usize> = (0..firsts.len()).collect();
+        for i in 0..firsts.len() {
+            for j in 0..i {
+                if group_of[i] == group_of[j] || firsts[i].2.is_disjoint(&firsts[j].2) {
+                    continue;
+                }
+                let (from, to): (usize, usize) = (group_of[i].max(group_of[j]), group_of[i].min(group_of[j]));
+                for group in group_of.iter_mut() {
+                    if *group == from {
+                        *group = to;
+                    }
+                }
+            }
+        }
+
+        let accepts_empty: bool = self.recognize(&[]);
+        let mut res: Vec<FiniteStateAutomaton> = Vec::new();
+        for group in 0..firsts.len() {
+            if group_of[group] != group {
+                continue;
+            }
+            let mut component: FiniteStateAutomaton = FiniteStateAutomaton{states: self.states.clone(), accepting_states: self.accepting_states.clone(), start: self.start, transitions: self.transitions.clone(), atomic_to_state: self.atomic_to_state.clone()};
+            let mut highest_state: State = self.states.iter().max().map_or(0, |state| *state);
+            let mut start_transitions: HashMap<Symbol, HashSet<(State, Rules)>> = HashMap::new();
+            for (member, (terminal, configurations, _)) in firsts.iter().enumerate() {
+                if group_of[member] == group {
+                    let entries: HashSet<(State, Rules)> = component.enter_configurations(configurations.clone(), &mut highest_state);
+                    start_transitions.insert(Symbol::Terminal(*terminal), entries);
+                }
+            }
+            highest_state += 1;
+            component.start = highest_state;
+            component.states.insert(component.start);
+            component.transitions.insert(component.start, start_transitions);
+            if accepts_empty && res.is_empty() {
+                component.accepting_states.insert(component.start);
+            }
+            component.remove_unreachable_states();
+            res.push(component);
+        }
+
+        if accepts_empty && res.is_empty() {
+            let start: State = self.states.iter().max().map_or(0, |state| *state) + 1;
+            res.push(FiniteStateAutomaton{states: HashSet::from([start]), accepting_states: HashSet::from([start]), start, transitions: HashMap::new(), atomic_to_state: HashMap::new()});
+        }
+        res
+    }
+
+    // Creates a state for every configuration from which the rest of the configuration is accepted, and returns them as
+    // transition destinations without rules. The states above the lowest one are entered through copies of what can be
+    // reached from them, which continue at the state below instead of returning to it.
+    fn enter_configurations(&mut self, configurations: HashSet<Configuration>, highest_state: &mut State) -> HashSet<(State, Rules)> {
+        let mut copies: HashMap<(State, State), State> = HashMap::new();
+        let mut configurations: Vec<Configuration> = configurations.into_iter().collect();
+        configurations.sort();
+        let mut res: HashSet<(State, Rules)> = HashSet::new();
+        for configuration in configurations {
+            let mut entry: State = configuration[0];
+            for state in &configuration[1..] {
+                entry = match copies.get(&(*state, entry)) {
+                    Some(copy) => *copy,
+                    None => {
+                        let copy: State = self.copy_atomic_language(*state, entry, highest_state);
+                        copies.insert((*state, entry), copy);
+                        copy
+                    },
+                };
+            }
+            res.insert((entry, Vec::new()));
+        }
+        res
+    }
+
+    /// Recognizes the input like `recognize`, and also returns the current states of the configurations after every
+    /// position of the input, like `states_after` for every prefix. The first set is the closure of the start state, so
+    /// the history has `input.len() + 1` sets. Once no configuration survives, the remaining sets are empty.
+    pub fn recognize_history(&self, input: &[Terminal]) -> (bool, Vec<HashSet<State>>) {
+        let current_states = |configurations: &HashSet<Configuration>| -> HashSet<State> {
+            configurations.iter().map(|configuration| configuration[configuration.len() - 1]).collect()
+        };
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        let mut history: Vec<HashSet<State>> = Vec::with_capacity(input.len() + 1);
+        history.push(current_states(&configurations));
+        for terminal in input {
+            if !configurations.is_empty() {
+                configurations = self.derive_configurations(&configurations, *terminal);
+            }
+            history.push(current_states(&configurations));
+        }
+        (configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)), history)
+    }
+
+    /// Strips the derivation rules from the automaton, producing a smaller structure which can only recognize.
+    pub fn to_recognizer(&self) -> Recognizer {
+        let transitions: HashMap<State, HashMap<Symbol, HashSet<State>>> = self.transitions.iter()
+            .map(|(state, trans_list)| (*state, trans_list.iter()
+                .map(|(symbol, destinations)| (*symbol, destinations.iter().map(|(dest, _)| *dest).collect()))
+                .collect()))
+            .collect();
+        let atomic_to_state: HashMap<(Symbol, Terminal), State> = self.atomic_to_state.iter()
+            .map(|(key, (state, _))| (*key, *state))
+            .collect();
+        Recognizer{states: self.states.clone(), accepting_states: self.accepting_states.clone(), start: self.start, transitions, atomic_to_state}
+    }
+
+    /// Turns the automaton into its immutable compiled form, see CompiledFsa. Cloning the returned `Arc` is cheap, so
+    /// one compiled automaton can be shared by any number of threads.
+    pub fn freeze(self) -> Arc<CompiledFsa> {
+        let mut states: Vec<State> = self.states.iter().cloned().collect();
+        states.sort();
+        let index_of: HashMap<State, usize> = states.iter().enumerate().map(|(index, state)| (*state, index)).collect();
+
+        let mut all_rules: BTreeSet<&Rules> = BTreeSet::new();
+        let empty: Rules = Rules::new();
+        all_rules.insert(&empty);
+        all_rules.extend(self.transitions.values().flat_map(|trans_list| trans_list.values().flatten().map(|(_, rules)| rules)));
+        all_rules.extend(self.atomic_to_state.values().flat_map(|(_, rules_set)| rules_set));
+        let rules: Vec<Rules> = all_rules.into_iter().cloned().collect();
+        let rules_index: HashMap<&Rules, usize> = rules.iter().enumerate().map(|(index, rules)| (rules, index)).collect();
+
+        let mut edge_offsets: Vec<usize> = vec![0];
+        let mut edges: Vec<(Symbol, usize, usize)> = Vec::new();
+        for state in &states {
+            let mut state_edges: Vec<(Symbol, usize, usize)> = self.transitions.get(state).into_iter().flatten()
+                .flat_map(|(symbol, destinations)| destinations.iter().map(|(dest, rules)| (*symbol, index_of[dest], rules_index[rules])))
+                .collect();
+            state_edges.sort();
+            edges.extend(state_edges);
+            edge_offsets.push(edges.len());
+        }
+
+        let atomic: HashMap<(Nonterminal, Terminal), (usize, Vec<usize>)> = self.atomic_to_state.iter()
+            .filter_map(|((symbol, terminal), (state, rules_set))| symbol.as_nonterminal().map(|nt| {
+                let mut indices: Vec<usize> = rules_set.iter().map(|rules| rules_index[rules]).collect();
+                indices.sort();
+                ((nt, *terminal), (index_of[state], indices))
+            }))
+            .collect();
+        let accepting: Vec<bool> = states.iter().map(|state| self.accepting_states.contains(state)).collect();
+
+        Arc::new(CompiledFsa{start: index_of[&self.start], accepting, edge_offsets, edges, atomic, rules})
+    }
+
+    // The closed configurations the automaton can be in after consuming the prefix from the start.
+    fn configurations_after(&self, prefix: &[Terminal]) -> HashSet<Configuration> {
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        for terminal in prefix {
+            configurations = self.derive_configurations(&configurations, *terminal);
+            if configurations.is_empty() {
+                break;
+            }
+        }
+        configurations
+    }
+
+    /// Recognizes every input, see `recognize`. The configuration sets are allocated once and reused for all inputs,
+    /// which avoids allocating them anew for every input when recognizing many inputs against one automaton.
+    pub fn recognize_batch(&self, inputs: &[Vec<Terminal>]) -> Vec<bool> {
+        let start_configurations: HashSet<Configuration> = self.start_configurations();
+        let mut configurations: HashSet<Configuration> = HashSet::new();
+        let mut next_configurations: HashSet<Configuration> = HashSet::new();
+        let mut res: Vec<bool> = Vec::with_capacity(inputs.len());
+
+        for input in inputs {
+            configurations.clear();
+            configurations.extend(start_configurations.iter().cloned());
+            for terminal in input {
+                next_configurations.clear();
+                for configuration in &configurations {
+                    derive_into(self, configuration, *terminal, &mut next_configurations);
+                }
+                std::mem::swap(&mut configurations, &mut next_configurations);
+                if configurations.is_empty() {
+                    break;
+                }
+            }
+            res.push(configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)));
+        }
+        res
+    }
+
+    /// Case-insensitive variant of `recognize`, where an input terminal matches every terminal of the automaton that
+    /// is equal to it after case folding. This only makes sense as long as Terminal is a char-like type.
+    pub fn recognize_ci(&self, input: &[Terminal]) -> bool {
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut configurations: HashSet<Configuration> = self.start_configurations();
+        for terminal in input {
+            let mut next_configurations: HashSet<Configuration> = HashSet::new();
+            for folded_terminal in alphabet.iter().filter(|t| t.to_lowercase().eq(terminal.to_lowercase())) {
+                next_configurations.extend(self.derive_configurations(&configurations, *folded_terminal));
+            }
+            configurations = next_configurations;
+            if configurations.is_empty() {
+                return false;
+            }
+        }
+        configurations.iter().any(|configuration| is_accepting_configuration(self, configuration))
+    }
+
+    /// Recognizes every example and returns the examples for which the result differs from the expected result.
+    pub fn check_examples(&self, accept: &[Vec<Terminal>], reject: &[Vec<Terminal>]) -> Vec<ExampleFailure> {
+        let mut res: Vec<ExampleFailure> = Vec::new();
+        for (examples, expected) in [(accept, true), (reject, false)] {
+            for input in examples {
+                let actual: bool = self.recognize(input);
+                if actual != expected {
+                    res.push(ExampleFailure{input: input.clone(), expected, actual});
+                }
+            }
+        }
+        res
+    }
+
+    /// Determines whether the two states accept the same strings of at most `max_len` terminals when used as start
+    /// state. This is a bounded check: states which are considered equivalent may still differ on longer strings.
+    pub fn states_equivalent(&self, a: &State, b: &State, max_len: usize) -> bool {
+        self.accepted_from(*a, max_len) == self.accepted_from(*b, max_len)
+    }
+
+    /// Determines whether the two states are bisimilar, taking the rules into account: both are accepting or both are
+    /// not, and for every transition of one there is a transition of the other on the same symbol with the same rules to
+    /// a bisimilar state. This is stricter than `states_equivalent`, as states with the same language may still reach
+    /// it through different derivations. Epsilon and nonterminal transitions are matched like terminal transitions. The
+    /// largest bisimulation is found by partition refinement: starting from the split into accepting and non-accepting
+    /// states, blocks are split by the transitions of their states until no block changes.
+    pub fn bisimilar(&self, a: &State, b: &State) -> bool {
+        if !self.states.contains(a) || !self.states.contains(b) {
+            return a == b;
+        }
+        let block_of: HashMap<State, usize> = self.bisimulation_blocks(&self.states);
+        block_of[a] == block_of[b]
+    }
+
+    // Finds the largest bisimulation between the given states by partition refinement, see bisimilar, and returns the
+    // block of every state. The transitions of the states must stay among them. Blocks are numbered in the order of
+    // their signatures, so the numbers only depend on the structure of the automaton, not on its state numbers.
+    fn bisimulation_blocks(&self, states: &HashSet<State>) -> HashMap<State, usize> {
+        let mut block_of: HashMap<State, usize> = states.iter().map(|state| (*state, usize::from(self.is_accepting(state)))).collect();
+        let mut block_count: usize = block_of.values().collect::<HashSet<&usize>>().len();
+        loop {
+            let signature_of: HashMap<State, BlockSignature> = states.iter().map(|state| {
+                let transitions: BTreeSet<(Symbol, &Rules, usize)> = self.transitions.get(state).into_iter().flatten()
+                    .flat_map(|(symbol, destinations)| destinations.iter().map(|(dest, rules)| (*symbol, rules, block_of[dest])))
+                    .collect();
+                (*state, (block_of[state], transitions))
+            }).collect();
+            let signatures: BTreeMap<&BlockSignature, usize> = signature_of.values().collect::<BTreeSet<&BlockSignature>>()
+                .into_iter().enumerate().map(|(block, signature)| (signature, block)).collect();
+            block_of = signature_of.iter().map(|(state, signature)| (*state, signatures[signature])).collect();
+            // blocks are only ever split, so the partition is stable once their number stays the same
+            if signatures.len() == block_count {
+                break;
+            }
+            block_count = signatures.len();
+        }
+        block_of
+    }
+
+    /// Groups the states into classes of states which are equivalent according to `states_equivalent`, i.e. states
+    /// which accept the same strings of at most `max_len` terminals when used as start state. This is a bounded
+    /// approximation of the right congruence: states in one class may still differ on longer strings. Every state is in
+    /// exactly one class, and the classes are sorted by their smallest state.
+    pub fn nerode_classes(&self, max_len: usize) -> Vec<HashSet<State>> {
+        let mut states: Vec<State> = self.states.iter().cloned().collect();
+        states.sort();
+        let mut classes: BTreeMap<BTreeSet<Vec<Terminal>>, Vec<State>> = BTreeMap::new();
+        for state in states {
+            classes.entry(self.accepted_from(state, max_len)).or_default().push(state);
+        }
+        let mut res: Vec<HashSet<State>> = classes.into_values().map(|class| class.into_iter().collect()).collect();
+        res.sort_by_key(|class| class.iter().min().cloned());
+        res
+    }
+
+    // Enumerates all strings of at most max_len terminals accepted when starting from the given state.
+    fn accepted_from(&self, state: State, max_len: usize) -> BTreeSet<Vec<Terminal>> {
+        let mut start_configurations: HashSet<Configuration> = HashSet::new();
+        close_into(self, vec![state], &mut start_configurations);
+        self.accepted_within(start_configurations, max_len)
+    }
+
+    // Enumerates all strings of at most max_len terminals accepted from the given set of configurations.
+    fn accepted_within(&self, configurations: HashSet<Configuration>, max_len: usize) -> BTreeSet<Vec<Terminal>> {
+        let productive: HashSet<State> = self.productive_states();
+        let alphabet: BTreeSet<Terminal> = self.alphabet();
+        let mut res: BTreeSet<Vec<Terminal>> = BTreeSet::new();
+        let mut to_visit: Vec<(HashSet<Configuration>, Vec<Terminal>)> = vec![(configurations, Vec::new())];
+
+        while let Some((curr_configurations, word)) = to_visit.pop() {
+            if curr_configurations.iter().any(|configuration| is_accepting_configuration(self, configuration)) {
+                res.insert(word.clone());
+            }
+            if word.len() >= max_len {
+                continue;
+            }
+            for terminal in &alphabet {
+                let mut next_configurations: HashSet<Configuration> = self.derive_configurations(&curr_configurations, *terminal);
+                next_configurations.retain(|configuration| configuration.iter().all(|state| productive.contains(state)));
+                if !next_configurations.is_empty() {
+                    to_visit.push((next_configurations, [&word[..], &[*terminal]].concat()));
+                }
+            }
+        }
+        res
+    }
+
+    // Calculates the states from which an accepting state can be reached. A nonterminal transition only counts
+    // if at least one of the atomic languages of its nonterminal can reach an accepting state itself.
+    fn productive_states(&self) -> HashSet<State> {
+        let mut res: HashSet<State> = self.accepting_states.clone();
+        let mut changed: bool = true;
+
+        while changed {
+            changed = false;
+            for (state, transition_list) in &self.transitions {
+                if res.contains(state) {
+                    continue;
+                }
+                for (symbol, destinations) in transition_list {
+                    let usable: bool = match symbol {
+                        Symbol::Nonterminal(_) => self.atomic_to_state.iter()
+                            .any(|((atomic_symbol, _), (atomic_state, _))| atomic_symbol == symbol && res.contains(atomic_state)),
+                        _ => true,
+                    };
+                    if usable && destinations.iter().any(|(dest, _)| res.contains(dest)) {
+                        res.insert(*state);
+                        changed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    // The configurations the automaton is in before consuming any terminal.
+    fn start_configurations(&self) -> HashSet<Configuration> {
+        let mut res: HashSet<Configuration> = HashSet::new();
+        close_into(self, vec![self.start], &mut res);
+        res
+    }
+
+    // Derives every configuration in the set by the terminal and collects the results.
+    fn derive_configurations(&self, configurations: &HashSet<Configuration>, terminal: Terminal) -> HashSet<Configuration> {
+        let mut res: HashSet<Configuration> = HashSet::new();
+        for configuration in configurations {
+            derive_into(self, configuration, terminal, &mut res);
+        }
+        res
+    }
+
+
+}
+
+impl TransitionLookup for FiniteStateAutomaton {
+    type Rules = Rules;
+
+    fn start_state(&self) -> State {
+        self.start
+    }
+
+    fn accepting(&self, state: State) -> bool {
+        self.is_accepting(&state)
+    }
+
+    fn carries_rules(&self, rules: &Rules) -> bool {
+        !rules.is_empty()
+    }
+
+    fn epsilon_transitions(&self, state: State, mut f: impl FnMut(State, &Rules)) {
+        let mut destinations: Vec<&(State, Rules)> = self.transitions.get(&state).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)).into_iter().flatten().collect();
+        destinations.sort();
+        for (dest, rules) in destinations {
+            f(*dest, rules);
+        }
+    }
+
+    fn terminal_transitions(&self, state: State, terminal: Terminal, mut f: impl FnMut(State, &Rules, Entered<Rules>)) {
+        let mut transitions: Vec<(&Symbol, &State, &Rules, Entered<Rules>)> = Vec::new();
+        for (symbol, destinations) in self.transitions.get(&state).into_iter().flatten() {
+            let entered: Entered<Rules> = match symbol {
+                Symbol::Terminal(t) if *t == terminal => None,
+                Symbol::Nonterminal(_) => match self.atomic_to_state.get(&(*symbol, terminal)) {
+                    Some((atomic_state, rules_set)) => Some((*atomic_state, rules_set.iter().min())),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            transitions.extend(destinations.iter().map(|(dest, rules)| (symbol, dest, rules, entered)));
+        }
+        transitions.sort();
+        for (_, dest, rules, entered) in transitions {
+            f(*dest, rules, entered);
+        }
+    }
+}
+
+// Writes a symbol in the format of FiniteStateAutomaton::write_interned.
+fn interned_symbol(symbol: &Symbol) -> String {
+    match symbol {
+        Symbol::Terminal(t) => format!("t{}", *t as u32),
+        Symbol::Nonterminal(nt) => format!("n{}", *nt as u32),
+        Symbol::Epsilon => String::from("e"),
+    }
+}
+
+// Reads a symbol written by interned_symbol.
+fn parse_interned_symbol(field: &str) -> Option<Symbol> {
+    if field == "e" {
+        return Some(Symbol::Epsilon);
+    }
+    let c: char = field.get(1..)?.parse().ok().and_then(char::from_u32)?;
+    match field.chars().next() {
+        Some('t') => Some(Symbol::Terminal(c)),
+        Some('n') => Some(Symbol::Nonterminal(c)),
+        _ => None,
+    }
+}
+
+// Quotes a CSV field if it contains a comma, quote or line break, doubling the quotes inside it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -2,10 +2,12 @@
 //! 
 //! The `grammar` module defines the data structure which is used to express a context-free grammar.
 
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashSet, HashMap, BTreeMap, BTreeSet};
+use std::fmt;
 
 use crate::word::*;
 use crate::finite_state_automaton::*;
+use crate::regex::Rule;
 
 pub struct Grammar {
     pub terminals: HashSet<Terminal>,
@@ -20,4 +22,552 @@ impl Grammar {
         let finite_state_automaton = FiniteStateAutomaton::build_fsa(&terminals, start, &rules);
         Grammar{terminals, nonterminals, start, rules, finite_state_automaton}
     }
+
+    /// Adds the derivation rule `nt -> word` and updates the automaton. Only the atomic languages of the nonterminals
+    /// which have `nt` as a left corner are rebuilt, as the atomic languages of the other nonterminals do not depend
+    /// on the rules of `nt`. A new rule can change which nonterminals are nullable, like `S -> A` for a nullable `A`,
+    /// and thus the left corners of every nonterminal and the nulled variants of every rule using them, so in that
+    /// case the whole automaton is rebuilt.
+    pub fn add_production(&mut self, nt: Nonterminal, word: Word) {
+        if self.rules.get(&nt).is_some_and(|words| words.contains(&word)) {
+            return;
+        }
+        for symbol in &word {
+            match symbol {
+                Symbol::Terminal(t) => {
+                    self.terminals.insert(*t);
+                },
+                Symbol::Nonterminal(n) => {
+                    self.nonterminals.insert(*n);
+                },
+                Symbol::Epsilon => {},
+            }
+        }
+        self.nonterminals.insert(nt);
+        let nullable: HashSet<Nonterminal> = nullable_nonterminals(&self.rules);
+        self.rules.entry(nt).or_default().insert(word);
+
+        if nullable_nonterminals(&self.rules) != nullable {
+            self.finite_state_automaton = FiniteStateAutomaton::build_fsa(&self.terminals, self.start, &self.rules);
+        } else {
+            let affected: HashSet<Nonterminal> = left_corner_dependents(&self.rules, nt);
+            self.finite_state_automaton.rebuild_atomic(&self.terminals, self.start, &self.rules, &affected);
+        }
+    }
+}
+
+// Returns the nonterminals which have the given nonterminal as a left corner, including the nonterminal itself. A
+// symbol is a left corner of a nonterminal if it can be the first symbol of a word derived from it.
+fn left_corner_dependents(rules: &HashMap<Nonterminal, HashSet<Word>>, nonterminal: Nonterminal) -> HashSet<Nonterminal> {
+    let nullable: HashSet<Nonterminal> = nullable_nonterminals(rules);
+    let mut res: HashSet<Nonterminal> = HashSet::from([nonterminal]);
+    let mut changed: bool = true;
+
+    while changed {
+        changed = false;
+        for (head, words) in rules {
+            if res.contains(head) {
+                continue;
+            }
+            let has_left_corner: bool = words.iter().any(|word| {
+                for symbol in word {
+                    match symbol {
+                        Symbol::Nonterminal(n) if res.contains(n) => return true,
+                        Symbol::Nonterminal(n) if nullable.contains(n) => {},
+                        Symbol::Epsilon => {},
+                        _ => return false,
+                    }
+                }
+                false
+            });
+            if has_left_corner {
+                res.insert(*head);
+                changed = true;
+            }
+        }
+    }
+    res
+}
+
+// Returns the nonterminals which can derive the empty word.
+pub(crate) fn nullable_nonterminals(rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashSet<Nonterminal> {
+    let mut res: HashSet<Nonterminal> = HashSet::new();
+    let mut changed: bool = true;
+
+    while changed {
+        changed = false;
+        for (head, words) in rules {
+            if !res.contains(head) && words.iter().any(|word| word.iter().all(|symbol| match symbol {
+                Symbol::Nonterminal(n) => res.contains(n),
+                Symbol::Epsilon => true,
+                Symbol::Terminal(_) => false,
+            })) {
+                res.insert(*head);
+                changed = true;
+            }
+        }
+    }
+    res
+}
+
+/// Returns the terminals which are declared, but do not appear in any derivation rule.
+pub fn unused_terminals(terminals: &HashSet<Terminal>, rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashSet<Terminal> {
+    let mut res: HashSet<Terminal> = terminals.clone();
+    for word in rules.values().flatten() {
+        for symbol in word {
+            if let Symbol::Terminal(terminal) = symbol {
+                res.remove(terminal);
+            }
+        }
+    }
+    res
+}
+
+/// Returns the rules which cannot be part of any derivation of a string from the start nonterminal, sorted. A rule is
+/// dead if it contains a nonterminal which derives no string at all, or if its nonterminal cannot be reached from the
+/// start nonterminal through rules which are not dead themselves. Removing the dead rules leaves the language as is.
+pub fn dead_rules(rules: &HashMap<Nonterminal, HashSet<Word>>, start_nt: Nonterminal) -> Vec<Rule> {
+    let mut productive: HashSet<Nonterminal> = HashSet::new();
+    let mut changed: bool = true;
+    while changed {
+        changed = false;
+        for (head, words) in rules {
+            if !productive.contains(head) && words.iter().any(|word| word.iter().all(|symbol| match symbol {
+                Symbol::Nonterminal(n) => productive.contains(n),
+                _ => true,
+            })) {
+                productive.insert(*head);
+                changed = true;
+            }
+        }
+    }
+    let is_productive = |word: &Word| -> bool {
+        word.iter().all(|symbol| !matches!(symbol, Symbol::Nonterminal(n) if !productive.contains(n)))
+    };
+
+    let mut reachable: HashSet<Nonterminal> = HashSet::from([start_nt]);
+    let mut to_visit: Vec<Nonterminal> = vec![start_nt];
+    while let Some(curr) = to_visit.pop() {
+        for word in rules.get(&curr).into_iter().flatten().filter(|word| is_productive(word)) {
+            for symbol in word {
+                if let Symbol::Nonterminal(n) = symbol {
+                    if reachable.insert(*n) {
+                        to_visit.push(*n);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut res: Vec<Rule> = rules.iter()
+        .flat_map(|(head, words)| words.iter().map(move |word| (*head, word.clone())))
+        .filter(|(head, word)| !reachable.contains(head) || !is_productive(word))
+        .collect();
+    res.sort();
+    res
+}
+
+// Returns a nonterminal which is not used in the derivation rules, preferring uppercase letters.
+fn fresh_nonterminal(rules: &HashMap<Nonterminal, HashSet<Word>>) -> Nonterminal {
+    let mut used: HashSet<char> = rules.keys().cloned().collect();
+    for symbol in rules.values().flatten().flatten() {
+        match symbol {
+            Symbol::Terminal(c) | Symbol::Nonterminal(c) => {
+                used.insert(*c);
+            },
+            Symbol::Epsilon => {},
+        }
+    }
+    ('A'..='Z').chain((0x100..).filter_map(char::from_u32))
+        .find(|c| !used.contains(c) && *c != 'e')
+        .unwrap()
+}
+
+/// Returns the self-embedding nonterminals, i.e. the nonterminals `A` from which a sentential form `uAv` can be
+/// derived where both `u` and `v` can derive a non-empty string, like `S` in `S -> aSb`. A grammar without
+/// self-embedding nonterminals describes a regular language.
+pub fn self_embedding_nonterminals(rules: &HashMap<Nonterminal, HashSet<Word>>) -> BTreeSet<Nonterminal> {
+    // the nonterminals which can derive a non-empty string
+    let mut non_empty: HashSet<Nonterminal> = HashSet::new();
+    let mut changed: bool = true;
+    while changed {
+        changed = false;
+        for (head, words) in rules {
+            if !non_empty.contains(head) && words.iter().flatten().any(|symbol| match symbol {
+                Symbol::Terminal(_) => true,
+                Symbol::Nonterminal(n) => non_empty.contains(n),
+                Symbol::Epsilon => false,
+            }) {
+                non_empty.insert(*head);
+                changed = true;
+            }
+        }
+    }
+    let is_non_empty = |symbols: &[Symbol]| -> bool {
+        symbols.iter().any(|symbol| match symbol {
+            Symbol::Terminal(_) => true,
+            Symbol::Nonterminal(n) => non_empty.contains(n),
+            Symbol::Epsilon => false,
+        })
+    };
+
+    let mut res: BTreeSet<Nonterminal> = BTreeSet::new();
+    for nonterminal in rules.keys() {
+        // a nonterminal reached from the nonterminal, and whether there is a non-empty context to its left and right
+        let mut visited: HashSet<(Nonterminal, bool, bool)> = HashSet::new();
+        let mut to_visit: Vec<(Nonterminal, bool, bool)> = vec![(*nonterminal, false, false)];
+        while let Some((curr, left, right)) = to_visit.pop() {
+            for word in rules.get(&curr).into_iter().flatten() {
+                for (index, symbol) in word.iter().enumerate() {
+                    if let Symbol::Nonterminal(n) = symbol {
+                        let next: (Nonterminal, bool, bool) = (*n, left || is_non_empty(&word[..index]), right || is_non_empty(&word[index + 1..]));
+                        if visited.insert(next) {
+                            to_visit.push(next);
+                        }
+                    }
+                }
+            }
+        }
+        if visited.contains(&(*nonterminal, true, true)) {
+            res.insert(*nonterminal);
+        }
+    }
+    res
+}
+
+/// Left-factors the derivation rules: alternatives of a nonterminal sharing a common prefix, e.g. `A -> aB | aC`, are
+/// replaced by `A -> aA'` and `A' -> B | C`, where `A'` is a fresh nonterminal. This is repeated until no two
+/// alternatives of any nonterminal start with the same symbol. The described language stays the same.
+pub fn left_factor(rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashMap<Nonterminal, HashSet<Word>> {
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = rules.clone();
+    let mut to_factor: BTreeSet<Nonterminal> = res.keys().cloned().collect();
+
+    while let Some(nonterminal) = to_factor.pop_first() {
+        let mut by_first_symbol: BTreeMap<Symbol, BTreeSet<Word>> = BTreeMap::new();
+        for word in &res[&nonterminal] {
+            if !word.is_empty() && word[0] != Symbol::Epsilon {
+                by_first_symbol.entry(word[0]).or_default().insert(word.clone());
+            }
+        }
+
+        for (_, words) in by_first_symbol {
+            if words.len() < 2 {
+                continue;
+            }
+            let first: &Word = words.first().unwrap();
+            let prefix_len: usize = (1..first.len()).take_while(|len| words.iter().all(|word| word.len() > *len && word[*len] == first[*len])).count() + 1;
+
+            let new_nonterminal: Nonterminal = fresh_nonterminal(&res);
+            let mut new_words: HashSet<Word> = HashSet::new();
+            for word in &words {
+                if word.len() == prefix_len {
+                    new_words.insert(vec![Symbol::Epsilon]);
+                } else {
+                    new_words.insert(word[prefix_len..].to_vec());
+                }
+            }
+            let nonterminal_words: &mut HashSet<Word> = res.get_mut(&nonterminal).unwrap();
+            nonterminal_words.retain(|word| !words.contains(word));
+            nonterminal_words.insert([&first[..prefix_len], &[Symbol::Nonterminal(new_nonterminal)]].concat());
+            res.insert(new_nonterminal, new_words);
+            to_factor.insert(new_nonterminal);
+        }
+    }
+    res
+}
+
+/// Removes the unit productions, i.e. the productions `A -> B` consisting of a single nonterminal. Every nonterminal
+/// gets the other productions of all nonterminals reachable from it by unit productions instead. The described language
+/// stays the same.
+pub fn eliminate_unit_productions(rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashMap<Nonterminal, HashSet<Word>> {
+    let unit_target = |word: &Word| -> Option<Nonterminal> {
+        match word[..] {
+            [Symbol::Nonterminal(n)] => Some(n),
+            _ => None,
+        }
+    };
+
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    for nonterminal in rules.keys() {
+        let mut reachable: HashSet<Nonterminal> = HashSet::from([*nonterminal]);
+        let mut to_visit: Vec<Nonterminal> = vec![*nonterminal];
+        while let Some(curr) = to_visit.pop() {
+            for word in rules.get(&curr).into_iter().flatten() {
+                if let Some(n) = unit_target(word) {
+                    if reachable.insert(n) {
+                        to_visit.push(n);
+                    }
+                }
+            }
+        }
+
+        let words: &mut HashSet<Word> = res.entry(*nonterminal).or_default();
+        for n in reachable {
+            words.extend(rules.get(&n).into_iter().flatten().filter(|word| unit_target(word).is_none()).cloned());
+        }
+    }
+    res
+}
+
+/// Removes the immediate left recursion: the alternatives of a nonterminal `A -> Aa | b`, where `b` does not start with
+/// `A`, are replaced by `A -> bA'` and `A' -> aA' | e`, where `A'` is a fresh nonterminal. Alternatives `A -> A` are
+/// dropped, as they derive nothing new. A nonterminal without an alternative which does not start with itself derives
+/// no string at all and is kept as it is. Left recursion through other nonterminals is not removed. The described
+/// language stays the same.
+pub fn remove_immediate_left_recursion(rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashMap<Nonterminal, HashSet<Word>> {
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = rules.clone();
+    let mut nonterminals: Vec<Nonterminal> = rules.keys().cloned().collect();
+    nonterminals.sort();
+
+    for nonterminal in nonterminals {
+        let (recursive, other): (Vec<&Word>, Vec<&Word>) = rules[&nonterminal].iter()
+            .partition(|word| word.first() == Some(&Symbol::Nonterminal(nonterminal)));
+        if recursive.is_empty() || other.is_empty() {
+            continue;
+        }
+
+        let new_nonterminal: Nonterminal = fresh_nonterminal(&res);
+        let without_epsilon = |word: &[Symbol]| -> Word {
+            word.iter().filter(|symbol| **symbol != Symbol::Epsilon).cloned().collect()
+        };
+        let mut new_words: HashSet<Word> = HashSet::from([vec![Symbol::Epsilon]]);
+        for word in recursive {
+            let rest: Word = without_epsilon(&word[1..]);
+            if !rest.is_empty() {
+                new_words.insert([&rest[..], &[Symbol::Nonterminal(new_nonterminal)]].concat());
+            }
+        }
+        let words: HashSet<Word> = other.iter()
+            .map(|word| [&without_epsilon(word)[..], &[Symbol::Nonterminal(new_nonterminal)]].concat())
+            .collect();
+        res.insert(nonterminal, words);
+        res.insert(new_nonterminal, new_words);
+    }
+    res
+}
+
+/// An error while renaming a symbol in the derivation rules, see `rename_nonterminal` and `rename_terminal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameError {
+    /// The new name is already used by another symbol of the same kind, renaming would merge the two.
+    AlreadyUsed(Symbol),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::AlreadyUsed(symbol) => write!(f, "symbol {} is already used in the rules", symbol),
+        }
+    }
+}
+
+/// Renames the nonterminal `from` to `to`, both as the head of its rules and wherever it appears in a rule. Fails if
+/// `to` is already used as a nonterminal, unless `from` and `to` are the same.
+pub fn rename_nonterminal(rules: &HashMap<Nonterminal, HashSet<Word>>, from: Nonterminal, to: Nonterminal) -> Result<HashMap<Nonterminal, HashSet<Word>>, RenameError> {
+    rename_symbol(rules, Symbol::Nonterminal(from), Symbol::Nonterminal(to))
+}
+
+/// Renames the terminal `from` to `to` wherever it appears in a rule. Fails if `to` is already used as a terminal,
+/// unless `from` and `to` are the same.
+pub fn rename_terminal(rules: &HashMap<Nonterminal, HashSet<Word>>, from: Terminal, to: Terminal) -> Result<HashMap<Nonterminal, HashSet<Word>>, RenameError> {
+    rename_symbol(rules, Symbol::Terminal(from), Symbol::Terminal(to))
+}
+
+// Replaces every occurrence of the symbol `from` by `to`, including the heads of the rules for nonterminals.
+fn rename_symbol(rules: &HashMap<Nonterminal, HashSet<Word>>, from: Symbol, to: Symbol) -> Result<HashMap<Nonterminal, HashSet<Word>>, RenameError> {
+    if from == to {
+        return Ok(rules.clone());
+    }
+    let used: bool = rules.values().flatten().flatten().any(|symbol| *symbol == to)
+        || matches!(to, Symbol::Nonterminal(nt) if rules.contains_key(&nt));
+    if used {
+        return Err(RenameError::AlreadyUsed(to));
+    }
+
+    let rename = |symbol: &Symbol| -> Symbol { if *symbol == from { to } else { *symbol } };
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    for (head, words) in rules {
+        let new_head: Nonterminal = match rename(&Symbol::Nonterminal(*head)) {
+            Symbol::Nonterminal(nt) => nt,
+            _ => *head,
+        };
+        res.insert(new_head, words.iter().map(|word| word.iter().map(rename).collect()).collect());
+    }
+    Ok(res)
+}
+
+/// Builds a grammar for the concatenation of the languages of two grammars, returning its start nonterminal and its
+/// rules. The nonterminals of `b` which also occur in `a` are renamed to fresh nonterminals with `rename_nonterminal`,
+/// after which the rules of both are combined and a fresh start nonterminal derives `start_a` followed by the possibly
+/// renamed `start_b`.
+pub fn concat_grammars(a: &HashMap<Nonterminal, HashSet<Word>>, start_a: Nonterminal, b: &HashMap<Nonterminal, HashSet<Word>>, start_b: Nonterminal) -> (Nonterminal, HashMap<Nonterminal, HashSet<Word>>) {
+    let nonterminals_of = |rules: &HashMap<Nonterminal, HashSet<Word>>| -> BTreeSet<Nonterminal> {
+        rules.keys().cloned().chain(rules.values().flatten().flatten().filter_map(|symbol| symbol.as_nonterminal())).collect()
+    };
+    let used_in_a: BTreeSet<Nonterminal> = nonterminals_of(a);
+
+    let mut renamed_b: HashMap<Nonterminal, HashSet<Word>> = b.clone();
+    let mut renamed_start_b: Nonterminal = start_b;
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = a.clone();
+    for nonterminal in nonterminals_of(b).intersection(&used_in_a) {
+        let combined: HashMap<Nonterminal, HashSet<Word>> = res.iter().chain(renamed_b.iter()).map(|(head, words)| (*head, words.clone())).collect();
+        let fresh: Nonterminal = fresh_nonterminal(&combined);
+        renamed_b = rename_nonterminal(&renamed_b, *nonterminal, fresh).unwrap();
+        if renamed_start_b == *nonterminal {
+            renamed_start_b = fresh;
+        }
+    }
+
+    res.extend(renamed_b);
+    let start: Nonterminal = fresh_nonterminal(&res);
+    res.insert(start, HashSet::from([vec![Symbol::Nonterminal(start_a), Symbol::Nonterminal(renamed_start_b)]]));
+    (start, res)
+}
+
+/// An error while inlining a nonterminal, see `inline_nonterminal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineError {
+    /// The nonterminal can derive a word containing itself, so substituting its rules would never end.
+    Recursive(Nonterminal),
+}
+
+impl fmt::Display for InlineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InlineError::Recursive(nt) => write!(f, "nonterminal {} is recursive and cannot be inlined", nt),
+        }
+    }
+}
+
+/// Substitutes the rules of the nonterminal for every occurrence of it in the other rules, after which its own rules
+/// are removed. A rule with several occurrences is replaced by every combination of the rules of the nonterminal. The
+/// language stays the same as long as the nonterminal is not the start nonterminal. Fails if the nonterminal is
+/// recursive, directly or through other nonterminals.
+pub fn inline_nonterminal(rules: &HashMap<Nonterminal, HashSet<Word>>, nt: Nonterminal) -> Result<HashMap<Nonterminal, HashSet<Word>>, InlineError> {
+    let mut reachable: HashSet<Nonterminal> = HashSet::new();
+    let mut to_visit: Vec<Nonterminal> = vec![nt];
+    while let Some(curr) = to_visit.pop() {
+        for symbol in rules.get(&curr).into_iter().flatten().flatten() {
+            if let Symbol::Nonterminal(n) = symbol {
+                if *n == nt {
+                    return Err(InlineError::Recursive(nt));
+                }
+                if reachable.insert(*n) {
+                    to_visit.push(*n);
+                }
+            }
+        }
+    }
+
+    let replacements: Vec<&Word> = rules.get(&nt).into_iter().flatten().collect();
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    for (head, words) in rules.iter().filter(|(head, _)| **head != nt) {
+        let inlined: &mut HashSet<Word> = res.entry(*head).or_default();
+        for word in words {
+            let mut alternatives: Vec<Word> = vec![Vec::new()];
+            for symbol in word {
+                alternatives = match symbol {
+                    Symbol::Nonterminal(n) if *n == nt => alternatives.iter()
+                        .flat_map(|alternative| replacements.iter().map(|replacement| [&alternative[..], &replacement[..]].concat()))
+                        .collect(),
+                    _ => alternatives.into_iter().map(|mut alternative| { alternative.push(*symbol); alternative }).collect(),
+                };
+            }
+            for alternative in alternatives {
+                let mut alternative: Word = alternative.into_iter().filter(|symbol| *symbol != Symbol::Epsilon).collect();
+                if alternative.is_empty() {
+                    alternative.push(Symbol::Epsilon);
+                }
+                inlined.insert(alternative);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the FIRST set of every nonterminal: the terminals which can start a string derived from it.
+pub fn first_sets(rules: &HashMap<Nonterminal, HashSet<Word>>) -> HashMap<Nonterminal, BTreeSet<Terminal>> {
+    let nullable: HashSet<Nonterminal> = nullable_nonterminals(rules);
+    let mut res: HashMap<Nonterminal, BTreeSet<Terminal>> = rules.keys().map(|nonterminal| (*nonterminal, BTreeSet::new())).collect();
+    let mut changed: bool = true;
+
+    while changed {
+        changed = false;
+        for (head, words) in rules {
+            for word in words {
+                let first: BTreeSet<Terminal> = first_of_word(word, &res, &nullable);
+                let head_first: &mut BTreeSet<Terminal> = res.get_mut(head).unwrap();
+                if !first.is_subset(head_first) {
+                    head_first.extend(first);
+                    changed = true;
+                }
+            }
+        }
+    }
+    res
+}
+
+// Returns the terminals which can start a string derived from the word, given the FIRST sets and nullable nonterminals.
+fn first_of_word(word: &[Symbol], first_sets: &HashMap<Nonterminal, BTreeSet<Terminal>>, nullable: &HashSet<Nonterminal>) -> BTreeSet<Terminal> {
+    let mut res: BTreeSet<Terminal> = BTreeSet::new();
+    for symbol in word {
+        match symbol {
+            Symbol::Terminal(terminal) => {
+                res.insert(*terminal);
+                return res;
+            },
+            Symbol::Nonterminal(nonterminal) => {
+                res.extend(first_sets.get(nonterminal).into_iter().flatten());
+                if !nullable.contains(nonterminal) {
+                    return res;
+                }
+            },
+            Symbol::Epsilon => {},
+        }
+    }
+    res
+}
+
+/// Returns the pairs `(A, t)` for which two different alternatives of `A` can both start with the terminal `t`,
+/// sorted by nonterminal and terminal. Such a FIRST/FIRST conflict does not prove the grammar is ambiguous, but it
+/// shows where the automaton branches on a terminal.
+pub fn first_conflicts(rules: &HashMap<Nonterminal, HashSet<Word>>) -> Vec<(Nonterminal, Terminal)> {
+    let nullable: HashSet<Nonterminal> = nullable_nonterminals(rules);
+    let first: HashMap<Nonterminal, BTreeSet<Terminal>> = first_sets(rules);
+    let mut res: BTreeSet<(Nonterminal, Terminal)> = BTreeSet::new();
+
+    for (head, words) in rules {
+        let mut seen: BTreeSet<Terminal> = BTreeSet::new();
+        for word in words {
+            for terminal in first_of_word(word, &first, &nullable) {
+                if !seen.insert(terminal) {
+                    res.insert((*head, terminal));
+                }
+            }
+        }
+    }
+    res.into_iter().collect()
+}
+
+/// Builds a grammar which accepts exactly the examples, read off the prefix tree of the examples: every prefix of an
+/// example gets a nonterminal `N` with a rule `N -> tM` for every terminal `t` extending it to the prefix of `M`, and a
+/// rule `N -> e` if the prefix is an example itself. The start nonterminal is `S`, belonging to the empty prefix. The
+/// grammar does not generalize beyond the examples, but it can serve as a starting point to refine. Examples with
+/// terminals which are not in `terminals` are skipped.
+pub fn generalize_from_examples(accept: &[Vec<Terminal>], terminals: &HashSet<Terminal>) -> HashMap<Nonterminal, HashSet<Word>> {
+    let examples: BTreeSet<&Vec<Terminal>> = accept.iter().filter(|example| example.iter().all(|terminal| terminals.contains(terminal))).collect();
+    let prefixes: BTreeSet<&[Terminal]> = examples.iter().flat_map(|example| (0..=example.len()).map(|end| &example[..end])).collect();
+    let mut names = std::iter::once('S').chain(('A'..='Z').filter(|c| *c != 'S')).chain((0x100..).filter_map(char::from_u32));
+    let nonterminal_of: BTreeMap<&[Terminal], Nonterminal> = prefixes.iter().map(|prefix| (*prefix, names.next().unwrap())).collect();
+
+    let mut res: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    for (prefix, nonterminal) in &nonterminal_of {
+        if let Some((last, parent)) = prefix.split_last() {
+            res.entry(nonterminal_of[parent]).or_default().insert(vec![Symbol::Terminal(*last), Symbol::Nonterminal(*nonterminal)]);
+        }
+        if examples.contains(&prefix.to_vec()) {
+            res.entry(*nonterminal).or_default().insert(vec![Symbol::Epsilon]);
+        }
+    }
+    res
 }
@@ -0,0 +1,270 @@
+//! # Grammar import
+//!
+//! The `grammar_import` module reads grammars written in the core production syntax shared by Yacc and ANTLR, e.g.
+//! `expr : expr '+' term | term ;`. Quoted literals are terminals, with every character of a literal being a
+//! separate terminal, and names are nonterminals. An empty alternative derives the empty word. Actions in braces,
+//! comments and lines starting with `%` are skipped. The first rule defines the start symbol. Grammars can also be
+//! written in this syntax, see `grammar_to_string`.
+
+use std::collections::{HashSet, HashMap, BTreeSet};
+use std::fmt;
+
+use crate::word::*;
+use crate::grammar::*;
+
+/// An error in the grammar input, together with the line (starting at 1) at which it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for GrammarParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Literal(String),
+    Colon,
+    Bar,
+    Semicolon,
+}
+
+// The symbols of an alternative as they appear in the input, each with its line.
+type Alternative = Vec<(Token, usize)>;
+
+/// Parses a grammar in Yacc-like syntax. Since nonterminals are single characters, a rule name consisting of a single
+/// character is used as is, other names are assigned an unused uppercase letter in order of their definition.
+pub fn parse_grammar_yacc_like(input: &str) -> Result<Grammar, GrammarParseError> {
+    let tokens: Vec<(Token, usize)> = tokenize(input)?;
+
+    // The alternatives of every rule, still referring to names, in order of definition.
+    let mut definitions: Vec<(String, Vec<Alternative>)> = Vec::new();
+    let mut pos: usize = 0;
+    while pos < tokens.len() {
+        let (name, line) = match &tokens[pos] {
+            (Token::Name(name), line) => (name.clone(), *line),
+            (_, line) => return Err(GrammarParseError{line: *line, message: String::from("expected a rule name")}),
+        };
+        match tokens.get(pos + 1) {
+            Some((Token::Colon, _)) => {},
+            _ => return Err(GrammarParseError{line, message: format!("expected ':' after rule name '{}'", name)}),
+        }
+        pos += 2;
+
+        let mut alternatives: Vec<Alternative> = vec![Vec::new()];
+        loop {
+            match tokens.get(pos) {
+                Some((Token::Semicolon, _)) => break,
+                Some((Token::Bar, _)) => alternatives.push(Vec::new()),
+                Some((Token::Colon, colon_line)) => return Err(GrammarParseError{line: *colon_line, message: format!("missing ';' after rule '{}'", name)}),
+                Some(token) => alternatives.last_mut().unwrap().push(token.clone()),
+                None => return Err(GrammarParseError{line, message: format!("missing ';' after rule '{}'", name)}),
+            }
+            pos += 1;
+        }
+        pos += 1;
+        definitions.push((name, alternatives));
+    }
+
+    if definitions.is_empty() {
+        return Err(GrammarParseError{line: 1, message: String::from("the grammar contains no rules")});
+    }
+
+    let names: Vec<String> = definitions.iter().map(|(name, _)| name.clone()).collect();
+    let nonterminal_of: HashMap<String, Nonterminal> = assign_nonterminals(&names);
+
+    let mut terminals: HashSet<Terminal> = HashSet::new();
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    for (name, alternatives) in &definitions {
+        for alternative in alternatives {
+            let mut word: Word = Vec::new();
+            for (token, line) in alternative {
+                match token {
+                    Token::Name(used_name) => match nonterminal_of.get(used_name) {
+                        Some(nonterminal) => word.push(Symbol::Nonterminal(*nonterminal)),
+                        None => return Err(GrammarParseError{line: *line, message: format!("undefined nonterminal '{}'", used_name)}),
+                    },
+                    Token::Literal(literal) => {
+                        for terminal in literal.chars() {
+                            terminals.insert(terminal);
+                            word.push(Symbol::Terminal(terminal));
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+            }
+            if word.is_empty() {
+                word.push(Symbol::Epsilon);
+            }
+            rules.entry(nonterminal_of[name]).or_default().insert(word);
+        }
+    }
+
+    let nonterminals: HashSet<Nonterminal> = rules.keys().cloned().collect();
+    let start: Nonterminal = nonterminal_of[&names[0]];
+    Ok(Grammar::new(terminals, nonterminals, start, rules))
+}
+
+/// Renders the rules in the syntax read by `parse_grammar_yacc_like`, one rule per line. The rule of the start
+/// nonterminal comes first, as it defines the start symbol, followed by the other rules sorted by nonterminal. The
+/// alternatives of a rule are sorted, every terminal is a separate literal and epsilon is an empty alternative.
+/// Reading the result back gives the same rules and start nonterminal, as long as the nonterminals are letters and no
+/// terminal is a line break. Terminals which do not appear in any rule are not part of the result.
+pub fn grammar_to_string(rules: &HashMap<Nonterminal, HashSet<Word>>, start_nt: Nonterminal) -> String {
+    let mut nonterminals: Vec<&Nonterminal> = rules.keys().collect();
+    nonterminals.sort_by_key(|nonterminal| (**nonterminal != start_nt, **nonterminal));
+
+    let mut res: String = String::new();
+    for nonterminal in nonterminals {
+        let alternatives: BTreeSet<Word> = rules[nonterminal].iter()
+            .map(|word| word.iter().filter(|symbol| **symbol != Symbol::Epsilon).cloned().collect())
+            .collect();
+        let rendered: Vec<String> = alternatives.iter()
+            .map(|word| word.iter().map(|symbol| match symbol {
+                Symbol::Terminal('\\') => String::from(" '\\\\'"),
+                Symbol::Terminal('\'') => String::from(" '\\''"),
+                Symbol::Terminal(terminal) => format!(" '{}'", terminal),
+                _ => format!(" {}", symbol),
+            }).collect())
+            .collect();
+        res.push_str(&format!("{} :{} ;\n", nonterminal, rendered.join(" |")));
+    }
+    res
+}
+
+// Maps every rule name to a nonterminal. Single character names keep their character, other names are assigned the
+// first unused uppercase letter, or an unused character beyond the ASCII range when those run out.
+fn assign_nonterminals(names: &[String]) -> HashMap<String, Nonterminal> {
+    let mut res: HashMap<String, Nonterminal> = HashMap::new();
+    let mut used: HashSet<Nonterminal> = HashSet::new();
+    for name in names {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            res.insert(name.clone(), c);
+            used.insert(c);
+        }
+    }
+    for name in names {
+        if !res.contains_key(name) {
+            let nonterminal: Nonterminal = ('A'..='Z').chain((0x100..).filter_map(char::from_u32))
+                .find(|c| !used.contains(c) && *c != 'e')
+                .unwrap();
+            res.insert(name.clone(), nonterminal);
+            used.insert(nonterminal);
+        }
+    }
+    res
+}
+
+// Splits the input into tokens, each with the line it starts on. Actions, comments and `%` lines are dropped here.
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, GrammarParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut res: Vec<(Token, usize)> = Vec::new();
+    let mut line: usize = 1;
+    let mut at_line_start: bool = true;
+    let mut pos: usize = 0;
+
+    while pos < chars.len() {
+        let c: char = chars[pos];
+        if c == '\n' {
+            line += 1;
+            at_line_start = true;
+            pos += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if c == '%' && at_line_start {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        at_line_start = false;
+        let start_line: usize = line;
+
+        match c {
+            ':' | '|' | ';' => {
+                res.push((match c { ':' => Token::Colon, '|' => Token::Bar, _ => Token::Semicolon }, line));
+                pos += 1;
+            },
+            '\'' | '"' => {
+                let mut literal: String = String::new();
+                pos += 1;
+                while pos < chars.len() && chars[pos] != c {
+                    if chars[pos] == '\n' {
+                        return Err(GrammarParseError{line: start_line, message: String::from("unterminated literal")});
+                    }
+                    if chars[pos] == '\\' && pos + 1 < chars.len() {
+                        pos += 1;
+                    }
+                    literal.push(chars[pos]);
+                    pos += 1;
+                }
+                if pos == chars.len() {
+                    return Err(GrammarParseError{line: start_line, message: String::from("unterminated literal")});
+                }
+                if literal.is_empty() {
+                    return Err(GrammarParseError{line: start_line, message: String::from("empty literal")});
+                }
+                res.push((Token::Literal(literal), start_line));
+                pos += 1;
+            },
+            '{' => {
+                let mut depth: usize = 0;
+                while pos < chars.len() {
+                    match chars[pos] {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        '\n' => line += 1,
+                        _ => {},
+                    }
+                    pos += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                if depth > 0 {
+                    return Err(GrammarParseError{line: start_line, message: String::from("unterminated action")});
+                }
+            },
+            '/' if chars.get(pos + 1) == Some(&'/') => {
+                while pos < chars.len() && chars[pos] != '\n' {
+                    pos += 1;
+                }
+            },
+            '/' if chars.get(pos + 1) == Some(&'*') => {
+                pos += 2;
+                while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/')) {
+                    if chars[pos] == '\n' {
+                        line += 1;
+                    }
+                    pos += 1;
+                }
+                if pos == chars.len() {
+                    return Err(GrammarParseError{line: start_line, message: String::from("unterminated comment")});
+                }
+                pos += 2;
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name: String = String::new();
+                while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                    name.push(chars[pos]);
+                    pos += 1;
+                }
+                res.push((Token::Name(name), start_line));
+            },
+            c => {
+                return Err(GrammarParseError{line, message: format!("unexpected character '{}'", c)});
+            },
+        }
+    }
+    Ok(res)
+}
@@ -181,4 +181,13 @@ impl LanguageList {
         self.languages.pop()
     }
 
+    /// Returns the number of languages in the list, i.e. how deep the languages are currently nested.
+    pub fn len(&self) -> usize {
+        self.languages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+
 }
\ No newline at end of file
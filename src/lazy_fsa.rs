@@ -0,0 +1,160 @@
+//! # Lazy finite state automaton
+//!
+//! The `lazy_fsa` module defines an automaton which turns the atomic languages of a nonterminal into states only once
+//! they are first needed, so inputs which only touch part of a large grammar never build the rest of its automaton.
+
+use std::cell::RefCell;
+use std::collections::{HashSet, HashMap, BTreeSet};
+
+use crate::word::*;
+use crate::regex::*;
+use crate::finite_state_automaton::*;
+use crate::configuration::{self, TransitionLookup, Entered};
+
+/// A finite state automaton whose atomic languages are built on demand, offering the interface used while parsing.
+/// Initially it only contains the start state, the shared epsilon state and the atomic languages of the terminals.
+/// The first call of `get_atomic` for a nonterminal builds the states and transitions of all atomic languages of that
+/// nonterminal and caches them, later calls only look them up. Since the states of an atomic language can only be
+/// reached by entering it through `get_atomic`, `simulate` never needs to build anything.
+///
+/// The regular expressions of the atomic languages depend on each other, so they are calculated for the whole grammar
+/// when the automaton is created. Only turning them into states is deferred, which is where the memory of a fully
+/// built automaton goes. The automaton accepts the same language as the one of `build_fsa`, but its states differ:
+/// `build_fsa` shares states between the atomic languages of different nonterminals where their expressions end the
+/// same, which a nonterminal built on its own cannot do, and the numbers of the states depend on the order in which
+/// the nonterminals are first used.
+///
+/// The cache is kept in a `RefCell`, so building happens behind a shared reference. This makes a LazyFsa `Send` but
+/// not `Sync`: it can be moved to another thread, but not shared between threads. Threads parsing concurrently either
+/// use a LazyFsa each, or share a FiniteStateAutomaton which is fully built, see `into_fsa`.
+#[derive(Debug)]
+pub struct LazyFsa {
+    terminals: HashSet<Terminal>,
+    start_nt: Nonterminal,
+    rules: HashMap<Nonterminal, HashSet<Word>>,
+    atomic_regex: Regex,
+    fsa: RefCell<FiniteStateAutomaton>,
+    built: RefCell<HashSet<Nonterminal>>,
+}
+
+impl LazyFsa {
+    /// Calculates the atomic languages of the grammar, without building the states of any nonterminal yet.
+    pub fn new(terminals: &HashSet<Terminal>, start_nt: Nonterminal, rules: &HashMap<Nonterminal, HashSet<Word>>) -> LazyFsa {
+        let atomic_regex: Regex = Regex::new(terminals, rules);
+        let fsa: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa_from_regex(&atomic_regex, terminals, start_nt, rules, Some(&HashSet::new()), true, None);
+        LazyFsa {
+            terminals: terminals.clone(),
+            start_nt,
+            rules: rules.clone(),
+            atomic_regex,
+            fsa: RefCell::new(fsa),
+            built: RefCell::new(HashSet::new()),
+        }
+    }
+
+    pub fn get_start(&self) -> (State, bool) {
+        self.fsa.borrow().get_start()
+    }
+
+    pub fn is_accepting(&self, curr_state: &State) -> bool {
+        self.fsa.borrow().is_accepting(curr_state)
+    }
+
+    /// Returns the destinations of the transitions on the symbol from the state, like FiniteStateAutomaton::simulate.
+    /// The destinations are copied out of the cache, as it may grow while they are in use.
+    pub fn simulate(&self, curr_state: &State, symbol: Symbol) -> Option<HashSet<(State, Rules, bool)>> {
+        self.fsa.borrow().simulate(curr_state, symbol)
+            .map(|destinations| destinations.into_iter().map(|(dest, rules, accepting)| (*dest, rules.clone(), accepting)).collect())
+    }
+
+    /// Returns the atomic language of the symbol and terminal like FiniteStateAutomaton::get_atomic, building the atomic
+    /// languages of the symbol first if it is a nonterminal which has not been used yet.
+    pub fn get_atomic(&self, symbol: Symbol, terminal: Terminal) -> Option<(State, HashSet<Rules>, bool)> {
+        if let Symbol::Nonterminal(nt) = symbol {
+            self.build(nt);
+        }
+        self.fsa.borrow().get_atomic(symbol, terminal)
+            .map(|(state, rules_set, accepting)| (*state, rules_set.clone(), accepting))
+    }
+
+    /// Determines whether the input is accepted, in the same way as FiniteStateAutomaton::recognize, building the atomic
+    /// languages of the nonterminals it enters on the way.
+    pub fn recognize(&self, input: &[Terminal]) -> bool {
+        configuration::recognize(self, input)
+    }
+
+    /// Parses the input like CompiledFsa::parse, returning the rules collected along one accepting path of the
+    /// automaton, or None if the input is not accepted. Only the atomic languages entered on the way are built.
+    pub fn parse(&self, input: &[Terminal]) -> Option<Vec<Rules>> {
+        configuration::parse(self, input)
+    }
+
+    /// Returns the nonterminals whose atomic languages have been built so far.
+    pub fn built_nonterminals(&self) -> HashSet<Nonterminal> {
+        self.built.borrow().clone()
+    }
+
+    /// Builds the atomic languages of the remaining nonterminals and returns the complete automaton.
+    pub fn into_fsa(self) -> FiniteStateAutomaton {
+        let mut nonterminals: Vec<Nonterminal> = self.rules.keys().cloned().collect();
+        nonterminals.sort();
+        for nt in nonterminals {
+            self.build(nt);
+        }
+        self.fsa.into_inner()
+    }
+
+    // Builds the atomic languages of the nonterminal, unless they have been built before.
+    fn build(&self, nt: Nonterminal) {
+        if !self.built.borrow_mut().insert(nt) {
+            return;
+        }
+        let nts: HashSet<Nonterminal> = HashSet::from([nt]);
+        let partial: FiniteStateAutomaton = FiniteStateAutomaton::build_fsa_from_regex(&self.atomic_regex, &self.terminals, self.start_nt, &self.rules, Some(&nts), true, None);
+        self.fsa.borrow_mut().merge_atomic(partial, &nts, OverwritePolicy::Replace);
+    }
+}
+
+impl TransitionLookup for LazyFsa {
+    type Rules = Rules;
+
+    fn start_state(&self) -> State {
+        self.get_start().0
+    }
+
+    fn accepting(&self, state: State) -> bool {
+        self.is_accepting(&state)
+    }
+
+    fn carries_rules(&self, rules: &Rules) -> bool {
+        !rules.is_empty()
+    }
+
+    fn epsilon_transitions(&self, state: State, mut f: impl FnMut(State, &Rules)) {
+        let mut destinations: Vec<(State, Rules, bool)> = self.simulate(&state, Symbol::Epsilon).into_iter().flatten().collect();
+        destinations.sort();
+        for (dest, rules, _) in destinations {
+            f(dest, &rules);
+        }
+    }
+
+    // The atomic languages are looked up, and so built, without holding on to the cache.
+    fn terminal_transitions(&self, state: State, terminal: Terminal, mut f: impl FnMut(State, &Rules, Entered<Rules>)) {
+        let mut terminal_destinations: Vec<(State, Rules, bool)> = self.simulate(&state, Symbol::Terminal(terminal)).into_iter().flatten().collect();
+        terminal_destinations.sort();
+        for (dest, rules, _) in terminal_destinations {
+            f(dest, &rules, None);
+        }
+        let nonterminals: BTreeSet<Nonterminal> = self.fsa.borrow().startable_nonterminals(&state);
+        for nt in nonterminals {
+            let Some((atomic_state, rules_set, _)) = self.get_atomic(Symbol::Nonterminal(nt), terminal) else {
+                continue;
+            };
+            let mut destinations: Vec<(State, Rules, bool)> = self.simulate(&state, Symbol::Nonterminal(nt)).into_iter().flatten().collect();
+            destinations.sort();
+            for (dest, rules, _) in destinations {
+                f(dest, &rules, Some((atomic_state, rules_set.iter().min())));
+            }
+        }
+    }
+}
@@ -11,4 +11,16 @@ pub use memoize::*;
 mod language_list;
 pub use language_list::*;
 mod parse;
-pub use parse::*;
\ No newline at end of file
+pub use parse::*;
+mod derivation;
+pub use derivation::*;
+mod recognizer;
+pub use recognizer::*;
+mod compiled_fsa;
+pub use compiled_fsa::*;
+mod grammar_import;
+pub use grammar_import::*;
+mod lazy_fsa;
+pub use lazy_fsa::*;
+mod regex_export;
+mod configuration;
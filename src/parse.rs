@@ -1,6 +1,8 @@
-use std::collections::{HashSet, HashMap};
+use std::cmp::Reverse;
+use std::collections::{HashSet, HashMap, BTreeSet};
 
 use crate::*;
+use crate::configuration;
 
 pub struct ParseRound {
     deriv: Option<Language>,
@@ -68,7 +70,7 @@ impl ParseRound {
                 language_list.insert_new_language(lang);
                 Ok(self.memo.build_memo())
             } else {
-                return Err(ParseError);
+                Err(ParseError::Rejected)
             }
         }
     }
@@ -105,9 +107,13 @@ impl ParseRound {
     }
 
     pub fn e_sim(lang: &mut Language, language_list: &LanguageList, finite_state_automaton: &FiniteStateAutomaton) {
-        let mut to_simulate: Vec<((State, Depth), HashSet<Rules>)> = lang.edges_ref().clone().into_iter().collect();
+        // Every edge to simulate carries the edges on its epsilon path, an edge which is already on the path is not
+        // visited again. Following an epsilon cycle only adds derivations, so this keeps e_sim from looping forever.
+        let mut to_simulate: Vec<(Edge, HashSet<Rules>, Vec<Edge>)> = lang.edges_ref().clone().into_iter()
+            .map(|(edge, rules_set)| (edge, rules_set, vec![edge]))
+            .collect();
 
-        while let Some(((source_state, dest_depth), applied_rules_set)) = to_simulate.pop() {
+        while let Some(((source_state, dest_depth), applied_rules_set, path)) = to_simulate.pop() {
             if let Some(destinations) = finite_state_automaton.simulate(&source_state, Symbol::Epsilon) {
                 for (end_state, new_rules, end_state_accepting) in destinations {
                     let dest_language: &Language = language_list.get(dest_depth).unwrap();
@@ -118,15 +124,20 @@ impl ParseRound {
                             lang.extend_completed_parses(res_rules_set.clone().into_iter());
                         } else {
                             for ((state, depth), rules_set) in dest_language.edges_ref() {
+                                let edge: Edge = (*state, dest_depth + *depth);
+                                if path.contains(&edge) {
+                                    continue;
+                                }
                                 let new_res_rules_set: HashSet<Rules> = ParseRound::concatenate_rules_sets(&res_rules_set, rules_set);
-                                lang.extend_edge((*state, dest_depth + *depth), new_res_rules_set.clone());
-                                to_simulate.push(((*state, dest_depth + *depth), new_res_rules_set));
+                                lang.extend_edge(edge, new_res_rules_set.clone());
+                                to_simulate.push((edge, new_res_rules_set, [&path[..], &[edge]].concat()));
                             }
                         }
                     }
-                    if finite_state_automaton.has_transition(end_state) {
-                        lang.extend_edge((*end_state, dest_depth), res_rules_set.clone());
-                        to_simulate.push(((*end_state, dest_depth), res_rules_set));
+                    let edge: Edge = (*end_state, dest_depth);
+                    if finite_state_automaton.has_transition(end_state) && !path.contains(&edge) {
+                        lang.extend_edge(edge, res_rules_set.clone());
+                        to_simulate.push((edge, res_rules_set, [&path[..], &[edge]].concat()));
                     }
                 }
             }
@@ -353,9 +364,23 @@ pub fn apply_memo(memo: &Memo, mut curr_lang: Language, language_list: &mut Lang
 }
 
 #[derive(Debug, PartialEq, Eq)]
-pub struct ParseError;
+pub enum ParseError {
+    /// The input is not in the language of the grammar.
+    Rejected,
+    /// The languages nested more than the given limit deep while parsing.
+    DepthExceeded(usize),
+}
+
+/// The maximum nesting depth of languages used by `parse`, high enough to not affect normal grammars and inputs.
+pub const DEFAULT_MAX_DEPTH: usize = 100_000;
 
 pub fn parse(token_string: Vec<Terminal>, grammar: &Grammar, memoize: &mut Memoize) -> Result<Language, ParseError> {
+    parse_with_max_depth(token_string, grammar, memoize, DEFAULT_MAX_DEPTH)
+}
+
+/// Parses like `parse`, but fails with ParseError::DepthExceeded as soon as the languages nest more than `max_depth`
+/// deep, which protects against inputs and grammars making the parser nest without bound.
+pub fn parse_with_max_depth(token_string: Vec<Terminal>, grammar: &Grammar, memoize: &mut Memoize, max_depth: usize) -> Result<Language, ParseError> {
     let finite_state_automaton: &FiniteStateAutomaton = &grammar.finite_state_automaton;
     let mut language_list: LanguageList = LanguageList::new();
 
@@ -392,11 +417,15 @@ pub fn parse(token_string: Vec<Terminal>, grammar: &Grammar, memoize: &mut Memoi
                 }
             }
 
+            if language_list.len() > max_depth {
+                return Err(ParseError::DepthExceeded(max_depth));
+            }
+
             //println!("End lang_list: {}", language_list);
             //println!("{}", memoize);
 
         } else {
-            return Err(ParseError);
+            return Err(ParseError::Rejected);
         }
         
     }
@@ -409,7 +438,7 @@ pub fn parse(token_string: Vec<Terminal>, grammar: &Grammar, memoize: &mut Memoi
         Ok(last_lang)
     } else {
         //println!("{}", language_list);
-        Err(ParseError)
+        Err(ParseError::Rejected)
     }
 }
 
@@ -426,9 +455,105 @@ pub fn find_parses(token_string: Vec<Terminal>, grammar: &Grammar, memoize: &mut
             if last_lang.is_final() && last_lang.has_completed_parses() {
                 Ok(last_lang.take_completed_parses().collect())
             } else {
-                Err(ParseError)
+                Err(ParseError::Rejected)
             }
         },
         Err(e) => {Err(e)},
     }
-}
\ No newline at end of file
+}
+
+/// The parts of a grammar which a corpus does not exercise, see `Grammar::coverage`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Coverage {
+    /// The terminals of the grammar which occur in none of the accepted inputs.
+    pub unused_terminals: BTreeSet<Terminal>,
+    /// The rules of the grammar which are applied in none of the derivations of the accepted inputs.
+    pub unused_rules: BTreeSet<Rule>,
+    /// The inputs which are not accepted, these do not count towards the coverage.
+    pub rejected: Vec<Vec<Terminal>>,
+}
+
+/// The semantic actions registered per rule, see `Grammar::parse_with_actions`.
+pub type Actions<A> = HashMap<(Nonterminal, Rule), Box<dyn Fn(&mut A)>>;
+
+impl Grammar {
+    /// Parses the input and invokes the registered action of every rule applied along an accepting derivation, in the
+    /// order in which `find_parses` reports the rules. Actions are registered under the nonterminal of the rule and
+    /// the rule itself, rules without an action are skipped. When the grammar is ambiguous, the actions of only one
+    /// of the derivations fire, which one is unspecified. Returns whether the input is accepted; when it is not, no
+    /// action fires.
+    pub fn parse_with_actions<A>(&self, input: &[Terminal], actions: &Actions<A>, acc: &mut A) -> bool {
+        let mut memoize: Memoize = Memoize::new();
+        match find_parses(input.to_vec(), self, &mut memoize) {
+            Ok(completed_parses) => {
+                if let Some(rules) = completed_parses.into_iter().min() {
+                    for rule in rules {
+                        if let Some(action) = actions.get(&(rule.0, rule)) {
+                            action(acc);
+                        }
+                    }
+                }
+                true
+            },
+            Err(_) => {false},
+        }
+    }
+
+    /// Parses the input with the automaton of the grammar, preferring rules of higher priority wherever the automaton
+    /// has a choice, and returns the rules collected along the preferred accepting path like CompiledFsa::parse, or
+    /// None if the input is not accepted. Priorities are registered like actions, under the nonterminal of the rule
+    /// and the rule itself, and rules without one have priority 0. The automaton is searched depth-first, one terminal
+    /// at a time: the ways to consume the next terminal are tried in the order of the highest priority among the
+    /// rules they collect, a way collecting no rules counting as priority 0, and the first path to accept is
+    /// returned. Like the first alternative winning in a PEG, a lower priority way is only tried once the higher
+    /// priority ones cannot lead to acceptance. The derivations are never enumerated, and the rules come in the order in which the automaton
+    /// collects them, which differs from `find_parses`.
+    pub fn parse_prioritized(&self, input: &[Terminal], priorities: &HashMap<(Nonterminal, Rule), i32>) -> Option<Vec<Rules>> {
+        configuration::parse_first(&self.finite_state_automaton, input, |added: &[Rules]| -> Reverse<i32> {
+            Reverse(added.iter().flat_map(|rules| rules.iter()).map(|rule| priorities.get(&(rule.0, rule.clone())).cloned().unwrap_or(0)).max().unwrap_or(0))
+        })
+    }
+
+    /// Returns at most `max` of the derivations of the input, in sorted order, and whether there were more. Every
+    /// derivation is given as its sequence of rules, like in `find_parses`. Note that the parser still collects
+    /// all derivations, the cap only bounds what is returned.
+    pub fn parse_all_capped(&self, input: &[Terminal], max: usize) -> (Vec<Rules>, bool) {
+        let mut memoize: Memoize = Memoize::new();
+        match find_parses(input.to_vec(), self, &mut memoize) {
+            Ok(completed_parses) => {
+                let mut res: Vec<Rules> = completed_parses.into_iter().collect();
+                res.sort();
+                let more: bool = res.len() > max;
+                res.truncate(max);
+                (res, more)
+            },
+            Err(_) => {(Vec::new(), false)},
+        }
+    }
+
+    /// Parses every input of the corpus and reports the terminals and rules of the grammar which none of the accepted
+    /// inputs uses. The rules of all derivations of an accepted input count as used, like the rules collected by
+    /// `FiniteStateAutomaton::used_rules` for the whole automaton.
+    pub fn coverage(&self, corpus: &[Vec<Terminal>]) -> Coverage {
+        let mut res: Coverage = Coverage{
+            unused_terminals: self.terminals.iter().cloned().collect(),
+            unused_rules: self.rules.iter().flat_map(|(nt, words)| words.iter().map(|word| (*nt, word.clone()))).collect(),
+            rejected: Vec::new(),
+        };
+        for input in corpus {
+            let mut memoize: Memoize = Memoize::new();
+            match find_parses(input.clone(), self, &mut memoize) {
+                Ok(completed_parses) => {
+                    for terminal in input {
+                        res.unused_terminals.remove(terminal);
+                    }
+                    for rule in completed_parses.iter().flatten() {
+                        res.unused_rules.remove(rule);
+                    }
+                },
+                Err(_) => res.rejected.push(input.clone()),
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,73 @@
+//! # Recognizer
+//!
+//! The `recognizer` module defines a stripped down version of the finite state automaton, which only contains the
+//! data needed to decide whether a string is accepted. It is built by FiniteStateAutomaton::to_recognizer.
+
+use std::collections::{HashSet, HashMap};
+
+use crate::word::*;
+use crate::finite_state_automaton::*;
+use crate::configuration::{self, TransitionLookup, Entered};
+
+/// A finite state automaton without any of the derivation rules, so it can recognize, but not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recognizer {
+    pub(crate) states: HashSet<State>,
+    pub(crate) accepting_states: HashSet<State>,
+    pub(crate) start: State,
+    pub(crate) transitions: HashMap<State, HashMap<Symbol, HashSet<State>>>,
+    pub(crate) atomic_to_state: HashMap<(Symbol, Terminal), State>,
+}
+
+impl Recognizer {
+    pub fn states(&self) -> &HashSet<State> {
+        &self.states
+    }
+
+    pub fn is_accepting(&self, curr_state: &State) -> bool {
+        self.accepting_states.contains(curr_state)
+    }
+
+    /// Determines whether the input is accepted, in the same way as FiniteStateAutomaton::recognize.
+    pub fn recognize(&self, input: &[Terminal]) -> bool {
+        configuration::recognize(self, input)
+    }
+}
+
+impl TransitionLookup for Recognizer {
+    type Rules = ();
+
+    fn start_state(&self) -> State {
+        self.start
+    }
+
+    fn accepting(&self, state: State) -> bool {
+        self.is_accepting(&state)
+    }
+
+    fn carries_rules(&self, _: &()) -> bool {
+        false
+    }
+
+    fn epsilon_transitions(&self, state: State, mut f: impl FnMut(State, &())) {
+        for dest in self.transitions.get(&state).and_then(|trans_list| trans_list.get(&Symbol::Epsilon)).into_iter().flatten() {
+            f(*dest, &());
+        }
+    }
+
+    fn terminal_transitions(&self, state: State, terminal: Terminal, mut f: impl FnMut(State, &(), Entered<()>)) {
+        for (symbol, destinations) in self.transitions.get(&state).into_iter().flatten() {
+            let entered: Entered<()> = match symbol {
+                Symbol::Terminal(t) if *t == terminal => None,
+                Symbol::Nonterminal(_) => match self.atomic_to_state.get(&(*symbol, terminal)) {
+                    Some(atomic_state) => Some((*atomic_state, None)),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            for dest in destinations {
+                f(*dest, &(), entered);
+            }
+        }
+    }
+}
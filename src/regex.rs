@@ -15,6 +15,9 @@ pub enum RegexSymbol {
     Terminal(Terminal),
     Nonterminal(Nonterminal),
 
+    // Any terminal of the alphabet except the given ones, from a placeholder terminal of a terminal class.
+    TerminalExcept(BTreeSet<Terminal>),
+
     // Placeholder symbol for atomic languages calculated in the future. If these atomic languages will exist,
     // they will be prepended to the rest of the rule following the atomic language symbol.
     AtomicLanguage(Nonterminal, Terminal),
@@ -28,12 +31,20 @@ pub enum RegexSymbol {
 #[derive(Debug)]
 pub struct Regex {
     pub regex: HashMap<(Nonterminal, Terminal), RegexNode>,
+
+    // Further alternatives of an atomic language which cannot be expressed as part of its node in regex, the atomic
+    // language is the union of the node and these alternatives.
+    pub alternatives: HashMap<(Nonterminal, Terminal), Vec<RegexNode>>,
 }
 
 impl fmt::Display for Regex {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for ((nonterminal, terminal), node) in &self.regex {
-            write!(f, "[{}]({}): {}\n", nonterminal, terminal, node)?;
+            write!(f, "[{}]({}): {}", nonterminal, terminal, node)?;
+            for alternative in self.alternatives.get(&(*nonterminal, *terminal)).into_iter().flatten() {
+                write!(f, " + {}", alternative)?;
+            }
+            write!(f, "\n")?;
         }
         Ok(())
     }
@@ -43,18 +54,24 @@ impl Regex {
 
     /// Takes a set of terminals and a set of derivation rules and calculates its associated atomic languages as a regular expression.
     pub fn new(terminals: &HashSet<Terminal>, rules: &HashMap<Nonterminal, HashSet<Word>>) -> Regex {
+        Regex::new_with_classes(terminals, rules, &HashMap::new())
+    }
+
+    /// Calculates the atomic languages like `new`, where the placeholder terminals of the given classes stand for any
+    /// of the terminals except the excluded ones. The terminals should not contain the placeholders themselves.
+    pub fn new_with_classes(terminals: &HashSet<Terminal>, rules: &HashMap<Nonterminal, HashSet<Word>>, classes: &HashMap<Terminal, HashSet<Terminal>>) -> Regex {
         let mut atomic_regex_rules: HashMap<(Nonterminal, Terminal), (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>)> = HashMap::new();
         let mut queue: VecDeque<(Nonterminal, Terminal)> = VecDeque::new();
 
         for (nonterminal, rule_list) in rules {
             for terminal in terminals {
-                let regex_rules = Regex::calculate_regex_rules(nonterminal, terminal, rule_list, rules);
+                let regex_rules = Regex::calculate_regex_rules(nonterminal, terminal, rule_list, rules, classes);
                 atomic_regex_rules.insert((*nonterminal, *terminal), regex_rules);
                 queue.push_back((*nonterminal, *terminal));
             }
         }
 
-        Regex {regex: Regex::build_regex_map(queue, atomic_regex_rules)}
+        Regex::build_regex_map(queue, atomic_regex_rules)
     }
 
     // For a given nonterminal, its associated derivation rules and a terminal, sorts derivation rules in three separate sets according what properties they hold.
@@ -68,15 +85,14 @@ impl Regex {
     // S -> epsilon
     // then S -> aSa and S -> epsilon are sorted into direct, S -> Sa into recursive.
     // Additionally, the rules S -> aa and S -> a are created by nulling S in the first two rules and both are sorted into direct.
-    fn calculate_regex_rules(nonterminal: &Nonterminal, terminal: &Terminal, rule_list: &HashSet<Vec<Symbol>>, rules: &HashMap<Nonterminal, HashSet<Word>>) -> (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>) {
+    fn calculate_regex_rules(nonterminal: &Nonterminal, terminal: &Terminal, rule_list: &HashSet<Vec<Symbol>>, rules: &HashMap<Nonterminal, HashSet<Word>>, classes: &HashMap<Terminal, HashSet<Terminal>>) -> (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>) {
         let mut direct: HashSet<(Vec<RegexSymbol>, Rules)> = HashSet::new();
         let mut recursive: HashSet<(Vec<RegexSymbol>, Rules)> = HashSet::new();
         let mut different_atomic: HashSet<(Vec<RegexSymbol>, Rules)> = HashSet::new();
-        let mut nullable_atomic: bool = false;
 
         for rule in rule_list {
-            let regex_rule = Regex::word_to_regex_word(rule, *terminal);
-            if regex_rule.len() > 0 {
+            let regex_rule = Regex::word_to_regex_word_with(rule, *terminal, classes);
+            if !regex_rule.is_empty() {
                 match regex_rule[0] {
                     RegexSymbol::AtomicLanguage(nt, _) => {
                         if *nonterminal == nt {
@@ -85,10 +101,6 @@ impl Regex {
                             different_atomic.insert((regex_rule, vec![(*nonterminal, rule.clone())]));
                         }
                     },
-                    RegexSymbol::Epsilon => {
-                        nullable_atomic = true;
-                        direct.insert((regex_rule, vec![(*nonterminal, rule.clone())]));
-                    }
                     _ => {
                         direct.insert((regex_rule, vec![(*nonterminal, rule.clone())]));
                     },
@@ -96,36 +108,43 @@ impl Regex {
             }
         }
 
+        // Nulled symbols in the remainder of a direct rule are kept, they are turned into transitions carrying the nulling rules.
+        let mut new_direct_rules: HashSet<(Vec<RegexSymbol>, Rules)> = HashSet::new();
+        Regex::null_rules(rules, &direct, &mut new_direct_rules);
+        direct.extend(new_direct_rules);
+
         let mut new_rules: HashSet<(Vec<RegexSymbol>, Rules)> = HashSet::new();
-        Regex::null_rules(rules, &mut direct, nullable_atomic, &mut new_rules);
-        Regex::null_rules(rules, &mut recursive, nullable_atomic, &mut new_rules);
-        Regex::null_rules(rules, &mut different_atomic, nullable_atomic, &mut new_rules);
+        Regex::null_rules(rules, &recursive, &mut new_rules);
+        Regex::null_rules(rules, &different_atomic, &mut new_rules);
 
         for rule in new_rules {
-            let no_nulled_rule = Regex::reg_symb_vec_without_nulled(&rule);
-            let mut new_rules: Rules = rule.1.clone();
-            let collected_rules: Rules = Regex::collect_starting_null_rules(&rule);
-            if collected_rules.len() > 0 {
-                new_rules.extend(collected_rules);
+            if let RegexSymbol::AtomicLanguage(nt, _) = rule.0[0] {
+                if *nonterminal == nt {
+                    recursive.insert(rule);
+                } else {
+                    different_atomic.insert(rule);
+                }
+                continue;
             }
-            match no_nulled_rule.0[0] {
-                RegexSymbol::AtomicLanguage(nt, _) => {
-                    if *nonterminal == nt {
-                        recursive.insert((rule.0.clone(), new_rules));
+
+            // The atomic language at the start of the rule is nulled, so the terminal has to be derived from the first symbol which is not nulled.
+            let collected_rules: Rules = Regex::collect_starting_null_rules(&rule);
+            let new_rules: Rules = [rule.1.clone(), collected_rules].concat();
+            let remainder: Vec<RegexSymbol> = rule.0.into_iter().skip_while(|symbol| matches!(symbol, RegexSymbol::Nulled(_))).collect();
+            match remainder.first() {
+                Some(symbol) if Regex::derives_terminal(symbol, terminal) => {
+                    if remainder.len() > 1 {
+                        direct.insert((remainder[1..].to_vec(), new_rules));
                     } else {
-                        different_atomic.insert((rule.0.clone(), new_rules));
+                        direct.insert((vec![RegexSymbol::Epsilon], new_rules));
                     }
                 },
-                RegexSymbol::Epsilon => {
-                    direct.insert((rule.0.clone(), new_rules));
-                },
-                RegexSymbol::Terminal(t) => {
-                    if t == *terminal && nullable_atomic {
-                        if no_nulled_rule.0.len() > 1 {
-                            direct.insert((no_nulled_rule.0[1..].to_vec(), new_rules));
-                        } else {
-                            direct.insert((vec![RegexSymbol::Epsilon], new_rules));
-                        }
+                Some(RegexSymbol::Nonterminal(nt)) => {
+                    let atomic_rule: Vec<RegexSymbol> = [&[RegexSymbol::AtomicLanguage(*nt, *terminal)], &remainder[1..]].concat();
+                    if nonterminal == nt {
+                        recursive.insert((atomic_rule, new_rules));
+                    } else {
+                        different_atomic.insert((atomic_rule, new_rules));
                     }
                 },
                 _ => {},
@@ -133,22 +152,14 @@ impl Regex {
         }
         (direct, recursive, different_atomic)
     }
-    
-    // From an input, produces a new output with any Nulled symbols removed.
-    fn reg_symb_vec_without_nulled(input: &(Vec<RegexSymbol>, Rules)) -> (Vec<RegexSymbol>, Rules) {
-        let mut res: (Vec<RegexSymbol>, Rules) = (Vec::with_capacity(input.0.len()), input.1.clone());
-        for regsymb in &input.0 {
-            if let RegexSymbol::Nulled(_) = regsymb {
-                if res.0.len() == 0 {
-                    res.1.remove(0);
-                } else {
-                    res.0.push(regsymb.clone());
-                }
-            } else {
-                res.0.push(regsymb.clone());
-            }
+
+    // Determines if the symbol is a terminal, or a class of terminals, from which the given terminal is derived.
+    fn derives_terminal(symbol: &RegexSymbol, terminal: &Terminal) -> bool {
+        match symbol {
+            RegexSymbol::Terminal(t) => t == terminal,
+            RegexSymbol::TerminalExcept(excluded) => !excluded.contains(terminal),
+            _ => false,
         }
-        res
     }
 
     // From an input starting with Nulled symbols, adds the associated rules until a non-nulled symbol is encountered.
@@ -163,9 +174,16 @@ impl Regex {
     }
 
     // From the three lists, direct, recursive and different_atomic, calculates the atomic languages.
-    fn build_regex_map(mut queue: VecDeque<(Nonterminal, Terminal)>, atomic_regex_rules: HashMap<(Nonterminal, Terminal), (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>)>) -> HashMap<(Nonterminal, Terminal), RegexNode> {
+    // A rule starting with a directly left-recursive nonterminal followed by more symbols, e.g. E -> Tx with T -> Ty,
+    // cannot be inlined, as the recursion of T has to loop before the remainder of the rule. Such rules are kept in
+    // different_atomic until the atomic language of T has been calculated, after which its nodes followed by the
+    // remainder and the recursion of E become an alternative of the atomic language of E.
+    fn build_regex_map(mut queue: VecDeque<(Nonterminal, Terminal)>, atomic_regex_rules: HashMap<(Nonterminal, Terminal), (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>)>) -> Regex {
         let mut atomic_regex_rules_working_copy: HashMap<(Nonterminal, Terminal), (HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>, HashSet<(Vec<RegexSymbol>, Rules)>)> = atomic_regex_rules.clone();
         let mut res: HashMap<(Nonterminal, Terminal), RegexNode> = HashMap::new();
+        let mut alternatives: HashMap<(Nonterminal, Terminal), Vec<RegexNode>> = HashMap::new();
+        // the number of atomic languages put back in the queue since the last one was calculated
+        let mut stalled: usize = 0;
 
         while queue.len() > 0 {
             if let Some((nonterminal, terminal)) = queue.pop_front() {
@@ -173,15 +191,22 @@ impl Regex {
                     for different_rule in different_atomic.clone() {
                         if let RegexSymbol::AtomicLanguage(nt, t) = different_rule.0[0] {
                             if let Some((ntdirect, ntrecursive, ntdifferent)) = atomic_regex_rules.get(&(nt, t)) {
+                                if different_rule.0.len() > 1 && !ntrecursive.is_empty() {
+                                    continue;
+                                }
                                 for ntdirect_rule in ntdirect.clone() {
                                     if different_rule.0.len() <= 1 {
                                         direct.insert((ntdirect_rule.0.clone(), [&different_rule.1[..], &ntdirect_rule.1[..]].concat()));
+                                    } else if ntdirect_rule.0 == [RegexSymbol::Epsilon] {
+                                        direct.insert((different_rule.0[1..].to_vec(), [&different_rule.1[..], &ntdirect_rule.1[..]].concat()));
                                     } else {
-                                        direct.insert(([&ntdirect_rule.0[1..], &different_rule.0[1..]].concat(), [&different_rule.1[..], &ntdirect_rule.1[..]].concat()));
+                                        // the terminal has already been derived from the direct rule, so all of its symbols remain
+                                        direct.insert(([&ntdirect_rule.0[..], &different_rule.0[1..]].concat(), [&different_rule.1[..], &ntdirect_rule.1[..]].concat()));
                                     }
                                 }
+                                // the rule is only the nonterminal, so the recursion of the nonterminal is the recursion of the rule
                                 for ntrecursive_rule in ntrecursive.clone() {
-                                    recursive.insert(([&[RegexSymbol::AtomicLanguage(nonterminal, t)], &different_rule.0[1..], &ntrecursive_rule.0[1..]].concat(), ntrecursive_rule.1.clone()));
+                                    recursive.insert(([&[RegexSymbol::AtomicLanguage(nonterminal, t)], &ntrecursive_rule.0[1..]].concat(), ntrecursive_rule.1.clone()));
                                 }
                                 for ntdifferent_rule in ntdifferent.clone() {
                                     if let RegexSymbol::AtomicLanguage(nt2, _) = ntdifferent_rule.0[0] {
@@ -199,32 +224,41 @@ impl Regex {
                     
                     if different_atomic.len() == 0 && direct.len() > 0 {
                         res.insert((nonterminal, terminal), Regex::build_regex_node(&direct, &Vec::new(), &recursive));
-                    } else {
-                        if different_atomic.len() > 0 {
-                            let mut all_in_res: bool = true;
-                            let mut regex_nodes: Vec<(RegexNode, Rules)> = Vec::with_capacity(different_atomic.len());
-                            for different_rule in different_atomic.iter() {
-                                if let RegexSymbol::AtomicLanguage(nt, t) = different_rule.0[0] {
-                                    if let Some(regex) = res.get(&(nt, t)) {
-                                        regex_nodes.push((regex.clone(), different_rule.1.clone()));
-                                    } else if queue.contains(&(nt, t)) {
-                                        all_in_res = false;
-                                        queue.push_back((nonterminal, terminal));
-                                    } else {
-                                        all_in_res = false;
+                        stalled = 0;
+                    } else if different_atomic.len() > 0 {
+                        let mut all_in_res: bool = true;
+                        let mut nodes: Vec<RegexNode> = Vec::new();
+                        if !direct.is_empty() {
+                            nodes.push(Regex::build_regex_node(direct, &Vec::new(), recursive));
+                        }
+                        for different_rule in different_atomic.iter() {
+                            if let RegexSymbol::AtomicLanguage(nt, t) = different_rule.0[0] {
+                                if let Some(regex) = res.get(&(nt, t)) {
+                                    for nt_node in std::iter::once(regex).chain(alternatives.get(&(nt, t)).into_iter().flatten()) {
+                                        nodes.push(Regex::build_leading_node(nt_node, different_rule, recursive));
                                     }
+                                } else if queue.contains(&(nt, t)) {
+                                    all_in_res = false;
                                 }
+                                // otherwise the atomic language of the nonterminal is empty, and so is the alternative
                             }
-                            if all_in_res {
-                                res.insert((nonterminal, terminal), Regex::build_regex_node(&direct, &regex_nodes, &recursive));
-                                continue;
+                        }
+                        // a cycle of such rules, where no atomic language in the queue can be calculated anymore, is left out
+                        if !all_in_res && stalled <= queue.len() {
+                            stalled += 1;
+                            queue.push_back((nonterminal, terminal));
+                        } else if !nodes.is_empty() {
+                            res.insert((nonterminal, terminal), nodes.remove(0));
+                            if !nodes.is_empty() {
+                                alternatives.insert((nonterminal, terminal), nodes);
                             }
+                            stalled = 0;
                         }
                     }
                 }
             }
         }
-        res
+        Regex {regex: res, alternatives}
     }
 
     // Produces all possible combinations of nulled rules.
@@ -233,35 +267,28 @@ impl Regex {
     // S -> epsilon
     // the set of new rules will be:
     // S -> SaSb, S -> SabS, S -> aSbS, S -> Sab, S -> aSb, S -> abS, S -> ab
-    fn null_rules(rules: &HashMap<Nonterminal, HashSet<Word>>, rule_list: &HashSet<(Vec<RegexSymbol>, Rules)>, nullable_atomic: bool, new_rules: &mut HashSet<(Vec<RegexSymbol>, Rules)>) {
+    fn null_rules(rules: &HashMap<Nonterminal, HashSet<Word>>, rule_list: &HashSet<(Vec<RegexSymbol>, Rules)>, new_rules: &mut HashSet<(Vec<RegexSymbol>, Rules)>) {
         for rule in rule_list {
             let mut nullable_positions: Vec<(usize, Rules)> = Vec::new();
             for (pos, symbol) in rule.0.iter().enumerate() {
                 match symbol {
-                    RegexSymbol::Nonterminal(nt) => {
-                        if Regex::nullable_nonterminal(rules, &nt) {
-                            nullable_positions.push((pos, vec![(*nt, vec![Symbol::Epsilon])]));
-                        }
+                    RegexSymbol::Nonterminal(nt) | RegexSymbol::AtomicLanguage(nt, _) if Regex::nullable_nonterminal(rules, nt) => {
+                        nullable_positions.push((pos, vec![(*nt, vec![Symbol::Epsilon])]));
                     },
-                    RegexSymbol::AtomicLanguage(nt, _) => {
-                        if nullable_atomic && Regex::nullable_nonterminal(rules, &nt) {
-                            nullable_positions.push((pos, vec![(*nt, vec![Symbol::Epsilon])]));
-                        }
-                    }
                     _ => {},
                 }
             }
 
             let nullable_combinations: Vec<Vec<(usize, Rules)>> = Regex::powerset(&nullable_positions);
             for comb in nullable_combinations {
-                let mut rev = comb.clone();
-                rev.sort();
-                rev.reverse();
+                if comb.is_empty() {
+                    continue;
+                }
                 let mut new_word = rule.0.clone();
-                for (index, erasing_rules) in rev {
+                for (index, erasing_rules) in comb {
                     new_word[index] = RegexSymbol::Nulled(erasing_rules);
                 }
-                if rule.1.len() > 0 {
+                if !rule.1.is_empty() {
                     new_rules.insert((new_word, rule.1.clone()));
                 }
             }
@@ -321,22 +348,45 @@ impl Regex {
         RegexNode{nodes: res_nodes}
     }
 
+    // From the node of the atomic language of the nonterminal leading the rule, calculates the regular expression of
+    // the node followed by the remainder of the rule and the recursion of the calling nonterminal.
+    fn build_leading_node(nt_node: &RegexNode, different_rule: &(Vec<RegexSymbol>, Rules), recursive: &HashSet<(Vec<RegexSymbol>, Rules)>) -> RegexNode {
+        let mut res_nodes: Vec<WordNode> = nt_node.nodes.clone();
+        if let Some(first) = res_nodes.first_mut() {
+            *first = first.append_rule(&different_rule.1);
+        }
+        if different_rule.0.len() > 1 {
+            res_nodes.push(WordNode{words: BTreeSet::from([(Regex::regex_word_to_word(&different_rule.0[1..].to_vec()), Vec::new())]), kleene_star: false});
+        }
+        res_nodes.extend(Regex::build_regex_node(&HashSet::new(), &Vec::new(), recursive).nodes);
+        RegexNode{nodes: res_nodes}
+    }
+
     pub fn word_to_regex_word(rule: &Word, terminal: Terminal) -> Vec<RegexSymbol> {
+        Regex::word_to_regex_word_with(rule, terminal, &HashMap::new())
+    }
+
+    // Like word_to_regex_word, where a placeholder terminal of the classes derives every terminal it stands for.
+    fn word_to_regex_word_with(rule: &Word, terminal: Terminal, classes: &HashMap<Terminal, HashSet<Terminal>>) -> Vec<RegexSymbol> {
         match rule[0] {
             Symbol::Nonterminal(nt) => {
                 let mut res: Vec<RegexSymbol> = Vec::with_capacity(rule.len());
                 res.push(RegexSymbol::AtomicLanguage(nt, terminal));
                 for symbol in &rule[1..] {
-                    res.push(Regex::symbol_to_regex_symbol(symbol));
+                    res.push(Regex::symbol_to_regex_symbol(symbol, classes));
                 }
                 res
             },
             Symbol::Terminal(t) => {
-                if t == terminal {
+                let derives_terminal: bool = match classes.get(&t) {
+                    Some(excluded) => !excluded.contains(&terminal),
+                    None => t == terminal,
+                };
+                if derives_terminal {
                     if rule.len() > 1 {
                         let mut res: Vec<RegexSymbol> = Vec::with_capacity(rule.len());
                         for symbol in &rule[1..] {
-                            res.push(Regex::symbol_to_regex_symbol(symbol));
+                            res.push(Regex::symbol_to_regex_symbol(symbol, classes));
                         }
                         res
                     } else {
@@ -350,9 +400,10 @@ impl Regex {
         }
     }
 
-    fn symbol_to_regex_symbol(symbol: &Symbol) -> RegexSymbol {
+    fn symbol_to_regex_symbol(symbol: &Symbol, classes: &HashMap<Terminal, HashSet<Terminal>>) -> RegexSymbol {
         match symbol {
             Symbol::Nonterminal(nt) => RegexSymbol::Nonterminal(*nt),
+            Symbol::Terminal(t) if classes.contains_key(t) => RegexSymbol::TerminalExcept(classes[t].iter().cloned().collect()),
             Symbol::Terminal(t) => RegexSymbol::Terminal(*t),
             Symbol::Epsilon => RegexSymbol::Epsilon
         }
@@ -374,6 +425,7 @@ impl Regex {
         match regex_symbol {
             RegexSymbol::Nonterminal(nt) => Some(WordNodeSymbol::Nonterminal(*nt)),
             RegexSymbol::Terminal(t) => Some(WordNodeSymbol::Terminal(*t)),
+            RegexSymbol::TerminalExcept(excluded) => Some(WordNodeSymbol::TerminalExcept(excluded.clone())),
             RegexSymbol::Epsilon => Some(WordNodeSymbol::Epsilon),
             RegexSymbol::Nulled(rules) => Some(WordNodeSymbol::Rules(rules.clone())),
             RegexSymbol::AtomicLanguage(_, _) => None,
@@ -384,6 +436,10 @@ impl Regex {
         for ((nonterminal, terminal), node) in &self.regex {
             print!("[{}]({}): ", nonterminal, terminal);
             node.print_with_rules();
+            for alternative in self.alternatives.get(&(*nonterminal, *terminal)).into_iter().flatten() {
+                print!(" + ");
+                alternative.print_with_rules();
+            }
             println!();
         }
         println!();
@@ -457,11 +513,32 @@ pub fn print_rules(rules: &Rules, f: &mut fmt::Formatter) -> fmt::Result {
 }
 
 pub fn print_rule(rule: &Rule, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "[{} -> ", rule.0)?;
+    write!(f, "{}", rule_to_string(rule))
+}
+
+/// Builds the rule `nonterminal -> word`.
+pub fn rule_of(nonterminal: Nonterminal, word: &[Symbol]) -> Rule {
+    (nonterminal, word.to_vec())
+}
+
+/// Builds a sequence of rules consisting of only the rule `nonterminal -> word`.
+pub fn single_rules(nonterminal: Nonterminal, word: &[Symbol]) -> Rules {
+    vec![rule_of(nonterminal, word)]
+}
+
+/// Renders the rule as `[A -> w]`, the form used by every Display implementation and DOT output of the crate.
+pub fn rule_to_string(rule: &Rule) -> String {
+    let mut res: String = format!("[{} -> ", rule.0);
     for symbol in &rule.1 {
-        write!(f, "{}", symbol)?;
+        res.push_str(&symbol.to_string());
     }
-    write!(f, "]")
+    res.push(']');
+    res
+}
+
+/// Renders the rules one after another, see `rule_to_string`.
+pub fn rules_to_string(rules: &Rules) -> String {
+    rules.iter().map(rule_to_string).collect()
 }
 
 // Placeholder structure similar to Symbol, allowing us to keep track of the rules used to null symbols.
@@ -470,6 +547,8 @@ pub fn print_rule(rule: &Rule, f: &mut fmt::Formatter) -> fmt::Result {
 pub enum WordNodeSymbol {
     Rules(Rules),
     Terminal(Terminal),
+    // Any terminal of the alphabet except the given ones, lowered into a transition on each of them.
+    TerminalExcept(BTreeSet<Terminal>),
     Nonterminal(Nonterminal),
     Epsilon,
 }
@@ -478,6 +557,7 @@ impl fmt::Display for WordNodeSymbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             WordNodeSymbol::Terminal(t) => write!(f, "{}", t),
+            WordNodeSymbol::TerminalExcept(excluded) => write!(f, "[^{}]", excluded.iter().collect::<String>()),
             WordNodeSymbol::Nonterminal(nt) => write!(f, "{}", nt),
             WordNodeSymbol::Epsilon => write!(f, "e"),
             WordNodeSymbol::Rules(rules) => {
@@ -624,4 +704,15 @@ mod tests{
         assert_eq!(Regex::regex_word_to_word(&vec![RegexSymbol::Terminal('a'), RegexSymbol::AtomicLanguage('a', 'b')]), vec![]);
         assert_eq!(Regex::regex_word_to_word(&vec![RegexSymbol::Nonterminal('a'), RegexSymbol::AtomicLanguage('a', 'b')]), vec![]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rule_to_string_test() {
+        let word: Word = vec![Symbol::Terminal('a'), Symbol::Nonterminal('S'), Symbol::Terminal('b')];
+        assert_eq!(rule_of('S', &word), ('S', word.clone()));
+        assert_eq!(single_rules('S', &word), vec![('S', word.clone())]);
+        assert_eq!(rule_to_string(&rule_of('S', &word)), "[S -> aSb]");
+        assert_eq!(rule_to_string(&rule_of('S', &[Symbol::Epsilon])), "[S -> e]");
+        assert_eq!(rules_to_string(&vec![rule_of('S', &word), rule_of('S', &[Symbol::Epsilon])]), "[S -> aSb][S -> e]");
+        assert_eq!(rules_to_string(&Vec::new()), "");
+    }
+}
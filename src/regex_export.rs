@@ -0,0 +1,238 @@
+//! # Regex export
+//!
+//! The `regex_export` module converts the language of a finite state automaton into a regular expression string by
+//! state elimination. Derivation rules are ignored, only the terminals consumed along the transitions are kept.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::word::*;
+use crate::finite_state_automaton::*;
+
+// A regular expression, kept simplified by its constructors.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum RegexTerm {
+    Empty,
+    Epsilon,
+    Literal(String),
+    Concat(Vec<RegexTerm>),
+    Alt(BTreeSet<RegexTerm>),
+    Star(Box<RegexTerm>),
+}
+
+impl RegexTerm {
+    fn concat(self, other: RegexTerm) -> RegexTerm {
+        match (self, other) {
+            (RegexTerm::Empty, _) | (_, RegexTerm::Empty) => RegexTerm::Empty,
+            (RegexTerm::Epsilon, term) | (term, RegexTerm::Epsilon) => term,
+            (RegexTerm::Concat(mut left), RegexTerm::Concat(right)) => {
+                left.extend(right);
+                RegexTerm::Concat(left)
+            },
+            (RegexTerm::Concat(mut left), right) => {
+                left.push(right);
+                RegexTerm::Concat(left)
+            },
+            (left, RegexTerm::Concat(right)) => RegexTerm::Concat([vec![left], right].concat()),
+            (left, right) => RegexTerm::Concat(vec![left, right]),
+        }
+    }
+
+    fn alt(self, other: RegexTerm) -> RegexTerm {
+        let mut terms: BTreeSet<RegexTerm> = BTreeSet::new();
+        for term in [self, other] {
+            match term {
+                RegexTerm::Empty => {},
+                RegexTerm::Alt(alternatives) => terms.extend(alternatives),
+                term => {
+                    terms.insert(term);
+                },
+            }
+        }
+        match terms.len() {
+            0 => RegexTerm::Empty,
+            1 => terms.pop_first().unwrap(),
+            _ => RegexTerm::Alt(terms),
+        }
+    }
+
+    fn star(self) -> RegexTerm {
+        match self {
+            RegexTerm::Empty | RegexTerm::Epsilon => RegexTerm::Epsilon,
+            RegexTerm::Star(term) => RegexTerm::Star(term),
+            term => RegexTerm::Star(Box::new(term)),
+        }
+    }
+
+    // Writes the term, wrapped in parentheses when it binds less strongly than the surrounding operator.
+    fn fmt_grouped(&self, f: &mut fmt::Formatter, group_concat: bool) -> fmt::Result {
+        let grouped: bool = match self {
+            RegexTerm::Alt(terms) => !(terms.len() == 2 && terms.contains(&RegexTerm::Epsilon)) || group_concat,
+            RegexTerm::Concat(_) => group_concat,
+            _ => false,
+        };
+        if grouped {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for RegexTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegexTerm::Empty => write!(f, "∅"),
+            RegexTerm::Epsilon => write!(f, "()"),
+            RegexTerm::Literal(literal) => write!(f, "{}", literal),
+            RegexTerm::Concat(terms) => {
+                for term in terms {
+                    term.fmt_grouped(f, false)?;
+                }
+                Ok(())
+            },
+            RegexTerm::Alt(terms) => {
+                // an alternative between epsilon and a single other term is written as an option
+                if terms.len() == 2 && terms.contains(&RegexTerm::Epsilon) {
+                    let term: &RegexTerm = terms.iter().find(|term| **term != RegexTerm::Epsilon).unwrap();
+                    term.fmt_grouped(f, true)?;
+                    return write!(f, "?");
+                }
+                for (index, term) in terms.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}", term)?;
+                }
+                Ok(())
+            },
+            RegexTerm::Star(term) => {
+                term.fmt_grouped(f, true)?;
+                write!(f, "*")
+            },
+        }
+    }
+}
+
+impl FiniteStateAutomaton {
+    /// Returns a regular expression for the language of the automaton, obtained by state elimination. Only the
+    /// terminal-consuming projection of the automaton is reflected, the derivation rules are ignored. A nonterminal
+    /// transition is replaced by the atomic languages of its nonterminal. An atomic language which refers back to its
+    /// own nonterminal at its very end, as for `A -> aA | a`, is solved by Arden's lemma, giving `aa*`. Any other
+    /// reference back to a nonterminal which is already being expanded, e.g. through self-embedding or through another
+    /// nonterminal, is not solved and None is returned, even though the language may still be regular. The empty word
+    /// is written as `()`, the empty language as `∅`, and terminals which are special characters in regular
+    /// expressions are escaped with a backslash.
+    pub fn to_regex_string(&self) -> Option<String> {
+        self.regex_from(&[(RegexTerm::Epsilon, self.get_start().0)], None, &mut Vec::new()).map(|regex| regex.to_string())
+    }
+
+    // Builds the regular expression for the strings which take one of the roots, after the term leading to it, to an
+    // accepting state. When the roots are the atomic languages of a nonterminal, the nonterminal is given, and a
+    // transition on it into an accepting state without transitions continues from the roots again. Expanding contains
+    // the nonterminals whose atomic languages are currently being expanded. Returns None on any other transition on
+    // one of them.
+    fn regex_from(&self, roots: &[(RegexTerm, State)], nonterminal: Option<Nonterminal>, expanding: &mut Vec<Nonterminal>) -> Option<RegexTerm> {
+        let adjacency = self.adjacency();
+
+        let mut reachable: BTreeSet<State> = roots.iter().map(|(_, root)| *root).collect();
+        let mut to_visit: Vec<State> = reachable.iter().cloned().collect();
+        while let Some(curr) = to_visit.pop() {
+            for (_, dest) in &adjacency[&curr] {
+                if reachable.insert(*dest) {
+                    to_visit.push(*dest);
+                }
+            }
+        }
+
+        // the states are shifted by two, making room for a new start state 0 and a new final state 1
+        let mut edges: BTreeMap<(State, State), RegexTerm> = BTreeMap::new();
+        let add_edge = |edges: &mut BTreeMap<(State, State), RegexTerm>, source: State, dest: State, term: RegexTerm| {
+            let curr: RegexTerm = edges.remove(&(source, dest)).unwrap_or(RegexTerm::Empty);
+            edges.insert((source, dest), curr.alt(term));
+        };
+        for (term, root) in roots {
+            add_edge(&mut edges, 0, root + 2, term.clone());
+        }
+        for curr in &reachable {
+            if self.is_accepting(curr) {
+                add_edge(&mut edges, curr + 2, 1, RegexTerm::Epsilon);
+            }
+            for (symbol, dest) in &adjacency[curr] {
+                match symbol {
+                    Symbol::Terminal(terminal) => add_edge(&mut edges, curr + 2, dest + 2, RegexTerm::Literal(escape_terminal(*terminal))),
+                    Symbol::Epsilon => add_edge(&mut edges, curr + 2, dest + 2, RegexTerm::Epsilon),
+                    // nothing follows the nonterminal, so the atomic languages start over: X = aX | b becomes X = a*b
+                    Symbol::Nonterminal(nt) if nonterminal == Some(*nt) && self.derives_only_epsilon(*dest) => add_edge(&mut edges, curr + 2, 0, RegexTerm::Epsilon),
+                    Symbol::Nonterminal(nt) => {
+                        let term: RegexTerm = self.expand_nonterminal(*nt, expanding)?;
+                        add_edge(&mut edges, curr + 2, dest + 2, term);
+                    },
+                }
+            }
+        }
+
+        for eliminated in reachable.iter().map(|state| state + 2) {
+            let self_loop: RegexTerm = edges.remove(&(eliminated, eliminated)).unwrap_or(RegexTerm::Empty).star();
+            let incoming: Vec<(State, RegexTerm)> = edges.iter()
+                .filter(|((_, dest), _)| *dest == eliminated)
+                .map(|((source, _), term)| (*source, term.clone()))
+                .collect();
+            let outgoing: Vec<(State, RegexTerm)> = edges.iter()
+                .filter(|((source, _), _)| *source == eliminated)
+                .map(|((_, dest), term)| (*dest, term.clone()))
+                .collect();
+            edges.retain(|(source, dest), _| *source != eliminated && *dest != eliminated);
+            for (source, incoming_term) in &incoming {
+                for (dest, outgoing_term) in &outgoing {
+                    let term: RegexTerm = incoming_term.clone().concat(self_loop.clone()).concat(outgoing_term.clone());
+                    add_edge(&mut edges, *source, *dest, term);
+                }
+            }
+        }
+        let start_loop: RegexTerm = edges.remove(&(0, 0)).unwrap_or(RegexTerm::Empty).star();
+        Some(start_loop.concat(edges.remove(&(0, 1)).unwrap_or(RegexTerm::Empty)))
+    }
+
+    // Determines whether the empty word is the only string taking the state to an accepting state, i.e. whether only
+    // epsilon transitions can be followed from it and they lead to an accepting state.
+    fn derives_only_epsilon(&self, state: State) -> bool {
+        let adjacency = self.adjacency();
+        let mut visited: BTreeSet<State> = BTreeSet::from([state]);
+        let mut to_visit: Vec<State> = vec![state];
+        while let Some(curr) = to_visit.pop() {
+            for (symbol, dest) in &adjacency[&curr] {
+                if *symbol != Symbol::Epsilon {
+                    return false;
+                }
+                if visited.insert(*dest) {
+                    to_visit.push(*dest);
+                }
+            }
+        }
+        visited.iter().any(|state| self.is_accepting(state))
+    }
+
+    // Builds the regular expression for a nonterminal transition from the atomic languages of the nonterminal, or None
+    // if the nonterminal is already being expanded.
+    fn expand_nonterminal(&self, nt: Nonterminal, expanding: &mut Vec<Nonterminal>) -> Option<RegexTerm> {
+        if expanding.contains(&nt) {
+            return None;
+        }
+        let roots: Vec<(RegexTerm, State)> = self.alphabet().into_iter()
+            .filter_map(|terminal| self.get_atomic(Symbol::Nonterminal(nt), terminal).map(|(atomic_state, _, _)| (RegexTerm::Literal(escape_terminal(terminal)), *atomic_state)))
+            .collect();
+        expanding.push(nt);
+        let res: Option<RegexTerm> = self.regex_from(&roots, Some(nt), expanding);
+        expanding.pop();
+        res
+    }
+}
+
+fn escape_terminal(terminal: Terminal) -> String {
+    if "\\|*+?()[]{}.^$".contains(terminal) {
+        format!("\\{}", terminal)
+    } else {
+        terminal.to_string()
+    }
+}
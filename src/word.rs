@@ -4,18 +4,68 @@
 //! defines a simple structure that allows us to express combinations of these symbols as words.
 
 use std::fmt;
+use std::cmp::Ordering;
 
 pub type Terminal = char;
 pub type Nonterminal = char;
 pub type Word = Vec<Symbol>;
 
-#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
 pub enum Symbol {
     Terminal(Terminal),
     Nonterminal(Nonterminal),
     Epsilon,
 }
 
+impl Symbol {
+    /// Returns the terminal if the symbol is a terminal.
+    pub fn as_terminal(&self) -> Option<Terminal> {
+        match self {
+            Symbol::Terminal(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    /// Returns the nonterminal if the symbol is a nonterminal.
+    pub fn as_nonterminal(&self) -> Option<Nonterminal> {
+        match self {
+            Symbol::Nonterminal(nt) => Some(*nt),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the symbol is epsilon.
+    pub fn is_epsilon(&self) -> bool {
+        *self == Symbol::Epsilon
+    }
+
+    // The position of the kind of symbol in the ordering of symbols.
+    fn rank(&self) -> u8 {
+        match self {
+            Symbol::Epsilon => 0,
+            Symbol::Terminal(_) => 1,
+            Symbol::Nonterminal(_) => 2,
+        }
+    }
+}
+
+/// Symbols are ordered with epsilon first, then the terminals and then the nonterminals, and by their character
+/// within terminals and nonterminals. This is the order used wherever the crate produces sorted output.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Symbol) -> Ordering {
+        match (self, other) {
+            (Symbol::Terminal(a), Symbol::Terminal(b)) | (Symbol::Nonterminal(a), Symbol::Nonterminal(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -24,4 +74,24 @@ impl fmt::Display for Symbol {
             Symbol::Epsilon => write!(f, "e"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn symbol_helpers_test() {
+        assert_eq!(Symbol::Terminal('a').as_terminal(), Some('a'));
+        assert_eq!(Symbol::Nonterminal('A').as_terminal(), None);
+        assert_eq!(Symbol::Epsilon.as_terminal(), None);
+
+        assert_eq!(Symbol::Terminal('a').as_nonterminal(), None);
+        assert_eq!(Symbol::Nonterminal('A').as_nonterminal(), Some('A'));
+        assert_eq!(Symbol::Epsilon.as_nonterminal(), None);
+
+        assert!(!Symbol::Terminal('e').is_epsilon());
+        assert!(!Symbol::Nonterminal('e').is_epsilon());
+        assert!(Symbol::Epsilon.is_epsilon());
+    }
+}
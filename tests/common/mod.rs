@@ -185,4 +185,100 @@ pub fn test_grammar() -> Grammar {
         vec![Symbol::Epsilon],
     ]));
     Grammar::new(terminals, nonterminals, start, rules)
-}
\ No newline at end of file
+}
+pub fn no_base_case_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['a']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['A']);
+    let start: Nonterminal = 'A';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('A', HashSet::from([
+        vec![Symbol::Terminal('a'), Symbol::Nonterminal('A')],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
+
+pub fn keyword_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['i', 'f', 'n']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['K']);
+    let start: Nonterminal = 'K';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('K', HashSet::from([
+        vec![Symbol::Terminal('i'), Symbol::Terminal('f')],
+        vec![Symbol::Terminal('i'), Symbol::Terminal('n')],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
+
+pub fn common_prefix_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['a', 'b', 'c']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['S', 'B']);
+    let start: Nonterminal = 'S';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('S', HashSet::from([
+        vec![Symbol::Terminal('a'), Symbol::Terminal('b'), Symbol::Nonterminal('B')],
+        vec![Symbol::Terminal('a'), Symbol::Terminal('b'), Symbol::Terminal('c')],
+        vec![Symbol::Terminal('a'), Symbol::Terminal('b')],
+        vec![Symbol::Terminal('a')],
+        vec![Symbol::Terminal('c')],
+    ]));
+    rules.insert('B', HashSet::from([
+        vec![Symbol::Terminal('b'), Symbol::Nonterminal('B')],
+        vec![Symbol::Terminal('b')],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
+
+pub fn nullable_recursive_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['a', 'b']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['S', 'A']);
+    let start: Nonterminal = 'S';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('S', HashSet::from([
+        vec![Symbol::Nonterminal('S'), Symbol::Nonterminal('A')],
+        vec![Symbol::Terminal('a')],
+    ]));
+    rules.insert('A', HashSet::from([
+        vec![Symbol::Nonterminal('A'), Symbol::Nonterminal('A')],
+        vec![Symbol::Terminal('b')],
+        vec![Symbol::Epsilon],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
+
+pub fn unit_chain_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['a', 'b']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['S', 'A', 'B', 'C']);
+    let start: Nonterminal = 'S';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('S', HashSet::from([
+        vec![Symbol::Nonterminal('A')],
+        vec![Symbol::Terminal('a'), Symbol::Nonterminal('S')],
+    ]));
+    rules.insert('A', HashSet::from([
+        vec![Symbol::Nonterminal('B')],
+    ]));
+    rules.insert('B', HashSet::from([
+        vec![Symbol::Nonterminal('C')],
+        vec![Symbol::Terminal('a')],
+    ]));
+    rules.insert('C', HashSet::from([
+        vec![Symbol::Terminal('b'), Symbol::Nonterminal('C')],
+        vec![Symbol::Terminal('b')],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
+
+pub fn indirectly_nullable_start_grammar() -> Grammar {
+    let terminals: HashSet<Terminal> = HashSet::from(['a']);
+    let nonterminals: HashSet<Nonterminal> = HashSet::from(['S', 'A']);
+    let start: Nonterminal = 'S';
+    let mut rules: HashMap<Nonterminal, HashSet<Word>> = HashMap::new();
+    rules.insert('S', HashSet::from([
+        vec![Symbol::Nonterminal('A')],
+    ]));
+    rules.insert('A', HashSet::from([
+        vec![Symbol::Terminal('a')],
+        vec![Symbol::Epsilon],
+    ]));
+    Grammar::new(terminals, nonterminals, start, rules)
+}
@@ -1,9 +1,13 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, BTreeSet};
+use std::sync::Arc;
 
 use relational_parsing;
-use crate::relational_parsing::{Regex, 
-    Memoize,
-    Symbol, Nonterminal};
+use crate::relational_parsing::{Regex, FiniteStateAutomaton,
+    Memoize, RenderOptions, RuleLabels, FsaVisitor, State, Rule, Rules, ExampleFailure,
+    Symbol, Nonterminal, Terminal, Word, Actions, GrammarParseError,
+    CompletedParses, Grammar, BuildError, ParseError, FsaStats, FsaStatsDelta, RenameError,
+    BuildConfig, Coverage, DotError, InlineError, LazyFsa, CompiledFsa, OverwritePolicy,
+    DeterministicFsa};
 
 mod common;
 
@@ -114,6 +118,9 @@ fn print_grammar_fsa() {
     //let reg = Regex::new(&grammar.terminals, &grammar.rules);
     //reg.print_with_rules();
     println!("{}", grammar.finite_state_automaton);
+    let mut keys: Vec<(Nonterminal, Terminal)> = relational_parsing::Regex::new(&grammar.terminals, &grammar.rules).regex.into_keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec![('S', 'a'), ('S', 'b')]);
 }
 
 #[test]
@@ -974,4 +981,2129 @@ fn indirect_right_recursive_grammar_parse_test() {
     assert!(relational_parsing::find_parses(vec!['b'], &grammar, &mut memoize).is_err());
     assert!(relational_parsing::find_parses(vec!['a', 'b', 'b'], &grammar, &mut memoize).is_err());
     assert!(relational_parsing::find_parses(vec!['a', 'a'], &grammar, &mut memoize).is_err());
-}
\ No newline at end of file
+}
+#[test]
+fn shortest_accepted_test() {
+    assert_eq!(common::basic_relational_parsing_example_grammar().finite_state_automaton.shortest_accepted(), Some(vec!['a']));
+    assert_eq!(common::e_rule_relational_parsing_example_grammar().finite_state_automaton.shortest_accepted(), Some(vec![]));
+    assert_eq!(common::three_rule_grammar().finite_state_automaton.shortest_accepted(), Some(vec!['a', 'a', 'a']));
+    assert_eq!(common::even_a_middle_b_grammar().finite_state_automaton.shortest_accepted(), Some(vec!['b']));
+    assert_eq!(common::no_base_case_grammar().finite_state_automaton.shortest_accepted(), None);
+}
+
+#[test]
+fn shortest_derivation_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::three_rule_grammar(), common::even_a_middle_b_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let (input, rules) = fsa.shortest_derivation().unwrap();
+        assert_eq!(input.len(), fsa.shortest_accepted().unwrap().len());
+        assert!(fsa.recognize(&input));
+        assert!(relational_parsing::verify_derivation(grammar.start, &rules, &input), "{:?} {:?}", input, rules);
+    }
+    assert_eq!(common::e_rule_relational_parsing_example_grammar().finite_state_automaton.shortest_derivation(), Some((vec![], vec![])));
+    assert_eq!(common::no_base_case_grammar().finite_state_automaton.shortest_derivation(), None);
+}
+
+#[test]
+fn build_atomic_test() {
+    let grammar = common::indirect_left_recursive_grammar();
+    let (atomic_entries, fsa) = FiniteStateAutomaton::build_atomic(&grammar.terminals, 'B', &grammar.rules);
+
+    assert_eq!(atomic_entries.keys().cloned().collect::<HashSet<Terminal>>(), HashSet::from(['a', 'b']));
+    assert!(fsa.get_atomic(Symbol::Nonterminal('A'), 'a').is_none());
+    for (terminal, (state, rules_set)) in &atomic_entries {
+        assert_eq!(fsa.get_atomic(Symbol::Nonterminal('B'), *terminal), Some((state, rules_set, fsa.is_accepting(state))));
+    }
+    assert_eq!(fsa.shortest_accepted(), Some(vec!['b']));
+}
+
+#[test]
+fn render_options_test() {
+    let grammar = common::e_rule_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    assert_eq!(format!("{}", fsa), format!("{}", fsa.display_with(&RenderOptions::default())));
+    assert!(format!("{}", fsa).contains("[S -> "));
+
+    let counted = format!("{}", fsa.display_with(&RenderOptions{rule_labels: RuleLabels::Count, ..Default::default()}));
+    assert!(!counted.contains("[S -> "));
+    assert!(counted.contains(" rules"));
+
+    let truncated = format!("{}", fsa.display_with(&RenderOptions{rule_labels: RuleLabels::Truncate(0), ..Default::default()}));
+    assert!(!truncated.contains("[S -> "));
+    assert!(truncated.contains("..."));
+
+    fsa.to_dot_with("e-rule relational counted", &RenderOptions{rule_labels: RuleLabels::Count, ..Default::default()}).expect("error");
+}
+
+#[test]
+fn states_equivalent_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let (start, _) = fsa.get_start();
+    let (epsilon, _, _) = fsa.get_atomic(Symbol::Terminal('a'), 'a').unwrap();
+
+    assert!(fsa.states_equivalent(&start, &start, 4));
+    assert!(fsa.states_equivalent(epsilon, epsilon, 4));
+    assert!(!fsa.states_equivalent(&start, epsilon, 4));
+    assert!(!fsa.states_equivalent(&start, epsilon, 0));
+}
+
+#[derive(Default)]
+struct CollectingVisitor {
+    states: Vec<State>,
+    transitions: Vec<(State, Symbol, State)>,
+    atomics: Vec<(Symbol, Terminal)>,
+}
+
+impl FsaVisitor for CollectingVisitor {
+    fn visit_state(&mut self, state: &State, _accepting: bool) {
+        self.states.push(*state);
+    }
+
+    fn visit_transition(&mut self, source: &State, symbol: &Symbol, dest: &State, _rules: &Rules) {
+        self.transitions.push((*source, *symbol, *dest));
+    }
+
+    fn visit_atomic(&mut self, symbol: &Symbol, terminal: &Terminal, _state: &State, _rules_set: &HashSet<Rules>) {
+        self.atomics.push((*symbol, *terminal));
+    }
+}
+
+#[test]
+fn visitor_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let mut visitor: CollectingVisitor = CollectingVisitor::default();
+    grammar.finite_state_automaton.accept(&mut visitor);
+
+    let mut sorted_states = visitor.states.clone();
+    sorted_states.sort();
+    assert_eq!(visitor.states, sorted_states);
+    assert!(visitor.states.contains(&grammar.finite_state_automaton.get_start().0));
+
+    let mut sorted_transitions = visitor.transitions.clone();
+    sorted_transitions.sort();
+    assert_eq!(visitor.transitions, sorted_transitions);
+    assert!(visitor.transitions.contains(&(0, Symbol::Nonterminal('S'), 1)));
+
+    assert_eq!(visitor.atomics, vec![
+        (Symbol::Terminal('a'), 'a'), (Symbol::Terminal('b'), 'b'), (Symbol::Terminal('c'), 'c'), (Symbol::Nonterminal('S'), 'a')
+    ]);
+}
+
+#[test]
+fn unused_terminals_test() {
+    let grammar = common::even_a_middle_b_grammar();
+    let terminals: HashSet<Terminal> = HashSet::from(['a', 'b', 'z']);
+
+    assert_eq!(relational_parsing::unused_terminals(&terminals, &grammar.rules), HashSet::from(['z']));
+    assert_eq!(relational_parsing::unused_terminals(&grammar.terminals, &grammar.rules), HashSet::new());
+
+    let fsa = FiniteStateAutomaton::build_fsa(&terminals, grammar.start, &grammar.rules);
+    assert_eq!(fsa.get_atomic(Symbol::Terminal('z'), 'z'), Some((&1, &HashSet::new(), true)));
+    assert!(fsa.alphabet().contains(&'z'));
+
+    let skipped = FiniteStateAutomaton::build_fsa_with(&terminals, grammar.start, &grammar.rules, &BuildConfig{skip_unused_terminals: true, ..BuildConfig::default()});
+    assert!(skipped.get_atomic(Symbol::Terminal('z'), 'z').is_none());
+    assert_eq!(skipped.get_atomic(Symbol::Terminal('a'), 'a'), fsa.get_atomic(Symbol::Terminal('a'), 'a'));
+    for input in [vec!['a', 'b', 'a'], vec!['z'], vec!['a', 'z', 'a']] {
+        assert_eq!(skipped.recognize(&input), fsa.recognize(&input), "{:?}", input);
+    }
+}
+
+#[test]
+fn check_examples_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    assert_eq!(fsa.check_examples(&[vec!['a'], vec!['a', 'b', 'a', 'c']], &[vec![], vec!['b', 'c']]), vec![]);
+    assert_eq!(fsa.check_examples(&[vec!['a'], vec!['a', 'b']], &[vec!['a', 'a']]), vec![
+        ExampleFailure{input: vec!['a', 'b'], expected: true, actual: false},
+        ExampleFailure{input: vec!['a', 'a'], expected: false, actual: true},
+    ]);
+}
+
+#[test]
+fn recognize_ci_test() {
+    let grammar = common::keyword_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    assert!(fsa.recognize(&['i', 'f']));
+    assert!(!fsa.recognize(&['I', 'F']));
+    assert!(fsa.recognize_ci(&['I', 'F']));
+    assert!(fsa.recognize_ci(&['i', 'N']));
+    assert!(fsa.recognize_ci(&['I', 'n']));
+    assert!(!fsa.recognize_ci(&['I', 'X']));
+    assert!(!fsa.recognize_ci(&['F', 'I']));
+}
+
+#[test]
+fn make_total_test() {
+    let mut grammar = common::basic_relational_parsing_example_grammar();
+    let inputs: Vec<Vec<Terminal>> = vec![vec![], vec!['a'], vec!['b'], vec!['a', 'b', 'a', 'c'], vec!['a', 'b', 'a'], vec!['a', 'c', 'a']];
+    let before: Vec<bool> = inputs.iter().map(|input| grammar.finite_state_automaton.recognize(input)).collect();
+
+    let trap = grammar.finite_state_automaton.make_total();
+    let fsa = &grammar.finite_state_automaton;
+    assert_eq!(inputs.iter().map(|input| fsa.recognize(input)).collect::<Vec<bool>>(), before);
+    assert!(!fsa.is_accepting(&trap));
+    for terminal in ['a', 'b', 'c'] {
+        assert_eq!(fsa.simulate(&trap, Symbol::Terminal(terminal)), Some(HashSet::from([(&trap, &Vec::new(), false)])));
+        assert!(fsa.simulate(&fsa.get_start().0, Symbol::Terminal(terminal)).is_some());
+    }
+
+    assert_eq!(grammar.finite_state_automaton.make_total(), trap);
+}
+
+fn all_strings(alphabet: &[Terminal], max_len: usize) -> Vec<Vec<Terminal>> {
+    let mut res: Vec<Vec<Terminal>> = vec![vec![]];
+    let mut curr: Vec<Vec<Terminal>> = vec![vec![]];
+    for _ in 0..max_len {
+        let mut next: Vec<Vec<Terminal>> = Vec::new();
+        for word in &curr {
+            for terminal in alphabet {
+                next.push([&word[..], &[*terminal]].concat());
+            }
+        }
+        res.extend(next.clone());
+        curr = next;
+    }
+    res
+}
+
+// Asserts that both recognize the same strings over the alphabet up to the given length.
+fn assert_same_language(a: impl Fn(&[Terminal]) -> bool, b: impl Fn(&[Terminal]) -> bool, alphabet: &[Terminal], max_len: usize) {
+    for input in all_strings(alphabet, max_len) {
+        assert_eq!(a(&input), b(&input), "{:?}", input);
+    }
+}
+
+#[test]
+fn left_factor_test() {
+    let grammar = common::common_prefix_grammar();
+    let factored = relational_parsing::left_factor(&grammar.rules);
+
+    assert_eq!(factored.len(), 5);
+    for words in factored.values() {
+        let first_symbols: HashSet<Symbol> = words.iter().map(|word| word[0]).collect();
+        assert_eq!(first_symbols.len(), words.len());
+    }
+
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &factored);
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &['a', 'b', 'c'], 5);
+}
+
+#[test]
+fn nullable_leading_nonterminal_test() {
+    // the nulled H must not cut the rules of H short: only the empty word, 'a' and 'b' 'c' may precede the 'z'
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : H 'z' ; H : 'a' | 'b' 'c' | ;").unwrap();
+    let expected: HashSet<Vec<Terminal>> = HashSet::from([vec!['z'], vec!['a', 'z'], vec!['b', 'c', 'z']]);
+    for input in all_strings(&['a', 'b', 'c', 'z'], 4) {
+        assert_eq!(grammar.finite_state_automaton.recognize(&input), expected.contains(&input), "{:?}", input);
+    }
+}
+
+#[test]
+fn remove_immediate_left_recursion_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("e : e '+' t | t ; t : t '*' 'a' | 'a' ;").unwrap();
+    let removed = relational_parsing::remove_immediate_left_recursion(&grammar.rules);
+
+    assert_eq!(relational_parsing::grammar_to_string(&removed, grammar.start), "e : t A ;\nA : | '+' t A ;\nB : | '*' 'a' B ;\nt : 'a' B ;\n");
+    for (nonterminal, words) in &removed {
+        assert!(words.iter().all(|word| word[0] != Symbol::Nonterminal(*nonterminal)));
+    }
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &removed);
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &['a', '+', '*'], 5);
+
+    let grammar = common::direct_left_recursive_grammar();
+    let removed = relational_parsing::remove_immediate_left_recursion(&grammar.rules);
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &removed);
+    let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 5);
+    assert_eq!(relational_parsing::remove_immediate_left_recursion(&common::no_base_case_grammar().rules), common::no_base_case_grammar().rules);
+}
+
+#[test]
+fn parse_with_actions_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let mut actions: Actions<Vec<String>> = HashMap::new();
+    for (nonterminal, word) in [('S', "SbSc"), ('S', "Sa"), ('S', "a")] {
+        actions.insert((nonterminal, build_rule((nonterminal, word))), Box::new(move |acc: &mut Vec<String>| acc.push(format!("{} -> {}", nonterminal, word))));
+    }
+
+    let mut acc: Vec<String> = Vec::new();
+    assert!(grammar.parse_with_actions(&['a', 'b', 'a', 'c'], &actions, &mut acc));
+    assert_eq!(acc, vec!["S -> SbSc", "S -> a", "S -> a"]);
+
+    let mut acc: Vec<String> = Vec::new();
+    assert!(grammar.parse_with_actions(&['a', 'a'], &actions, &mut acc));
+    assert_eq!(acc, vec!["S -> Sa", "S -> a"]);
+
+    let mut acc: Vec<String> = Vec::new();
+    assert!(!grammar.parse_with_actions(&['b'], &actions, &mut acc));
+    assert!(acc.is_empty());
+}
+
+#[test]
+fn states_after_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    assert!(fsa.states_after(&[]).contains(&fsa.get_start().0));
+    assert!(fsa.states_after(&['a']).iter().any(|state| fsa.is_accepting(state)));
+    assert!(!fsa.states_after(&['a', 'b']).is_empty());
+    assert!(!fsa.states_after(&['a', 'b']).iter().any(|state| fsa.is_accepting(state)));
+    assert!(fsa.states_after(&['b']).is_empty());
+    assert!(fsa.states_after(&['a', 'c', 'a']).is_empty());
+}
+
+#[test]
+fn to_recognizer_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::indirect_left_recursive_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let recognizer = fsa.to_recognizer();
+        let alphabet: Vec<Terminal> = fsa.alphabet().into_iter().collect();
+        assert_same_language(|input| recognizer.recognize(input), |input| fsa.recognize(input), &alphabet, 5);
+    }
+}
+
+#[test]
+fn freeze_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::three_rule_grammar(), common::even_a_middle_b_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar()] {
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        let inputs: Vec<Vec<Terminal>> = all_strings(&alphabet, 5);
+        let expected: Vec<bool> = inputs.iter().map(|input| grammar.finite_state_automaton.recognize(input)).collect();
+        let compiled: Arc<CompiledFsa> = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &grammar.rules).freeze();
+        assert!(compiled.rules()[0].is_empty());
+
+        for (input, accepted) in inputs.iter().zip(&expected) {
+            assert_eq!(compiled.recognize(input), *accepted, "{:?}", input);
+            let parse: Option<Vec<usize>> = compiled.parse(input);
+            assert_eq!(parse.is_some(), *accepted, "{:?}", input);
+            if let Some(indices) = parse {
+                let rules: Vec<Rules> = indices.iter().map(|index| compiled.rules()[*index].clone()).collect();
+                // the automaton collects the rules of a derivation in another order than the parser, see
+                // find_parses_leftmost_order_test, and the parser rejects some inputs, see parser_rejects_epsilon_end_test
+                let mut memoize: Memoize = Memoize::new();
+                if let Ok(derivations) = relational_parsing::find_parses(input.clone(), &grammar, &mut memoize) {
+                    let histogram: HashMap<Rule, usize> = relational_parsing::rule_histogram(&rules);
+                    assert!(derivations.into_iter().any(|derivation| relational_parsing::rule_histogram(&[derivation]) == histogram), "{:?} {:?}", input, rules);
+                }
+            }
+        }
+
+        let handles: Vec<std::thread::JoinHandle<Vec<bool>>> = (0..2).map(|_| {
+            let shared: Arc<CompiledFsa> = Arc::clone(&compiled);
+            let inputs: Vec<Vec<Terminal>> = inputs.clone();
+            std::thread::spawn(move || inputs.iter().map(|input| shared.recognize(input)).collect())
+        }).collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+}
+
+// Compares the recognition time of the compiled and the hash-based automaton, run with `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn freeze_benchmark_test() {
+    let grammar = common::difficult_bottom_up_grammar();
+    let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+    let inputs: Vec<Vec<Terminal>> = all_strings(&alphabet, 10);
+
+    let start = std::time::Instant::now();
+    let expected: Vec<bool> = inputs.iter().map(|input| grammar.finite_state_automaton.recognize(input)).collect();
+    let hash_based = start.elapsed();
+
+    let compiled: Arc<CompiledFsa> = grammar.finite_state_automaton.freeze();
+    let start = std::time::Instant::now();
+    let accepted: Vec<bool> = inputs.iter().map(|input| compiled.recognize(input)).collect();
+    let frozen = start.elapsed();
+
+    assert_eq!(accepted, expected);
+    println!("{} inputs: hash-based {:?}, compiled {:?}", inputs.len(), hash_based, frozen);
+}
+
+#[test]
+fn restrict_by_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::indirect_left_recursive_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let even = fsa.restrict_by(0, &HashSet::from([0]), |state: &State, _| Some(1 - state));
+        let alphabet: Vec<Terminal> = fsa.alphabet().into_iter().collect();
+        for input in all_strings(&alphabet, 6) {
+            assert_eq!(even.recognize(&input), input.len() % 2 == 0 && fsa.recognize(&input));
+        }
+    }
+}
+
+#[test]
+fn difference_test() {
+    for grammar in [common::direct_left_recursive_grammar(), common::indirect_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::keyword_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        assert!(fsa.difference(fsa).unwrap().is_empty_language());
+    }
+
+    let a = relational_parsing::parse_grammar_yacc_like("S : S 'a' | 'b' ;").unwrap();
+    let b = relational_parsing::parse_grammar_yacc_like("S : S 'a' | 'b' | 'c' ;").unwrap();
+    assert!(a.finite_state_automaton.difference(&b.finite_state_automaton).unwrap().is_empty_language());
+    let extension = b.finite_state_automaton.difference(&a.finite_state_automaton).unwrap();
+    for input in all_strings(&['a', 'b', 'c'], 5) {
+        let expected: bool = b.finite_state_automaton.recognize(&input) && !a.finite_state_automaton.recognize(&input);
+        assert_eq!(extension.recognize(&input), expected, "{:?}", input);
+    }
+
+    // the configurations of self-embedding rules grow with the input
+    let basic = common::basic_relational_parsing_example_grammar();
+    assert!(matches!(basic.finite_state_automaton.difference(&basic.finite_state_automaton), Err(BuildError::ConfigurationDepthExceeded(_))));
+
+    // b a* written right recursively is the same language, as the ends of the recursion are collapsed
+    let right = relational_parsing::parse_grammar_yacc_like("S : 'b' A ; A : 'a' A | ;").unwrap();
+    assert!(a.finite_state_automaton.difference(&right.finite_state_automaton).unwrap().is_empty_language());
+    assert!(right.finite_state_automaton.difference(&a.finite_state_automaton).unwrap().is_empty_language());
+    let right_extension = relational_parsing::parse_grammar_yacc_like("S : 'b' A | 'c' ; A : 'a' A | ;").unwrap();
+    let extension = right_extension.finite_state_automaton.difference(&right.finite_state_automaton).unwrap();
+    for input in all_strings(&['a', 'b', 'c'], 5) {
+        assert_eq!(extension.recognize(&input), input == vec!['c'], "{:?}", input);
+    }
+    for grammar in [common::direct_right_recursive_grammar(), common::indirect_right_recursive_grammar()] {
+        assert!(grammar.finite_state_automaton.difference(&grammar.finite_state_automaton).unwrap().is_empty_language());
+    }
+}
+
+#[test]
+fn epsilon_cycle_test() {
+    let grammar = common::nullable_recursive_grammar();
+    assert!(grammar.finite_state_automaton.has_epsilon_cycle());
+    assert!(!common::basic_relational_parsing_example_grammar().finite_state_automaton.has_epsilon_cycle());
+
+    // Used to loop forever in e_sim, the language is ab*.
+    for input in all_strings(&['a', 'b'], 4) {
+        let expected: bool = input.first() == Some(&'a') && input[1..].iter().all(|terminal| *terminal == 'b');
+        let mut memoize: Memoize = Memoize::new();
+        assert_eq!(relational_parsing::g_accepts_string(input.clone(), &grammar, &mut memoize), expected);
+        assert_eq!(grammar.finite_state_automaton.recognize(&input), expected);
+    }
+}
+
+#[test]
+fn parse_grammar_yacc_like_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("
+        %token NUM
+        %%
+        expr : expr '+' term { $$ = $1 + $3; }
+             | term
+             ;
+        /* terms are products
+           of factors */
+        term : term '*' factor | factor ; // no division
+        factor : '(' expr ')' | 'n' ;
+    ").unwrap();
+    assert_eq!(grammar.nonterminals.len(), 3);
+    assert_eq!(grammar.terminals, HashSet::from(['+', '*', '(', ')', 'n']));
+    let mut memoize: Memoize = Memoize::new();
+    assert!(relational_parsing::g_accepts_string("n+n*(n+n)".chars().collect(), &grammar, &mut memoize));
+    assert!(!relational_parsing::g_accepts_string("n+*n".chars().collect(), &grammar, &mut memoize));
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' S 'b' | ;").unwrap();
+    assert_eq!(grammar.start, 'S');
+    assert_eq!(grammar.rules[&'S'], HashSet::from([build_rule(('S', "aSb")).1, build_rule(('S', "e")).1]));
+
+    assert_eq!(relational_parsing::parse_grammar_yacc_like("S : 'a'\n  | T ;").map(|_| ()),
+        Err(GrammarParseError{line: 2, message: String::from("undefined nonterminal 'T'")}));
+    assert_eq!(relational_parsing::parse_grammar_yacc_like("S : 'a' ;\nT 'b' ;").map(|_| ()).unwrap_err().line, 2);
+    assert_eq!(relational_parsing::parse_grammar_yacc_like("S : 'a'\n\nT : 'b' ;").map(|_| ()).unwrap_err().line, 3);
+    assert_eq!(relational_parsing::parse_grammar_yacc_like("S : 'a' { x ;\n\n").map(|_| ()).unwrap_err().line, 1);
+    assert!(relational_parsing::parse_grammar_yacc_like("").is_err());
+}
+
+#[test]
+fn parse_all_capped_test() {
+    let grammar = common::test_grammar();
+    let mut memoize: Memoize = Memoize::new();
+    let all: CompletedParses = relational_parsing::find_parses(vec!['a', 'a', 'a'], &grammar, &mut memoize).unwrap();
+    assert!(all.len() > 1);
+
+    let (parses, more) = grammar.parse_all_capped(&['a', 'a', 'a'], 1);
+    assert_eq!(parses.len(), 1);
+    assert!(more);
+    assert!(parses.iter().all(|parse| all.contains(parse)));
+
+    let (parses, more) = grammar.parse_all_capped(&['a', 'a', 'a'], all.len());
+    assert_eq!(parses.into_iter().collect::<CompletedParses>(), all);
+    assert!(!more);
+
+    assert_eq!(grammar.parse_all_capped(&['b'], 2), (Vec::new(), false));
+}
+
+#[test]
+fn adjacency_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let adjacency = fsa.adjacency();
+
+    assert_eq!(adjacency[&fsa.get_start().0], vec![(Symbol::Nonterminal('S'), 1)]);
+    for (state, edges) in &adjacency {
+        let mut sorted: Vec<(Symbol, State)> = edges.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(*edges, sorted);
+        for (symbol, dest) in edges {
+            assert!(fsa.simulate(state, *symbol).unwrap().iter().any(|(end_state, _, _)| *end_state == dest));
+        }
+    }
+}
+
+#[cfg(feature = "petgraph")]
+#[test]
+fn to_petgraph_test() {
+    use petgraph::visit::EdgeRef;
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let adjacency = fsa.adjacency();
+    let (graph, nodes) = fsa.to_petgraph();
+
+    assert_eq!(graph.node_count(), adjacency.len());
+    assert_eq!(graph.edge_count(), adjacency.values().map(|edges| edges.len()).sum::<usize>());
+    for (state, edges) in &adjacency {
+        assert_eq!(graph[nodes[state]], *state);
+        let mut graph_edges: Vec<(Symbol, State)> = graph.edges(nodes[state]).map(|edge| (*edge.weight(), graph[edge.target()])).collect();
+        graph_edges.sort();
+        assert_eq!(graph_edges, *edges);
+    }
+
+    // the start state reaches the accepting state 1 through the transition on the start nonterminal
+    let start: petgraph::graph::NodeIndex = nodes[&fsa.get_start().0];
+    assert!(petgraph::algo::has_path_connecting(&graph, start, nodes[&1], None));
+}
+
+#[test]
+fn add_production_test() {
+    let additions: Vec<(Grammar, Nonterminal, &str)> = vec![
+        (common::basic_relational_parsing_example_grammar(), 'S', "Sbb"),
+        (common::basic_relational_parsing_example_grammar(), 'S', "cS"),
+        (common::indirect_left_recursive_grammar(), 'B', "Bd"),
+        (common::even_more_indirect_left_recursive_grammar(), 'B', "d"),
+        (common::e_rule_relational_parsing_example_grammar(), 'S', "e"),
+        (common::common_prefix_grammar(), 'B', "cB"),
+        // S becomes nullable through A, which changes the rules using S as well
+        (relational_parsing::parse_grammar_yacc_like("S : 'b' ; A : 'a' | ;").unwrap(), 'S', "A"),
+        (relational_parsing::parse_grammar_yacc_like("X : 'b' S 'c' ; S : 'b' ; A : 'a' | ;").unwrap(), 'S', "A"),
+    ];
+    for (mut grammar, nt, word) in additions {
+        grammar.add_production(nt, build_rule((nt, word)).1);
+        let rebuilt: Grammar = Grammar::new(grammar.terminals.clone(), grammar.nonterminals.clone(), grammar.start, grammar.rules.clone());
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| grammar.finite_state_automaton.recognize(input), |input| rebuilt.finite_state_automaton.recognize(input), &alphabet, 5);
+    }
+}
+
+#[test]
+fn rebuild_atomic_with_test() {
+    // both grammars have an atomic language of A on 'a', which conflict
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'x' A ; A : 'a' 'c' ;").unwrap();
+    let changed = relational_parsing::parse_grammar_yacc_like("S : 'x' A ; A : 'a' 'd' ;").unwrap();
+    let terminals: HashSet<Terminal> = HashSet::from(['x', 'a', 'c', 'd']);
+    let nts: HashSet<Nonterminal> = HashSet::from(['A']);
+    let build = || FiniteStateAutomaton::build_fsa(&terminals, grammar.start, &grammar.rules);
+    let accepted = |fsa: &FiniteStateAutomaton| -> Vec<bool> { [['x', 'a', 'c'], ['x', 'a', 'd']].iter().map(|input| fsa.recognize(input)).collect() };
+
+    let mut replaced = build();
+    assert_eq!(replaced.rebuild_atomic_with(&terminals, changed.start, &changed.rules, &nts, OverwritePolicy::Replace), Ok(()));
+    assert_eq!(accepted(&replaced), vec![false, true]);
+    let mut rebuilt = build();
+    rebuilt.rebuild_atomic(&terminals, changed.start, &changed.rules, &nts);
+    assert_eq!(replaced.stats(), rebuilt.stats());
+
+    let mut merged = build();
+    assert_eq!(merged.rebuild_atomic_with(&terminals, changed.start, &changed.rules, &nts, OverwritePolicy::Merge), Ok(()));
+    assert_eq!(accepted(&merged), vec![true, true]);
+    let merged_state: State = *merged.get_atomic(Symbol::Nonterminal('A'), 'a').unwrap().0;
+    assert_eq!(merged.simulate(&merged_state, Symbol::Epsilon).unwrap().len(), 2);
+
+    let mut rejected = build();
+    assert_eq!(rejected.rebuild_atomic_with(&terminals, changed.start, &changed.rules, &nts, OverwritePolicy::Error), Err(BuildError::AtomicConflict(Symbol::Nonterminal('A'), 'a')));
+    assert_eq!(accepted(&rejected), vec![true, false]);
+    assert_eq!(rejected.stats(), build().stats());
+
+    // without a conflict every policy adds the new atomic language
+    let other = relational_parsing::parse_grammar_yacc_like("S : 'x' A ; A : 'c' 'd' ;").unwrap();
+    for policy in [OverwritePolicy::Replace, OverwritePolicy::Merge, OverwritePolicy::Error] {
+        let mut fsa = build();
+        assert_eq!(fsa.rebuild_atomic_with(&terminals, other.start, &other.rules, &nts, policy), Ok(()));
+        assert!(fsa.recognize(&['x', 'c', 'd']));
+        assert_eq!(fsa.recognize(&['x', 'a', 'c']), policy != OverwritePolicy::Replace);
+    }
+}
+
+#[test]
+fn sorted_display_test() {
+    let displayed = format!("{}", common::basic_relational_parsing_example_grammar().finite_state_automaton);
+    assert_eq!(displayed, format!("{}", common::basic_relational_parsing_example_grammar().finite_state_automaton));
+    assert!(displayed.starts_with("States: 0 1 "));
+    assert!(displayed.contains("Transitions:\n0:\n  --S--> 1\n"));
+}
+
+#[test]
+fn to_regex_string_test() {
+    assert_eq!(common::keyword_grammar().finite_state_automaton.to_regex_string(), Some("i(f|n)".to_string()));
+    assert_eq!(common::direct_left_recursive_grammar().finite_state_automaton.to_regex_string(), Some("(aa*)?".to_string()));
+    // A -> aA | a is solved by Arden's lemma
+    assert_eq!(common::direct_right_recursive_grammar().finite_state_automaton.to_regex_string(), Some("a*a".to_string()));
+    assert_eq!(common::common_prefix_grammar().finite_state_automaton.to_regex_string(), Some("c|abc|abb*b|ab?".to_string()));
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : '(' ')' | '*' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.to_regex_string(), Some("\\*|\\(\\)".to_string()));
+
+    // recursion through another nonterminal and self-embedding are not solved
+    assert_eq!(common::indirect_right_recursive_grammar().finite_state_automaton.to_regex_string(), None);
+    assert_eq!(common::basic_relational_parsing_example_grammar().finite_state_automaton.to_regex_string(), None);
+}
+
+#[test]
+fn build_fsa_regular_test() {
+    let grammar = common::odd_number_of_a_grammar();
+    assert_eq!(relational_parsing::self_embedding_nonterminals(&grammar.rules), BTreeSet::from(['S']));
+    assert_eq!(FiniteStateAutomaton::build_fsa_regular(&grammar.terminals, grammar.start, &grammar.rules).map(|_| ()), Err(BuildError::NonRegular('S')));
+
+    let mut rules: HashMap<Nonterminal, HashSet<Vec<Symbol>>> = HashMap::new();
+    rules.insert('S', HashSet::from([build_rule(('S', "aSb")).1, build_rule(('S', "e")).1]));
+    assert_eq!(FiniteStateAutomaton::build_fsa_regular(&HashSet::from(['a', 'b']), 'S', &rules).map(|_| ()), Err(BuildError::NonRegular('S')));
+
+    let grammar = common::basic_relational_parsing_example_grammar();
+    assert_eq!(relational_parsing::self_embedding_nonterminals(&grammar.rules), BTreeSet::from(['S']));
+
+    for grammar in [common::direct_left_recursive_grammar(), common::indirect_right_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::common_prefix_grammar()] {
+        assert!(relational_parsing::self_embedding_nonterminals(&grammar.rules).is_empty());
+        let fsa = FiniteStateAutomaton::build_fsa_regular(&grammar.terminals, grammar.start, &grammar.rules).unwrap();
+        assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &['a', 'b', 'c'], 4);
+    }
+}
+
+#[test]
+fn atomic_rule_options_test() {
+    let grammar = common::extra_e_rule_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    assert_eq!(fsa.atomic_rule_options(Symbol::Nonterminal('S'), 'a'), vec![build_rules(vec![('S', "a")]), build_rules(vec![('S', "Sa"), ('S', "e")])]);
+    assert_eq!(fsa.atomic_rule_options(Symbol::Nonterminal('S'), 'b'), Vec::<Rules>::new());
+    assert_eq!(fsa.atomic_rule_options(Symbol::Nonterminal('S'), 'd'), Vec::<Rules>::new());
+}
+
+#[test]
+fn recognize_batch_test() {
+    let grammar = common::e_rule_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let inputs: Vec<Vec<Terminal>> = all_strings(&['a', 'b', 'c'], 5);
+
+    assert_eq!(fsa.recognize_batch(&inputs), inputs.iter().map(|input| fsa.recognize(input)).collect::<Vec<bool>>());
+    assert!(fsa.recognize_batch(&[]).is_empty());
+
+    for grammar in [common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::indirect_left_recursive_grammar(), common::nullable_recursive_grammar(), common::indirectly_nullable_start_grammar()] {
+        let compiled: Arc<CompiledFsa> = grammar.finite_state_automaton.freeze();
+        let inputs: Vec<Vec<Terminal>> = all_strings(&grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 5);
+        assert_eq!(compiled.recognize_batch(&inputs), inputs.iter().map(|input| compiled.recognize(input)).collect::<Vec<bool>>());
+    }
+    assert!(grammar.finite_state_automaton.freeze().recognize_batch(&[]).is_empty());
+}
+
+// Compares the recognition time of recognize_batch on the compiled and the hash-based automaton, run with
+// `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn recognize_batch_benchmark_test() {
+    let grammar = common::difficult_bottom_up_grammar();
+    let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+    let inputs: Vec<Vec<Terminal>> = all_strings(&alphabet, 10);
+
+    let start = std::time::Instant::now();
+    let expected: Vec<bool> = grammar.finite_state_automaton.recognize_batch(&inputs);
+    let hash_based = start.elapsed();
+
+    let compiled: Arc<CompiledFsa> = grammar.finite_state_automaton.freeze();
+    let start = std::time::Instant::now();
+    let accepted: Vec<bool> = compiled.recognize_batch(&inputs);
+    let frozen = start.elapsed();
+
+    assert_eq!(accepted, expected);
+    println!("{} inputs: hash-based {:?}, compiled {:?}", inputs.len(), hash_based, frozen);
+}
+
+#[test]
+fn dedup_atomic_test() {
+    let mut grammar = common::common_prefix_grammar();
+    assert_eq!(grammar.finite_state_automaton.dedup_atomic(), 0);
+
+    // the product with a two state automaton accepting everything duplicates states
+    let mut fsa = grammar.finite_state_automaton.restrict_by(0, &HashSet::from([0, 1]), |state: &State, _| Some(1 - state));
+    let mut before = CollectingVisitor{states: Vec::new(), transitions: Vec::new(), atomics: Vec::new()};
+    fsa.accept(&mut before);
+
+    assert!(fsa.dedup_atomic() > 0);
+    let mut after = CollectingVisitor{states: Vec::new(), transitions: Vec::new(), atomics: Vec::new()};
+    fsa.accept(&mut after);
+    assert_eq!(after.atomics, before.atomics);
+    assert!(after.states.len() < before.states.len());
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &['a', 'b', 'c'], 5);
+    assert_eq!(fsa.dedup_atomic(), 0);
+}
+
+#[test]
+fn depth_guard_test() {
+    // every a of the first half nests the languages one level deeper
+    let grammar = common::odd_number_of_a_grammar();
+    let input: Vec<Terminal> = vec!['a'; 41];
+
+    let mut memoize: Memoize = Memoize::new();
+    assert_eq!(relational_parsing::parse_with_max_depth(input.clone(), &grammar, &mut memoize, 10).map(|_| ()), Err(ParseError::DepthExceeded(10)));
+    let mut memoize: Memoize = Memoize::new();
+    assert!(relational_parsing::parse_with_max_depth(input.clone(), &grammar, &mut memoize, 100).unwrap().is_final());
+    let mut memoize: Memoize = Memoize::new();
+    assert!(relational_parsing::g_accepts_string(input, &grammar, &mut memoize));
+
+    let mut memoize: Memoize = Memoize::new();
+    assert_eq!(relational_parsing::parse(vec!['b'], &grammar, &mut memoize).map(|_| ()), Err(ParseError::Rejected));
+}
+
+#[test]
+fn nonterminals_test() {
+    assert_eq!(common::even_more_indirect_left_recursive_grammar().finite_state_automaton.nonterminals(), BTreeSet::from(['A', 'B', 'C']));
+    assert_eq!(common::common_prefix_grammar().finite_state_automaton.nonterminals(), BTreeSet::from(['S', 'B']));
+    assert_eq!(common::keyword_grammar().finite_state_automaton.nonterminals(), BTreeSet::from(['K']));
+}
+
+#[test]
+fn rules_as_nodes_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    fsa.to_dot_with("basic relational rule nodes", &RenderOptions{rules_as_nodes: true, ..Default::default()}).expect("error");
+    let dot: String = std::fs::read_to_string("basic relational rule nodes.dot").expect("error");
+    assert!(dot.contains("[S -> SbSc]"));
+    assert!(!dot.contains("label=\"c [S -> SbSc]"));
+
+    fsa.to_dot("basic relational edge labels").expect("error");
+    let labeled: String = std::fs::read_to_string("basic relational edge labels.dot").expect("error");
+    assert!(!labeled.contains("\"rules 1\""));
+    assert!(labeled.contains("label=\"c [S -> SbSc] \""));
+
+    // every transition carrying rules becomes a box with one edge into it and one out of it, the other transitions stay
+    let transitions = |dot: &str, with_rules: bool| -> Vec<String> {
+        let mut res: Vec<String> = dot.lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_digit()) && line.contains(" -> ") && line.contains(" [S -> ") == with_rules)
+            .map(|line| line.to_string())
+            .collect();
+        res.sort();
+        res
+    };
+    let boxes: usize = dot.lines().filter(|line| line.starts_with("\"rules ") && line.contains("shape=box")).count();
+    assert_eq!(boxes, transitions(&labeled, true).len());
+    assert!(boxes > 0);
+    for index in 1..=boxes {
+        assert_eq!(dot.lines().filter(|line| line.contains(&format!(" -> \"rules {}\" ", index))).count(), 1);
+        assert_eq!(dot.lines().filter(|line| line.starts_with(&format!("\"rules {}\" -> ", index))).count(), 1);
+    }
+    let plain: Vec<String> = transitions(&dot, false).into_iter().filter(|line| !line.contains("\"rules ")).collect();
+    assert_eq!(plain, transitions(&labeled, false));
+    assert_eq!(plain.iter().filter(|line| line.ends_with("[ label=\"S \" ]")).count(), 2);
+}
+
+#[test]
+fn eliminate_unit_productions_test() {
+    let grammar = common::unit_chain_grammar();
+    let eliminated = relational_parsing::eliminate_unit_productions(&grammar.rules);
+
+    assert_eq!(eliminated.len(), 4);
+    for words in eliminated.values() {
+        assert!(words.iter().all(|word| !matches!(word[..], [Symbol::Nonterminal(_)])));
+    }
+    assert_eq!(eliminated[&'S'].len(), 4);
+
+    // the language of the grammar is a*(a | b+)
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &eliminated);
+    for input in all_strings(&['a', 'b'], 6) {
+        let a_count: usize = input.iter().take_while(|terminal| **terminal == 'a').count();
+        let expected: bool = !input.is_empty() && (a_count == input.len() || (a_count < input.len() && input[a_count..].iter().all(|terminal| *terminal == 'b')));
+        assert_eq!(fsa.recognize(&input), expected, "{:?}", input);
+    }
+}
+
+#[test]
+fn size_delta_test() {
+    let grammar = common::common_prefix_grammar();
+    let mut fsa = grammar.finite_state_automaton.restrict_by(0, &HashSet::from([0, 1]), |state: &State, _| Some(1 - state));
+    let before: FsaStats = fsa.stats();
+    let mut visitor = CollectingVisitor{states: Vec::new(), transitions: Vec::new(), atomics: Vec::new()};
+    fsa.accept(&mut visitor);
+    assert_eq!(before.states, visitor.states.len());
+    assert_eq!(before.transitions, visitor.transitions.len());
+    assert_eq!(before.atomic_entries, visitor.atomics.len());
+
+    fsa.dedup_atomic();
+    let after: FsaStats = fsa.stats();
+    let delta: FsaStatsDelta = relational_parsing::size_delta(&before, &after);
+    assert!(delta.states < 0);
+    assert_eq!(delta.states, after.states as isize - before.states as isize);
+    assert_eq!(delta.atomic_entries, 0);
+    assert_eq!(relational_parsing::size_delta(&after, &before).states, -delta.states);
+    assert_eq!(relational_parsing::size_delta(&after, &after), FsaStatsDelta::default());
+}
+
+#[test]
+fn transition_count_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    for (state, edges) in fsa.adjacency() {
+        for symbol in edges.iter().map(|(symbol, _)| *symbol).chain([Symbol::Terminal('z')]) {
+            let expected: usize = fsa.simulate(&state, symbol).map_or(0, |destinations| destinations.len());
+            assert_eq!(fsa.transition_count(&state, &symbol), expected);
+        }
+    }
+    assert_eq!(fsa.transition_count(&fsa.get_start().0, &Symbol::Nonterminal('S')), 1);
+    assert_eq!(fsa.transition_count(&usize::MAX, &Symbol::Epsilon), 0);
+}
+
+#[test]
+fn build_fsa_with_provenance_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let (fsa, provenance) = FiniteStateAutomaton::build_fsa_with_provenance(&grammar.terminals, grammar.start, &grammar.rules);
+
+    let mut visitor = CollectingVisitor{states: Vec::new(), transitions: Vec::new(), atomics: Vec::new()};
+    fsa.accept(&mut visitor);
+    for state in &visitor.states {
+        assert_eq!(provenance.contains_key(state), *state > 1, "{}", state);
+    }
+    for (nonterminal, terminal) in provenance.values() {
+        assert!(fsa.get_atomic(Symbol::Nonterminal(*nonterminal), *terminal).is_some());
+    }
+    for (symbol, terminal) in &visitor.atomics {
+        if let (Symbol::Nonterminal(nonterminal), Some((state, _, _))) = (symbol, fsa.get_atomic(*symbol, *terminal)) {
+            if *state > 1 {
+                assert!(provenance[state] <= (*nonterminal, *terminal));
+            }
+        }
+    }
+}
+
+#[test]
+fn recognize_with_wildcard_test() {
+    let grammar = common::common_prefix_grammar();
+    let fsa = &grammar.finite_state_automaton;
+
+    // the wildcard resolves to b in abb and to c in abc
+    assert!(fsa.recognize_with_wildcard(&[Some('a'), Some('b'), None]));
+    assert!(fsa.recognize_with_wildcard(&[None]));
+    assert!(!fsa.recognize_with_wildcard(&[Some('c'), None]));
+    assert_eq!(fsa.recognize_with_wildcard(&[]), fsa.recognize(&[]));
+
+    let alphabet: [Terminal; 3] = ['a', 'b', 'c'];
+    for input in all_strings(&['a', 'b', 'c', '?'], 4) {
+        let pattern: Vec<Option<Terminal>> = input.iter().map(|terminal| if *terminal == '?' { None } else { Some(*terminal) }).collect();
+        let mut substitutions: Vec<Vec<Terminal>> = vec![vec![]];
+        for opt_terminal in &pattern {
+            let options: Vec<Terminal> = match opt_terminal {
+                Some(terminal) => vec![*terminal],
+                None => alphabet.to_vec(),
+            };
+            substitutions = substitutions.iter().flat_map(|prefix| options.iter().map(move |terminal| [&prefix[..], &[*terminal]].concat())).collect();
+        }
+        let expected: bool = substitutions.iter().any(|substitution| fsa.recognize(substitution));
+        assert_eq!(fsa.recognize_with_wildcard(&pattern), expected, "{:?}", pattern);
+    }
+}
+
+#[test]
+fn sccs_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::nullable_recursive_grammar(), common::common_prefix_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let adjacency: HashMap<State, Vec<(Symbol, State)>> = fsa.adjacency();
+        let reachable_from = |state: State| -> HashSet<State> {
+            let mut res: HashSet<State> = HashSet::from([state]);
+            let mut to_visit: Vec<State> = vec![state];
+            while let Some(curr) = to_visit.pop() {
+                for (_, dest) in &adjacency[&curr] {
+                    if res.insert(*dest) {
+                        to_visit.push(*dest);
+                    }
+                }
+            }
+            res
+        };
+
+        let sccs: Vec<HashSet<State>> = fsa.sccs();
+        assert_eq!(sccs.iter().map(|component| component.len()).sum::<usize>(), adjacency.len());
+        for (index, component) in sccs.iter().enumerate() {
+            for state in component {
+                let reachable: HashSet<State> = reachable_from(*state);
+                for other in adjacency.keys() {
+                    let same_component: bool = reachable.contains(other) && reachable_from(*other).contains(state);
+                    assert_eq!(component.contains(other), same_component);
+                }
+                for later in &sccs[index + 1..] {
+                    assert!(later.iter().all(|other| !reachable.contains(other)));
+                }
+            }
+        }
+    }
+    // the left recursion S -> Sa gives a cycle
+    let grammar = common::basic_relational_parsing_example_grammar();
+    assert!(grammar.finite_state_automaton.sccs().iter().any(|component| component.len() > 1 || component.iter().any(|state| grammar.finite_state_automaton.adjacency()[state].iter().any(|(_, dest)| dest == state))));
+}
+
+#[test]
+fn symbol_ord_test() {
+    let symbols: BTreeSet<Symbol> = BTreeSet::from([Symbol::Nonterminal('A'), Symbol::Terminal('b'), Symbol::Epsilon, Symbol::Nonterminal('B'), Symbol::Terminal('a')]);
+    assert_eq!(symbols.into_iter().collect::<Vec<Symbol>>(), vec![Symbol::Epsilon, Symbol::Terminal('a'), Symbol::Terminal('b'), Symbol::Nonterminal('A'), Symbol::Nonterminal('B')]);
+    // terminals come before nonterminals regardless of their characters
+    assert!(Symbol::Terminal('z') < Symbol::Nonterminal('A'));
+}
+
+#[test]
+fn recognize_history_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    for input in all_strings(&['a', 'b', 'c'], 4) {
+        let (accepted, history) = fsa.recognize_history(&input);
+        assert_eq!(accepted, fsa.recognize(&input));
+        assert_eq!(history.len(), input.len() + 1);
+        for (index, states) in history.iter().enumerate() {
+            assert_eq!(*states, fsa.states_after(&input[..index]));
+        }
+    }
+    let (accepted, history) = fsa.recognize_history(&['b', 'a']);
+    assert!(!accepted);
+    assert!(history[1].is_empty() && history[2].is_empty());
+}
+
+#[test]
+fn rename_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let renamed = relational_parsing::rename_nonterminal(&grammar.rules, 'S', 'T').unwrap();
+    assert!(!renamed.contains_key(&'S'));
+    assert!(renamed.values().flatten().flatten().all(|symbol| *symbol != Symbol::Nonterminal('S')));
+    assert!(renamed[&'T'].contains(&vec![Symbol::Nonterminal('T'), Symbol::Terminal('a')]));
+
+    let renamed = relational_parsing::rename_terminal(&renamed, 'a', 'x').unwrap();
+    let fsa = FiniteStateAutomaton::build_fsa(&HashSet::from(['x', 'b', 'c']), 'T', &renamed);
+    for input in all_strings(&['a', 'b', 'c'], 5) {
+        let renamed_input: Vec<Terminal> = input.iter().map(|terminal| if *terminal == 'a' { 'x' } else { *terminal }).collect();
+        assert_eq!(fsa.recognize(&renamed_input), grammar.finite_state_automaton.recognize(&input));
+    }
+
+    assert_eq!(relational_parsing::rename_terminal(&grammar.rules, 'a', 'b'), Err(RenameError::AlreadyUsed(Symbol::Terminal('b'))));
+    assert_eq!(relational_parsing::rename_nonterminal(&grammar.rules, 'S', 'S').unwrap(), grammar.rules);
+    // a terminal with the same character is a different symbol
+    assert!(relational_parsing::rename_nonterminal(&grammar.rules, 'S', 'a').is_ok());
+}
+
+#[test]
+fn leading_nonterminal_remainder_test() {
+    // the whole word of K has to be derived before the ';', not only its tail
+    let grammar = relational_parsing::parse_grammar_yacc_like("Z : K ';' ; K : 'i' 'f' ;").unwrap();
+    for input in all_strings(&['i', 'f', ';'], 4) {
+        assert_eq!(grammar.finite_state_automaton.recognize(&input), input == vec!['i', 'f', ';'], "{:?}", input);
+    }
+}
+
+#[test]
+fn leading_left_recursive_nonterminal_test() {
+    // the recursion of T loops before the 'x', i.e. E = ab y* x
+    let grammar = relational_parsing::parse_grammar_yacc_like("E : T 'x' ; T : T 'y' | 'a' 'b' ;").unwrap();
+    assert!(grammar.finite_state_automaton.recognize(&['a', 'b', 'x']));
+    assert!(grammar.finite_state_automaton.recognize(&['a', 'b', 'y', 'x']));
+    assert!(grammar.finite_state_automaton.recognize(&['a', 'b', 'y', 'y', 'x']));
+    let in_language = |input: &[Terminal]| -> bool {
+        input.len() >= 3 && input[..2] == ['a', 'b'] && input[input.len() - 1] == 'x' && input[2..input.len() - 1].iter().all(|t| *t == 'y')
+    };
+    assert_same_language(|input| grammar.finite_state_automaton.recognize(input), in_language, &['a', 'b', 'x', 'y'], 6);
+
+    // with another alternative and a recursion of E itself, i.e. E = (ab y* x + c) z*
+    let grammar = relational_parsing::parse_grammar_yacc_like("E : E 'z' | T 'x' | 'c' ; T : T 'y' | 'a' 'b' ;").unwrap();
+    let in_language = |input: &[Terminal]| -> bool {
+        let prefix: &[Terminal] = &input[..input.len() - input.iter().rev().take_while(|t| **t == 'z').count()];
+        prefix == ['c'] || in_language(prefix)
+    };
+    assert_same_language(|input| grammar.finite_state_automaton.recognize(input), in_language, &['a', 'b', 'c', 'x', 'y', 'z'], 6);
+}
+
+#[test]
+fn concat_grammars_test() {
+    let a = relational_parsing::parse_grammar_yacc_like("S : 'a' S | 'b' ;").unwrap();
+    let b = relational_parsing::parse_grammar_yacc_like("S : 'c' T | 'd' ; T : 'a' 'a' ;").unwrap();
+    let (start, rules) = relational_parsing::concat_grammars(&a.rules, a.start, &b.rules, b.start);
+    assert_eq!(rules.len(), 4);
+    assert!(!a.rules.contains_key(&start) && !b.rules.contains_key(&start));
+
+    let terminals: HashSet<Terminal> = a.terminals.union(&b.terminals).cloned().collect();
+    let fsa = FiniteStateAutomaton::build_fsa(&terminals, start, &rules);
+    for input in all_strings(&['a', 'b', 'c', 'd'], 6) {
+        let expected: bool = (0..=input.len()).any(|split| a.finite_state_automaton.recognize(&input[..split]) && b.finite_state_automaton.recognize(&input[split..]));
+        assert_eq!(fsa.recognize(&input), expected, "{:?}", input);
+    }
+}
+
+#[test]
+fn write_csv_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let mut csv: Vec<u8> = Vec::new();
+    fsa.write_csv(&mut csv).expect("error");
+    let csv: String = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "src,symbol,dest,rules,accepting_dest");
+    assert_eq!(lines[1], "0,S,1,,true");
+
+    let mut visitor = CollectingVisitor{states: Vec::new(), transitions: Vec::new(), atomics: Vec::new()};
+    fsa.accept(&mut visitor);
+    assert_eq!(lines.len() - 1, visitor.transitions.len());
+    for line in &lines[1..] {
+        let fields: Vec<&str> = line.split(',').collect();
+        assert_eq!(fields.len(), 5);
+        assert_eq!(fields[4], fsa.is_accepting(&fields[2].parse::<State>().unwrap()).to_string());
+    }
+
+    // a comma as terminal is quoted
+    let grammar = relational_parsing::parse_grammar_yacc_like("list : list ',' 'a' | 'a' ;").unwrap();
+    let mut csv: Vec<u8> = Vec::new();
+    grammar.finite_state_automaton.write_csv(&mut csv).expect("error");
+    let csv: String = String::from_utf8(csv).unwrap();
+    assert!(csv.lines().any(|line| line.contains(",\",\",")));
+    assert!(csv.contains("\"[A -> A,a]\""));
+}
+
+#[test]
+fn indirectly_nullable_start_test() {
+    let grammar = common::indirectly_nullable_start_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    assert!(fsa.get_start().1);
+    assert!(fsa.recognize(&[]));
+    assert!(fsa.recognize(&['a']));
+    assert!(!fsa.recognize(&['a', 'a']));
+    let mut memoize: Memoize = Memoize::new();
+    assert!(relational_parsing::g_accepts_string(vec![], &grammar, &mut memoize));
+    assert!(relational_parsing::g_accepts_string(vec!['a'], &grammar, &mut memoize));
+}
+
+#[test]
+fn length_bounds_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : 'a' 'b' | 'a' 'b' 'c' t ; t : 'd' | ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.length_bounds(), Some((2, Some(4))));
+    let accepted: Vec<Vec<Terminal>> = all_strings(&['a', 'b', 'c', 'd'], 6).into_iter().filter(|input| grammar.finite_state_automaton.recognize(input)).collect();
+    assert_eq!(accepted.iter().map(|input| input.len()).max(), Some(4));
+
+    // S -> Sa repeats the a, S -> aSa nests the atomic languages
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::odd_number_of_a_grammar(), common::common_prefix_grammar()] {
+        let shortest: usize = grammar.finite_state_automaton.shortest_accepted().unwrap().len();
+        assert_eq!(grammar.finite_state_automaton.length_bounds(), Some((shortest, None)));
+    }
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : 'a' | ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.length_bounds(), Some((0, Some(1))));
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : s 'a' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.length_bounds(), None);
+}
+
+#[test]
+fn first_conflicts_test() {
+    let grammar = common::common_prefix_grammar();
+    assert_eq!(relational_parsing::first_conflicts(&grammar.rules), vec![('B', 'b'), ('S', 'a')]);
+    let first = relational_parsing::first_sets(&grammar.rules);
+    assert_eq!(first[&'S'], BTreeSet::from(['a', 'c']));
+
+    // S -> Sa and S -> a both start with a, through the left recursion
+    let grammar = common::basic_relational_parsing_example_grammar();
+    assert_eq!(relational_parsing::first_conflicts(&grammar.rules), vec![('S', 'a')]);
+
+    // nullable nonterminals let the FIRST set of the next symbol through
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : a 'b' | 'b' ; a : 'a' | ;").unwrap();
+    assert_eq!(relational_parsing::first_conflicts(&grammar.rules), vec![('s', 'b')]);
+    assert_eq!(relational_parsing::first_conflicts(&common::unit_chain_grammar().rules), vec![('C', 'b'), ('S', 'a')]);
+}
+
+#[test]
+fn verify_derivation_test() {
+    // the parser reports the rules of a derivation bottom-up, which is its leftmost order as long as every sentential
+    // form contains one nonterminal at most
+    for grammar in [common::three_rule_grammar(), common::even_a_middle_b_grammar(), common::direct_left_recursive_grammar(), common::indirect_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::keyword_grammar(), common::common_prefix_grammar()] {
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        for input in all_strings(&alphabet, 5).into_iter().filter(|input| grammar.finite_state_automaton.recognize(input)) {
+            let mut memoize: Memoize = Memoize::new();
+            for rules in relational_parsing::find_parses(input.clone(), &grammar, &mut memoize).unwrap_or_default() {
+                assert!(relational_parsing::verify_derivation(grammar.start, std::slice::from_ref(&rules), &input), "{:?} {:?}", input, rules);
+                assert!(!relational_parsing::verify_derivation(grammar.start, &[rules], &[&input[..], &input[..1]].concat()));
+            }
+        }
+    }
+}
+
+#[test]
+#[ignore = "known bug: find_parses reports the rules of branching derivations in rightmost order, and moves the rules around left recursion"]
+fn find_parses_leftmost_order_test() {
+    for (grammar, input) in [(common::basic_relational_parsing_example_grammar(), vec!['a', 'b', 'a', 'a', 'c']), (common::difficult_bottom_up_grammar(), vec!['a', '+', 'a'])] {
+        let mut memoize: Memoize = Memoize::new();
+        for rules in relational_parsing::find_parses(input.clone(), &grammar, &mut memoize).unwrap() {
+            assert!(relational_parsing::verify_derivation(grammar.start, &[rules], &input));
+        }
+    }
+}
+
+#[test]
+#[ignore = "known bug: the parser accepts inputs which end inside an unfinished nested rule"]
+fn parser_accepts_unfinished_rule_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    assert!(!grammar.finite_state_automaton.recognize(&['a', 'b', 'a']));
+    let mut memoize: Memoize = Memoize::new();
+    assert!(!relational_parsing::g_accepts_string(vec!['a', 'b', 'a'], &grammar, &mut memoize));
+}
+
+#[test]
+#[ignore = "known bug: find_parses rejects inputs whose last atomic language ends in the epsilon state"]
+fn parser_rejects_epsilon_end_test() {
+    for (grammar, input) in [(common::e_rule_relational_parsing_example_grammar(), vec![]), (common::even_a_middle_b_grammar(), vec!['b'])] {
+        assert!(grammar.finite_state_automaton.recognize(&input));
+        let mut memoize: Memoize = Memoize::new();
+        assert!(relational_parsing::g_accepts_string(input.clone(), &grammar, &mut memoize));
+        let mut memoize: Memoize = Memoize::new();
+        assert!(relational_parsing::find_parses(input, &grammar, &mut memoize).is_ok());
+    }
+}
+
+#[test]
+#[ignore = "known bug: the parser reports incomplete derivations for the nullable recursive grammar"]
+fn parser_incomplete_derivation_test() {
+    let grammar = common::nullable_recursive_grammar();
+    let mut memoize: Memoize = Memoize::new();
+    for rules in relational_parsing::find_parses(vec!['a', 'b'], &grammar, &mut memoize).unwrap() {
+        assert!(rules.iter().any(|(nonterminal, _)| *nonterminal == grammar.start), "{:?}", rules);
+    }
+}
+
+#[test]
+fn inline_nonterminals_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::three_rule_grammar(), common::difficult_bottom_up_grammar(), common::odd_number_of_a_grammar(), common::even_a_middle_b_grammar(), common::direct_left_recursive_grammar(), common::indirect_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::direct_right_recursive_grammar(), common::indirect_right_recursive_grammar(), common::test_grammar(), common::no_base_case_grammar(), common::keyword_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar(), common::indirectly_nullable_start_grammar()];
+    for grammar in grammars {
+        let inlined = grammar.finite_state_automaton.inline_nonterminals();
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| inlined.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 5);
+    }
+
+    for grammar in [common::difficult_bottom_up_grammar(), common::direct_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::keyword_grammar(), common::indirectly_nullable_start_grammar()] {
+        assert!(grammar.finite_state_automaton.inline_nonterminals().nonterminals().is_empty());
+    }
+    // self-embedding and right recursive nonterminals cannot be inlined finitely
+    assert_eq!(common::basic_relational_parsing_example_grammar().finite_state_automaton.inline_nonterminals().nonterminals(), BTreeSet::from(['S']));
+    assert_eq!(common::direct_right_recursive_grammar().finite_state_automaton.inline_nonterminals().nonterminals(), BTreeSet::from(['A']));
+}
+
+// Compares the recognition time before and after inlining the nonterminals, run with `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn inline_nonterminals_benchmark_test() {
+    let grammar = common::difficult_bottom_up_grammar();
+    let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+    let inputs: Vec<Vec<Terminal>> = all_strings(&alphabet, 10);
+
+    let start = std::time::Instant::now();
+    let expected: Vec<bool> = inputs.iter().map(|input| grammar.finite_state_automaton.recognize(input)).collect();
+    let before = start.elapsed();
+
+    let inlined = grammar.finite_state_automaton.inline_nonterminals();
+    let start = std::time::Instant::now();
+    let accepted: Vec<bool> = inputs.iter().map(|input| inlined.recognize(input)).collect();
+    let after = start.elapsed();
+
+    assert_eq!(accepted, expected);
+    println!("{} inputs: before {:?}, after inlining {:?}", inputs.len(), before, after);
+}
+
+
+#[test]
+fn build_fsa_from_file_test() {
+    let path = std::env::temp_dir().join("build_fsa_from_file_test.y");
+    let path: &str = path.to_str().unwrap();
+
+    std::fs::write(path, "expr : expr '+' term | term ;\nterm : 'a' ;\n").unwrap();
+    let (fsa, grammar) = FiniteStateAutomaton::build_fsa_from_file(path).unwrap();
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &['a', '+'], 5);
+    assert!(fsa.recognize(&['a', '+', 'a']));
+
+    std::fs::write(path, "S : 'a' ;\nA : B ;\n").unwrap();
+    let err = FiniteStateAutomaton::build_fsa_from_file(path).map(|_| ()).unwrap_err();
+    assert_eq!(err, BuildError::Parse(2, String::from("undefined nonterminal 'B'")));
+    assert_eq!(err.line(), Some(2));
+    assert_eq!(err.to_string(), "line 2: undefined nonterminal 'B'");
+    std::fs::remove_file(path).unwrap();
+
+    let err = FiniteStateAutomaton::build_fsa_from_file(path).map(|_| ()).unwrap_err();
+    assert!(matches!(&err, BuildError::Io(err_path, _) if err_path == path));
+    assert_eq!(err.line(), None);
+}
+
+#[test]
+fn ambiguous_transitions_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::keyword_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let ambiguous: Vec<(State, Symbol)> = fsa.ambiguous_transitions();
+        assert!(ambiguous.windows(2).all(|pair| pair[0] < pair[1]));
+        for (state, edges) in fsa.adjacency() {
+            for (symbol, _) in edges {
+                assert_eq!(ambiguous.contains(&(state, symbol)), fsa.transition_count(&state, &symbol) > 1);
+            }
+        }
+    }
+    assert!(common::keyword_grammar().finite_state_automaton.ambiguous_transitions().is_empty());
+    let ambiguous: Vec<(State, Symbol)> = common::common_prefix_grammar().finite_state_automaton.ambiguous_transitions();
+    assert_eq!(ambiguous.iter().map(|(_, symbol)| *symbol).collect::<Vec<Symbol>>(), vec![Symbol::Terminal('b')]);
+}
+
+#[test]
+fn build_fsa_with_test() {
+    // the input without the sub-structure of every 'a', None if an 'a' is not followed by it or it occurs elsewhere
+    let strip = |input: &[Terminal]| -> Option<Vec<Terminal>> {
+        let mut res: Vec<Terminal> = Vec::new();
+        let mut pos: usize = 0;
+        while pos < input.len() {
+            res.push(input[pos]);
+            match input[pos] {
+                'a' if input.get(pos + 1) == Some(&'x') && input.get(pos + 2) == Some(&'y') => pos += 3,
+                'a' | 'x' | 'y' => return None,
+                _ => pos += 1,
+            }
+        }
+        Some(res)
+    };
+
+    let config = BuildConfig{terminal_overrides: HashMap::from([('a', vec!['x', 'y']), ('b', vec![])]), ..BuildConfig::default()};
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::direct_left_recursive_grammar(), common::direct_right_recursive_grammar(), common::e_rule_relational_parsing_example_grammar()] {
+        let default = FiniteStateAutomaton::build_fsa_with(&grammar.terminals, grammar.start, &grammar.rules, &BuildConfig::default());
+        assert_eq!(default.stats(), grammar.finite_state_automaton.stats());
+        let preallocated = FiniteStateAutomaton::build_fsa_with(&grammar.terminals, grammar.start, &grammar.rules, &BuildConfig{estimated_states: Some(64), ..BuildConfig::default()});
+        assert_eq!(preallocated.stats(), grammar.finite_state_automaton.stats());
+        assert_same_language(|input| preallocated.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4);
+
+        let fsa = FiniteStateAutomaton::build_fsa_with(&grammar.terminals, grammar.start, &grammar.rules, &config);
+        assert!(!fsa.get_atomic(Symbol::Terminal('a'), 'a').unwrap().2);
+        let mut alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        alphabet.extend(['x', 'y']);
+        for input in all_strings(&alphabet, 6) {
+            let expected: bool = strip(&input[..]).is_some_and(|stripped| grammar.finite_state_automaton.recognize(&stripped));
+            assert_eq!(fsa.recognize(&input), expected, "{:?}", input);
+        }
+    }
+}
+
+#[test]
+fn is_empty_language_test() {
+    let grammar = common::basic_relational_parsing_example_grammar();
+    assert!(!grammar.finite_state_automaton.is_empty_language());
+    // every string of the grammar starts with an 'a'
+    let starting_with_b = grammar.finite_state_automaton.restrict_by(0, &HashSet::from([1]), |state: &State, terminal: Terminal| match (state, terminal) {
+        (0, 'b') | (1, _) => Some(1),
+        _ => None,
+    });
+    assert!(starting_with_b.is_empty_language());
+    assert!(common::no_base_case_grammar().finite_state_automaton.is_empty_language());
+
+    for grammar in [common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::nullable_recursive_grammar(), common::no_base_case_grammar(), common::indirectly_nullable_start_grammar()] {
+        assert_eq!(grammar.finite_state_automaton.is_empty_language(), grammar.finite_state_automaton.shortest_accepted().is_none());
+    }
+}
+
+#[test]
+fn is_infinite_language_test() {
+    assert!(!common::keyword_grammar().finite_state_automaton.is_infinite_language());
+    assert!(!common::indirectly_nullable_start_grammar().finite_state_automaton.is_infinite_language());
+    assert!(!common::no_base_case_grammar().finite_state_automaton.is_infinite_language());
+    assert!(common::direct_left_recursive_grammar().finite_state_automaton.is_infinite_language());
+    assert!(common::direct_right_recursive_grammar().finite_state_automaton.is_infinite_language());
+    assert!(common::basic_relational_parsing_example_grammar().finite_state_automaton.is_infinite_language());
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' S | 'b' ;").unwrap();
+    assert!(grammar.finite_state_automaton.is_infinite_language());
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : A A ; A : 'a' | 'b' | ;").unwrap();
+    assert!(!grammar.finite_state_automaton.is_infinite_language());
+}
+
+#[test]
+fn has_accepted_extension_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::common_prefix_grammar(), common::no_base_case_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        let accepted: Vec<Vec<Terminal>> = all_strings(&alphabet, 7).into_iter().filter(|input| fsa.recognize(input)).collect();
+        for prefix in all_strings(&alphabet, 3) {
+            // the shortest extensions of these grammars are short, so looking at accepted strings of length 7 suffices
+            assert_eq!(fsa.has_accepted_extension(&prefix), accepted.iter().any(|input| input.starts_with(&prefix)), "{:?}", prefix);
+        }
+    }
+    assert!(!common::keyword_grammar().finite_state_automaton.has_accepted_extension(&['i', 'i']));
+    assert!(common::keyword_grammar().finite_state_automaton.has_accepted_extension(&['i']));
+}
+
+#[test]
+fn grammar_to_string_test() {
+    let grammar = common::e_rule_relational_parsing_example_grammar();
+    assert_eq!(relational_parsing::grammar_to_string(&grammar.rules, grammar.start), "S : | 'a' | S 'a' | S 'b' S 'c' ;\n");
+    let grammar = common::difficult_bottom_up_grammar();
+    assert_eq!(relational_parsing::grammar_to_string(&grammar.rules, grammar.start), "S : E ;\nE : E Q F | F ;\nF : 'a' ;\nQ : '+' | '-' ;\n");
+
+    let quoting = relational_parsing::parse_grammar_yacc_like("q : '\\'' q '\\\\' | \"x\" ;").unwrap();
+    let grammars: Vec<Grammar> = vec![quoting, common::basic_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::even_more_indirect_left_recursive_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar(), common::keyword_grammar()];
+    for grammar in grammars {
+        let rendered: String = relational_parsing::grammar_to_string(&grammar.rules, grammar.start);
+        let parsed = relational_parsing::parse_grammar_yacc_like(&rendered).unwrap();
+        assert_eq!(parsed.rules, grammar.rules, "{}", rendered);
+        assert_eq!(parsed.start, grammar.start);
+        assert_eq!(relational_parsing::grammar_to_string(&parsed.rules, parsed.start), rendered);
+    }
+}
+
+#[test]
+fn build_recognizer_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar(), common::indirectly_nullable_start_grammar()];
+    for grammar in grammars {
+        let recognizer = FiniteStateAutomaton::build_recognizer(&grammar.terminals, grammar.start, &grammar.rules);
+        let (full, stripped) = (grammar.finite_state_automaton.stats(), recognizer.stats());
+        assert_eq!((stripped.states, stripped.accepting_states, stripped.atomic_entries), (full.states, full.accepting_states, full.atomic_entries));
+        assert!(stripped.transitions <= full.transitions);
+
+        let mut csv: Vec<u8> = Vec::new();
+        recognizer.write_csv(&mut csv).unwrap();
+        assert!(String::from_utf8(csv).unwrap().lines().skip(1).all(|row| row.split(',').nth(3) == Some("")));
+        for nonterminal in &grammar.nonterminals {
+            for terminal in &grammar.terminals {
+                assert!(recognizer.atomic_rule_options(Symbol::Nonterminal(*nonterminal), *terminal).is_empty());
+            }
+        }
+
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| recognizer.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 5);
+    }
+}
+
+
+#[test]
+fn remove_epsilon_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::indirect_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::indirect_right_recursive_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar(), common::indirectly_nullable_start_grammar()];
+    for grammar in grammars {
+        let fsa = grammar.finite_state_automaton.remove_epsilon();
+        assert!(fsa.adjacency().values().flatten().all(|(symbol, _)| *symbol != Symbol::Epsilon));
+        assert!(fsa.stats().states <= grammar.finite_state_automaton.stats().states);
+        assert!(!fsa.has_epsilon_cycle());
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 5);
+    }
+}
+
+#[test]
+fn rule_conflicts_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar()];
+    for grammar in grammars {
+        for fsa in [grammar.finite_state_automaton.remove_epsilon(), grammar.finite_state_automaton] {
+            let conflicts: Vec<(State, Symbol, State)> = fsa.rule_conflicts();
+            assert!(conflicts.windows(2).all(|pair| pair[0] < pair[1]));
+            for (state, edges) in fsa.adjacency() {
+                for (symbol, dest) in edges {
+                    let rules_count: usize = fsa.simulate(&state, symbol).unwrap().iter().filter(|(d, _, _)| **d == dest).count();
+                    assert_eq!(conflicts.contains(&(state, symbol, dest)), rules_count > 1);
+                }
+            }
+        }
+    }
+    assert!(common::basic_relational_parsing_example_grammar().finite_state_automaton.rule_conflicts().is_empty());
+    let conflicts: Vec<(State, Symbol, State)> = common::extra_e_rule_relational_parsing_example_grammar().finite_state_automaton.rule_conflicts();
+    assert_eq!(conflicts.iter().map(|(_, symbol, _)| *symbol).collect::<Vec<Symbol>>(), vec![Symbol::Terminal('c')]);
+}
+
+#[test]
+fn used_rules_test() {
+    // the unit chain grammar is left out, as its atomic languages currently lose S -> aS
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::keyword_grammar()];
+    for grammar in grammars {
+        let used: HashSet<Rules> = grammar.finite_state_automaton.used_rules();
+        assert!(!used.is_empty() && used.iter().all(|rules| !rules.is_empty()));
+        let used_productions: HashSet<(Nonterminal, Vec<Symbol>)> = used.into_iter().flatten().collect();
+        let productions: HashSet<(Nonterminal, Vec<Symbol>)> = grammar.rules.iter().flat_map(|(nt, words)| words.iter().map(|word| (*nt, word.clone()))).collect();
+        assert_eq!(used_productions, productions);
+        assert!(FiniteStateAutomaton::build_recognizer(&grammar.terminals, grammar.start, &grammar.rules).used_rules().is_empty());
+    }
+}
+
+#[test]
+fn matches_prefix_anywhere_test() {
+    let grammar = common::keyword_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let input: Vec<Terminal> = "xinf".chars().collect();
+    assert!(!fsa.recognize(&input) && !fsa.matches_prefix(&input) && fsa.matches_anywhere(&input));
+    let input: Vec<Terminal> = "ifn".chars().collect();
+    assert!(!fsa.recognize(&input) && fsa.matches_prefix(&input) && fsa.matches_anywhere(&input));
+    let input: Vec<Terminal> = "if".chars().collect();
+    assert!(fsa.recognize(&input) && fsa.matches_prefix(&input) && fsa.matches_anywhere(&input));
+    let input: Vec<Terminal> = "iif".chars().collect();
+    assert!(!fsa.recognize(&input) && !fsa.matches_prefix(&input) && fsa.matches_anywhere(&input));
+    assert!(!fsa.matches_anywhere(&['i', 'i', 'n'][..2]));
+
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::common_prefix_grammar(), common::e_rule_relational_parsing_example_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        for input in all_strings(&alphabet, 5) {
+            let prefix: bool = (0..=input.len()).any(|end| fsa.recognize(&input[..end]));
+            let anywhere: bool = (0..=input.len()).any(|start| (start..=input.len()).any(|end| fsa.recognize(&input[start..end])));
+            assert_eq!(fsa.matches_prefix(&input), prefix, "{:?}", input);
+            assert_eq!(fsa.matches_anywhere(&input), anywhere, "{:?}", input);
+        }
+    }
+}
+
+#[derive(Default)]
+struct PartsVisitor {
+    states: HashSet<State>,
+    accepting_states: HashSet<State>,
+    transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>>,
+}
+
+impl FsaVisitor for PartsVisitor {
+    fn visit_state(&mut self, state: &State, accepting: bool) {
+        self.states.insert(*state);
+        if accepting {
+            self.accepting_states.insert(*state);
+        }
+    }
+
+    fn visit_transition(&mut self, source: &State, symbol: &Symbol, dest: &State, rules: &Rules) {
+        self.transitions.entry(*source).or_default().entry(*symbol).or_default().insert((*dest, rules.clone()));
+    }
+}
+
+#[test]
+fn from_parts_test() {
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::even_more_indirect_left_recursive_grammar(), common::common_prefix_grammar()] {
+        let fsa = &grammar.finite_state_automaton;
+        let mut parts: PartsVisitor = PartsVisitor::default();
+        fsa.accept(&mut parts);
+        let atomic_map = fsa.atomic_map().clone();
+        assert!(atomic_map.contains_key(&(Symbol::Nonterminal(grammar.start), 'a')));
+
+        let rebuilt = FiniteStateAutomaton::from_parts(parts.states.clone(), parts.accepting_states.clone(), fsa.get_start().0, parts.transitions.clone(), atomic_map.clone()).unwrap();
+        assert_eq!(rebuilt.stats(), fsa.stats());
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| rebuilt.recognize(input), |input| fsa.recognize(input), &alphabet, 5);
+
+        let atomic_state: State = atomic_map[&(Symbol::Nonterminal(grammar.start), 'a')].0;
+        let mut states: HashSet<State> = parts.states.clone();
+        states.remove(&atomic_state);
+        let err = FiniteStateAutomaton::from_parts(states, parts.accepting_states, fsa.get_start().0, parts.transitions, atomic_map).map(|_| ()).unwrap_err();
+        assert_eq!(err, BuildError::MissingState(atomic_state));
+    }
+}
+
+#[test]
+fn coverage_test() {
+    let grammar = common::difficult_bottom_up_grammar();
+    let corpus: Vec<Vec<Terminal>> = vec![vec!['a'], vec!['a', '+', 'a'], vec!['a', '+']];
+    let coverage: Coverage = grammar.coverage(&corpus);
+    assert_eq!(coverage.unused_terminals, BTreeSet::from(['-']));
+    assert_eq!(coverage.unused_rules, BTreeSet::from([build_rule(('Q', "-"))]));
+    assert_eq!(coverage.rejected, vec![vec!['a', '+']]);
+
+    let grammar = common::keyword_grammar();
+    let coverage: Coverage = grammar.coverage(&[vec!['i', 'n']]);
+    assert_eq!(coverage.unused_terminals, BTreeSet::from(['f']));
+    assert_eq!(coverage.unused_rules, BTreeSet::from([build_rule(('K', "if"))]));
+    assert_eq!(grammar.coverage(&[vec!['i', 'n'], vec!['i', 'f']]), Coverage::default());
+
+    let coverage: Coverage = grammar.coverage(&[]);
+    assert_eq!(coverage.unused_terminals.len(), 3);
+    assert_eq!(coverage.unused_rules.len(), 2);
+}
+
+#[test]
+fn generalize_from_examples_test() {
+    let terminals: HashSet<Terminal> = HashSet::from(['a', 'b']);
+    let examples: Vec<Vec<Terminal>> = vec![vec!['a', 'b'], vec!['a'], vec!['b', 'b', 'a'], vec![], vec!['a', 'b'], vec!['a', 'c']];
+    let rules = relational_parsing::generalize_from_examples(&examples, &terminals);
+    assert!(rules[&'S'].contains(&vec![Symbol::Epsilon]));
+    let fsa = FiniteStateAutomaton::build_fsa(&terminals, 'S', &rules);
+    for input in all_strings(&['a', 'b', 'c'], 4) {
+        assert_eq!(fsa.recognize(&input), examples[..5].contains(&input), "{:?}", input);
+    }
+
+    let rules = relational_parsing::generalize_from_examples(&[], &terminals);
+    assert!(rules.is_empty());
+    assert!(FiniteStateAutomaton::build_fsa(&terminals, 'S', &rules).is_empty_language());
+}
+
+#[test]
+fn unreachable_accepting_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::extra_e_rule_relational_parsing_example_grammar(), common::three_rule_grammar(), common::difficult_bottom_up_grammar(), common::odd_number_of_a_grammar(), common::even_a_middle_b_grammar(), common::direct_left_recursive_grammar(), common::indirect_left_recursive_grammar(), common::even_more_indirect_left_recursive_grammar(), common::direct_right_recursive_grammar(), common::indirect_right_recursive_grammar(), common::test_grammar(), common::no_base_case_grammar(), common::keyword_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar(), common::indirectly_nullable_start_grammar()];
+    for grammar in grammars {
+        // the atomic languages of every nonterminal are built, also of those which are never entered from the start,
+        // e.g. B in the indirect left recursive grammar, whose left recursion is resolved within the atomic languages of A
+        let (fsa, provenance) = FiniteStateAutomaton::build_fsa_with_provenance(&grammar.terminals, grammar.start, &grammar.rules);
+        for state in fsa.unreachable_accepting() {
+            assert!(provenance.get(&state).is_some_and(|(nonterminal, _)| *nonterminal != grammar.start));
+        }
+    }
+
+    let transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::from([(0, HashMap::from([(Symbol::Terminal('a'), HashSet::from([(1, Vec::new())]))]))]);
+    let fsa = FiniteStateAutomaton::from_parts(HashSet::from([0, 1, 2]), HashSet::from([1, 2]), 0, transitions, HashMap::new()).unwrap();
+    assert_eq!(fsa.unreachable_accepting(), HashSet::from([2]));
+    assert!(common::basic_relational_parsing_example_grammar().finite_state_automaton.unreachable_accepting().is_empty());
+    let grammar = common::indirect_left_recursive_grammar();
+    let (fsa, provenance) = FiniteStateAutomaton::build_fsa_with_provenance(&grammar.terminals, grammar.start, &grammar.rules);
+    let unreachable: HashSet<State> = fsa.unreachable_accepting();
+    assert!(!unreachable.is_empty() && unreachable.iter().all(|state| provenance[state].0 == 'B'));
+}
+
+#[test]
+fn enumerate_ordered_test() {
+    let fsa = &common::keyword_grammar().finite_state_automaton;
+    assert_eq!(fsa.enumerate_ordered(2, &['n', 'f']), vec![vec!['i', 'n'], vec!['i', 'f']]);
+    assert_eq!(fsa.enumerate_ordered(2, &['x', 'f']), vec![vec!['i', 'f'], vec!['i', 'n']]);
+    assert_eq!(fsa.enumerate_ordered(1, &[]), Vec::<Vec<Terminal>>::new());
+
+    let grammar = common::e_rule_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let alphabet: Vec<Terminal> = vec!['c', 'b', 'a'];
+    let enumerated: Vec<Vec<Terminal>> = fsa.enumerate_ordered(5, &alphabet);
+    // all_strings produces the strings of every length in the order of the alphabet
+    let expected: Vec<Vec<Terminal>> = all_strings(&alphabet, 5).into_iter().filter(|input| fsa.recognize(input)).collect();
+    assert_eq!(enumerated, expected);
+    assert_eq!(enumerated[0], vec![]);
+    assert_eq!(fsa.enumerate_ordered(5, &['b', 'a', 'b', 'c']), all_strings(&['b', 'a', 'c'], 5).into_iter().filter(|input| fsa.recognize(input)).collect::<Vec<Vec<Terminal>>>());
+}
+
+#[test]
+fn left_quotient_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar()];
+    for grammar in grammars {
+        let fsa = &grammar.finite_state_automaton;
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        for prefix in all_strings(&alphabet, 2) {
+            let quotient = fsa.left_quotient(&prefix);
+            assert_eq!(quotient.is_empty_language(), !fsa.has_accepted_extension(&prefix));
+            for suffix in all_strings(&alphabet, 4) {
+                assert_eq!(quotient.recognize(&suffix), fsa.recognize(&[&prefix[..], &suffix[..]].concat()), "{:?} {:?}", prefix, suffix);
+            }
+        }
+    }
+}
+
+#[test]
+fn dot_error_test() {
+    let grammar = common::keyword_grammar();
+    let path = std::env::temp_dir().join("dot_error_test");
+    grammar.finite_state_automaton.to_dot(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(path.with_extension("dot")).unwrap();
+
+    let missing_dir = std::env::temp_dir().join("dot_error_test_missing").join("fsa");
+    let err: DotError = grammar.finite_state_automaton.to_dot(missing_dir.to_str().unwrap()).unwrap_err();
+    assert!(matches!(err, DotError::Io(_)));
+    assert!(std::error::Error::source(&err).is_some());
+    assert!(err.to_string().starts_with("cannot write the DOT file: "));
+
+    assert_eq!(DotError::MissingTransitionState(2, Symbol::Terminal('a'), 7).to_string(), "transition 2 -a-> 7 refers to a state which does not exist");
+    assert_eq!(DotError::MissingAtomicState(Symbol::Nonterminal('S'), 'a', 7).to_string(), "atomic language [S]^(a) starts in state 7, which does not exist");
+}
+
+#[test]
+fn decompose_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::even_more_indirect_left_recursive_grammar(), common::keyword_grammar(), common::common_prefix_grammar(), common::nullable_recursive_grammar(), relational_parsing::parse_grammar_yacc_like("S : 'a' A | 'b' B ; A : 'a' A | ; B : 'b' B | ;").unwrap()];
+    for grammar in grammars {
+        let fsa = &grammar.finite_state_automaton;
+        let components: Vec<FiniteStateAutomaton> = fsa.decompose();
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        for input in all_strings(&alphabet, 5) {
+            let accepting: usize = components.iter().filter(|component| component.recognize(&input)).count();
+            assert_eq!(accepting, usize::from(fsa.recognize(&input)), "{:?}", input);
+        }
+    }
+
+    let union = relational_parsing::parse_grammar_yacc_like("S : 'a' A | 'b' B ; A : 'a' A | ; B : 'b' B | ;").unwrap();
+    let components: Vec<FiniteStateAutomaton> = union.finite_state_automaton.decompose();
+    assert_eq!(components.len(), 2);
+    assert!(components[0].recognize(&['a', 'a']) && !components[0].recognize(&['b']));
+    assert!(components[1].recognize(&['b', 'b']) && !components[1].recognize(&['a']));
+    assert_eq!(common::basic_relational_parsing_example_grammar().finite_state_automaton.decompose().len(), 1);
+    assert_eq!(common::common_prefix_grammar().finite_state_automaton.decompose().len(), 2);
+}
+
+#[test]
+fn tokenize_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'i' 'f' | 'i' 'n' | 'x' | 'x' 'x' ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    assert_eq!(fsa.longest_accepted_prefix(&['x', 'x', 'x']), Some(2));
+    assert_eq!(fsa.longest_accepted_prefix(&['i', 'x']), None);
+    assert_eq!(fsa.longest_accepted_prefix(&[]), None);
+
+    let input: Vec<Terminal> = "ifxxin".chars().collect();
+    let tokens: Vec<Result<&[Terminal], usize>> = fsa.tokenize(&input).collect();
+    assert_eq!(tokens, vec![Ok(&['i', 'f'][..]), Ok(&['x', 'x'][..]), Ok(&['i', 'n'][..])]);
+
+    let input: Vec<Terminal> = "xinfx".chars().collect();
+    let tokens: Vec<Result<&[Terminal], usize>> = fsa.tokenize(&input).collect();
+    assert_eq!(tokens, vec![Ok(&['x'][..]), Ok(&['i', 'n'][..]), Err(3)]);
+
+    assert_eq!(fsa.tokenize(&[]).count(), 0);
+}
+
+#[test]
+fn terminal_classes_test() {
+    // a quoted string: a quote, any number of characters except the quote, and a closing quote
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : '\"' C '\"' ; C : C '.' | ;").unwrap();
+    let terminals: HashSet<Terminal> = grammar.terminals.iter().cloned().chain(['a', 'b', ' ']).collect();
+    let config = BuildConfig{terminal_classes: HashMap::from([('.', HashSet::from(['"']))]), ..BuildConfig::default()};
+    let fsa = FiniteStateAutomaton::build_fsa_with(&terminals, grammar.start, &grammar.rules, &config);
+
+    let input = |s: &str| -> Vec<Terminal> { s.chars().collect() };
+    assert!(fsa.recognize(&input("\"\"")));
+    assert!(fsa.recognize(&input("\"a b\"")));
+    assert!(fsa.recognize(&input("\"bba\"")));
+    assert!(!fsa.recognize(&input("\"a\"b\"")));
+    assert!(!fsa.recognize(&input("\"a.\"")));
+    assert!(!fsa.recognize(&input("\"ab")));
+    assert!(fsa.alphabet().iter().all(|terminal| *terminal != '.'));
+
+    // a placeholder at the start of a rule
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : '.' '.' | 'a' ;").unwrap();
+    let terminals: HashSet<Terminal> = ['.', 'a', 'b', 'c'].into_iter().collect();
+    let config = BuildConfig{terminal_classes: HashMap::from([('.', HashSet::from(['c']))]), ..BuildConfig::default()};
+    let fsa = FiniteStateAutomaton::build_fsa_with(&terminals, grammar.start, &grammar.rules, &config);
+    for string in all_strings(&['a', 'b', 'c'], 3) {
+        let expected: bool = string == ['a'] || (string.len() == 2 && !string.contains(&'c'));
+        assert_eq!(fsa.recognize(&string), expected, "{:?}", string);
+    }
+    // the derivations contain the rule with the placeholder rather than a rule for every terminal of the class
+    let used: HashSet<Rule> = fsa.used_rules().into_iter().flatten().collect();
+    assert_eq!(used, HashSet::from([build_rule(('S', "..")), build_rule(('S', "a"))]));
+}
+
+#[test]
+fn recognize_within_distance_test() {
+    let grammar = common::keyword_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let accepted: Vec<Vec<Terminal>> = all_strings(&grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4).into_iter().filter(|string| fsa.recognize(string)).collect();
+    let accepted_string: &Vec<Terminal> = accepted.iter().find(|string| string.len() >= 2).unwrap();
+    let deleted: Vec<Terminal> = accepted_string[1..].to_vec();
+    assert!(!fsa.recognize(&deleted));
+    assert!(!fsa.recognize_within_distance(&deleted, 0));
+    assert!(fsa.recognize_within_distance(&deleted, 1));
+    assert!(fsa.recognize_within_distance(&deleted, 2));
+
+    // the distance agrees with the edit distance to the closest accepted string
+    let edit_distance = |a: &[Terminal], b: &[Terminal]| -> usize {
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for i in 1..=a.len() {
+            let mut prev: usize = row[0];
+            row[0] = i;
+            for j in 1..=b.len() {
+                let substitution: usize = prev + usize::from(a[i - 1] != b[j - 1]);
+                prev = row[j];
+                row[j] = substitution.min(row[j] + 1).min(row[j - 1] + 1);
+            }
+        }
+        row[b.len()]
+    };
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::common_prefix_grammar()];
+    for grammar in grammars {
+        let fsa = &grammar.finite_state_automaton;
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        let accepted: Vec<Vec<Terminal>> = all_strings(&alphabet, 6).into_iter().filter(|string| fsa.recognize(string)).collect();
+        for input in all_strings(&alphabet, 3) {
+            let distance: usize = accepted.iter().map(|string| edit_distance(&input, string)).min().unwrap();
+            for k in 0..3 {
+                assert_eq!(fsa.recognize_within_distance(&input, k), distance <= k, "{:?} {}", input, k);
+            }
+        }
+    }
+}
+
+#[test]
+fn recognize_with_peak_test() {
+    let deterministic = relational_parsing::parse_grammar_yacc_like("S : S 'a' | 'b' ;").unwrap();
+    let ambiguous = relational_parsing::parse_grammar_yacc_like("S : S S | 'a' ;").unwrap();
+    let mut peaks: Vec<usize> = Vec::new();
+    for len in [2, 4, 8] {
+        let (accepted, peak) = ambiguous.finite_state_automaton.recognize_with_peak(&vec!['a'; len]);
+        assert!(accepted);
+        peaks.push(peak);
+    }
+    assert!(peaks[0] < peaks[1] && peaks[1] < peaks[2], "{:?}", peaks);
+
+    for len in [1, 4, 16] {
+        let input: Vec<Terminal> = std::iter::once('b').chain(vec!['a'; len]).collect();
+        assert_eq!(deterministic.finite_state_automaton.recognize_with_peak(&input), (true, 2));
+    }
+    assert!(!deterministic.finite_state_automaton.recognize_with_peak(&['a', 'b']).0);
+
+    for grammar in [common::basic_relational_parsing_example_grammar(), common::even_a_middle_b_grammar()] {
+        for input in all_strings(&grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4) {
+            assert_eq!(grammar.finite_state_automaton.recognize_with_peak(&input).0, grammar.finite_state_automaton.recognize(&input));
+        }
+    }
+}
+
+#[test]
+fn parse_spanned_test() {
+    let spanned = |input: &str| -> Vec<(Terminal, usize)> { input.chars().enumerate().map(|(position, terminal)| (terminal, 10 + position)).collect() };
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'c' A 'x' ; A : 'a' 'b' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.parse_spanned(&spanned("cabx")), Some(vec![(vec![build_rule(('A', "ab"))], vec![11, 12]), (vec![build_rule(('S', "cAx"))], vec![10, 11, 12, 13])]));
+    assert_eq!(grammar.finite_state_automaton.parse_spanned(&spanned("cab")), None);
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : S 'a' | 'b' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.parse_spanned(&spanned("baa")), Some(vec![(vec![build_rule(('S', "b"))], vec![10]), (vec![build_rule(('S', "Sa"))], vec![10, 11]), (vec![build_rule(('S', "Sa"))], vec![10, 11, 12])]));
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' S | 'b' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.parse_spanned(&spanned("aab")), Some(vec![(vec![build_rule(('S', "b"))], vec![12]), (vec![build_rule(('S', "aS"))], vec![11, 12]), (vec![build_rule(('S', "aS"))], vec![10, 11, 12])]));
+}
+
+#[test]
+fn inline_nonterminal_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'x' H 'y' H | H 'z' E ; H : 'a' | 'b' 'c' | ; E : 'e' E | ;").unwrap();
+    let h: Nonterminal = 'H';
+    let inlined: HashMap<Nonterminal, HashSet<Word>> = relational_parsing::inline_nonterminal(&grammar.rules, h).unwrap();
+    assert!(!inlined.contains_key(&h));
+    assert!(inlined.values().flatten().flatten().all(|symbol| *symbol != Symbol::Nonterminal(h)));
+    assert_eq!(inlined[&'S'].len(), 9 + 3);
+    assert!(inlined[&'S'].contains(&vec![Symbol::Terminal('x'), Symbol::Terminal('y')]));
+
+    // the language of the grammar, up to length 5
+    let helpers: [&str; 3] = ["", "a", "bc"];
+    let mut language: HashSet<Vec<Terminal>> = HashSet::new();
+    for first in helpers {
+        for second in helpers {
+            language.insert(format!("x{}y{}", first, second).chars().collect());
+        }
+        for repeat in 0..5 {
+            language.insert(format!("{}z{}", first, "e".repeat(repeat)).chars().collect());
+        }
+    }
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &inlined);
+    for input in all_strings(&grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 5) {
+        assert_eq!(fsa.recognize(&input), language.contains(&input), "{:?}", input);
+    }
+
+    assert_eq!(relational_parsing::inline_nonterminal(&grammar.rules, 'E'), Err(InlineError::Recursive('E')));
+    let indirect = relational_parsing::parse_grammar_yacc_like("S : A ; A : 'a' B ; B : A 'b' | ;").unwrap();
+    assert_eq!(relational_parsing::inline_nonterminal(&indirect.rules, 'A'), Err(InlineError::Recursive('A')));
+    assert_eq!(InlineError::Recursive('A').to_string(), "nonterminal A is recursive and cannot be inlined");
+}
+
+#[test]
+fn dead_rules_test() {
+    // S -> 'b' B is dead because B derives no string, which also makes C unreachable
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' A | 'b' B ; A : 'a' | ; B : 'b' B | C ; C : 'c' C B ; D : 'd' ;").unwrap();
+    let dead: Vec<(Nonterminal, Word)> = relational_parsing::dead_rules(&grammar.rules, grammar.start);
+    let expected: Vec<(Nonterminal, Word)> = vec![build_rule(('B', "bB")), build_rule(('B', "C")), build_rule(('C', "cCB")), build_rule(('D', "d")), build_rule(('S', "bB"))];
+    assert_eq!(dead, expected);
+
+    let mut live_rules: HashMap<Nonterminal, HashSet<Word>> = grammar.rules.clone();
+    for (nonterminal, word) in &dead {
+        live_rules.get_mut(nonterminal).unwrap().remove(word);
+    }
+    let fsa = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &live_rules);
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4);
+
+    assert!(relational_parsing::dead_rules(&common::basic_relational_parsing_example_grammar().rules, 'S').is_empty());
+}
+
+#[test]
+fn write_interned_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::nullable_recursive_grammar(), common::keyword_grammar()];
+    for grammar in grammars {
+        let fsa = &grammar.finite_state_automaton;
+        let mut written: Vec<u8> = Vec::new();
+        fsa.write_interned(&mut written).unwrap();
+        let written: String = String::from_utf8(written).unwrap();
+        let read: FiniteStateAutomaton = FiniteStateAutomaton::read_interned(&written).unwrap();
+
+        let mut rewritten: Vec<u8> = Vec::new();
+        read.write_interned(&mut rewritten).unwrap();
+        assert_eq!(String::from_utf8(rewritten).unwrap(), written);
+        let (mut csv, mut read_csv): (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
+        fsa.write_csv(&mut csv).unwrap();
+        read.write_csv(&mut read_csv).unwrap();
+        assert_eq!(read_csv, csv);
+        assert_eq!(read.atomic_map(), fsa.atomic_map());
+        assert_eq!(read.get_start(), fsa.get_start());
+    }
+
+    let written: &str = "start 0\nstates 0 1\naccepting 1\nrule n83 t97\ntransition 0 n83 1 0\natomic n83 97 1 -\n";
+    let read: FiniteStateAutomaton = FiniteStateAutomaton::read_interned(written).unwrap();
+    assert!(read.recognize(&['a']));
+    assert_eq!(FiniteStateAutomaton::read_interned("start 0\nstates 0 1\ntransition 0 n83 1 3\n").unwrap_err(), BuildError::Parse(3, String::from("unknown rule")));
+    assert_eq!(FiniteStateAutomaton::read_interned("start 0\nstates 0\nlanguage\n").unwrap_err(), BuildError::Parse(3, String::from("unknown line 'language'")));
+    assert_eq!(FiniteStateAutomaton::read_interned("start 0\nstates 0\ntransition 0 n83 1 -\n").unwrap_err(), BuildError::MissingState(1));
+}
+
+#[test]
+fn assert_interned_roundtrip_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::nullable_recursive_grammar(), common::keyword_grammar()];
+    for grammar in grammars {
+        grammar.finite_state_automaton.assert_interned_roundtrip();
+    }
+    // terminals which are separators or escapes in other formats
+    relational_parsing::parse_grammar_yacc_like("S : ' ' S | ',' | '\\\\' | '-' ;").unwrap().finite_state_automaton.assert_interned_roundtrip();
+}
+
+#[test]
+fn nerode_classes_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::even_a_middle_b_grammar(), common::keyword_grammar(), common::common_prefix_grammar()];
+    for grammar in grammars {
+        let fsa = &grammar.finite_state_automaton;
+        let classes: Vec<HashSet<State>> = fsa.nerode_classes(3);
+        let all: Vec<State> = classes.iter().flatten().cloned().collect();
+        let states: HashSet<State> = fsa.sccs().into_iter().flatten().collect();
+        assert_eq!(all.len(), states.len());
+        assert_eq!(all.into_iter().collect::<HashSet<State>>(), states);
+        assert!(classes.windows(2).all(|pair| pair[0].iter().min() < pair[1].iter().min()));
+        for (index, class) in classes.iter().enumerate() {
+            let representative: &State = class.iter().min().unwrap();
+            assert!(class.iter().all(|state| fsa.states_equivalent(representative, state, 3)));
+            assert!(classes[index + 1..].iter().all(|other| !fsa.states_equivalent(representative, other.iter().min().unwrap(), 3)));
+        }
+    }
+
+    // with no terminals to look at, the states only differ in being accepting or not
+    let fsa = &common::basic_relational_parsing_example_grammar().finite_state_automaton;
+    assert!(fsa.nerode_classes(0).len() <= 2);
+}
+
+#[test]
+fn build_fsa_with_stops_test() {
+    let stops: HashSet<Terminal> = HashSet::from(['\n', ';']);
+    let grammars: Vec<Grammar> = vec![common::keyword_grammar(), common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::nullable_recursive_grammar()];
+    for grammar in grammars {
+        let fsa = FiniteStateAutomaton::build_fsa_with_stops(&grammar.terminals, grammar.start, &grammar.rules, &stops);
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        for input in all_strings(&alphabet, 4) {
+            let accepted: bool = grammar.finite_state_automaton.recognize(&input);
+            assert!(!fsa.recognize(&input), "{:?}", input);
+            for stop in &stops {
+                assert_eq!(fsa.recognize(&[&input[..], &[*stop]].concat()), accepted, "{:?}", input);
+            }
+            assert!(!fsa.recognize(&[&input[..], &['\n', '\n']].concat()));
+        }
+        assert!(fsa.alphabet().contains(&'\n'));
+    }
+
+    let grammar = common::keyword_grammar();
+
+    let no_stops = FiniteStateAutomaton::build_fsa_with_stops(&grammar.terminals, grammar.start, &grammar.rules, &HashSet::new());
+    assert!(no_stops.is_empty_language());
+}
+
+#[test]
+fn write_dot_diff_test() {
+    let diff = |a: &FiniteStateAutomaton, b: &FiniteStateAutomaton| -> String {
+        let mut res: Vec<u8> = Vec::new();
+        relational_parsing::write_dot_diff(a, b, &mut res).unwrap();
+        String::from_utf8(res).unwrap()
+    };
+
+    let grammar = common::basic_relational_parsing_example_grammar();
+    let rebuilt = FiniteStateAutomaton::build_fsa(&grammar.terminals, grammar.start, &grammar.rules);
+    let same: String = diff(&grammar.finite_state_automaton, &rebuilt);
+    assert!(same.starts_with("digraph G {\n") && same.ends_with("}\n"));
+    assert!(!same.contains("red") && !same.contains("green"));
+    assert!(same.contains("doublecircle"));
+
+    let before = relational_parsing::parse_grammar_yacc_like("S : 'a' 'b' ;").unwrap();
+    let after = relational_parsing::parse_grammar_yacc_like("S : 'a' 'b' 'c' ;").unwrap();
+    let changed: String = diff(&before.finite_state_automaton, &after.finite_state_automaton);
+    assert!(changed.contains("label=\"c\" color=green"));
+    assert!(!changed.contains("red"));
+    let reverted: String = diff(&after.finite_state_automaton, &before.finite_state_automaton);
+    assert!(reverted.contains("label=\"c\" color=red"));
+    assert!(!reverted.contains("green"));
+}
+
+#[test]
+fn write_dot_region_test() {
+    let region = |fsa: &FiniteStateAutomaton, states: &HashSet<State>| -> String {
+        let mut res: Vec<u8> = Vec::new();
+        fsa.write_dot_region(&mut res, states).unwrap();
+        String::from_utf8(res).unwrap()
+    };
+    let edge_count = |dot: &str| -> usize { dot.lines().filter(|line| line.contains(" -> ")).count() };
+
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' 'b' 'c' ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    let states: HashSet<State> = fsa.sccs().into_iter().flatten().collect();
+    let all: String = region(fsa, &states);
+    assert!(all.starts_with("digraph G {\n") && all.ends_with("}"));
+    assert!(!all.contains("dashed"));
+    assert_eq!(edge_count(&all), fsa.stats().transitions + fsa.stats().atomic_entries);
+
+    let (start, _) = fsa.get_start();
+    let partial: String = region(fsa, &HashSet::from([start]));
+    assert_eq!(edge_count(&partial), fsa.adjacency()[&start].len());
+    assert!(partial.contains("1 [ shape=circle style=dashed ]"));
+    assert_eq!(region(fsa, &HashSet::new()), "digraph G {\n}");
+}
+
+#[test]
+fn lazy_fsa_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::nullable_recursive_grammar(), common::unit_chain_grammar()];
+    for grammar in grammars {
+        let lazy = LazyFsa::new(&grammar.terminals, grammar.start, &grammar.rules);
+        assert!(lazy.built_nonterminals().is_empty());
+        for input in all_strings(&grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4) {
+            let accepted: bool = grammar.finite_state_automaton.recognize(&input);
+            assert_eq!(lazy.recognize(&input), accepted, "{:?}", input);
+            assert_eq!(lazy.parse(&input).is_some(), accepted, "{:?}", input);
+        }
+        let fsa: FiniteStateAutomaton = lazy.into_fsa();
+        assert_eq!(fsa.stats().atomic_entries, grammar.finite_state_automaton.stats().atomic_entries);
+        assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &grammar.terminals.iter().cloned().collect::<Vec<Terminal>>(), 4);
+    }
+
+    // only the nonterminals entered by the input are built
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' A | 'b' B ; A : 'a' A | ; B : C 'b' ; C : 'c' ;").unwrap();
+    let lazy = LazyFsa::new(&grammar.terminals, grammar.start, &grammar.rules);
+    assert!(lazy.recognize(&['a', 'a']));
+    assert_eq!(lazy.built_nonterminals(), HashSet::from(['S', 'A']));
+    let mut rules: Vec<Rule> = lazy.parse(&['a', 'a']).unwrap().into_iter().flatten().collect();
+    rules.sort();
+    assert_eq!(rules, vec![build_rule(('A', "e")), build_rule(('A', "aA")), build_rule(('S', "aA"))]);
+    assert_eq!(lazy.built_nonterminals(), HashSet::from(['S', 'A']));
+    let (start, _) = lazy.get_start();
+    assert!(lazy.simulate(&start, Symbol::Nonterminal('S')).is_some());
+    assert!(lazy.get_atomic(Symbol::Nonterminal('C'), 'a').is_none());
+    assert!(lazy.get_atomic(Symbol::Nonterminal('C'), 'c').is_some());
+    assert_eq!(lazy.built_nonterminals(), HashSet::from(['S', 'A', 'C']));
+}
+
+#[test]
+fn epsilon_cycles_test() {
+    // the nulled alternative of A makes the Kleene star of [S]^(a) loop on epsilon
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : S A | 'a' ; A : 'b' | ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    let (star, _, _) = fsa.get_atomic(Symbol::Nonterminal('S'), 'a').unwrap();
+    assert_eq!(fsa.epsilon_cycles(), vec![vec![*star]]);
+    assert!(fsa.recognize(&['a', 'b', 'b']));
+
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar()];
+    for grammar in grammars {
+        assert!(grammar.finite_state_automaton.epsilon_cycles().is_empty());
+    }
+    for cycle in common::nullable_recursive_grammar().finite_state_automaton.epsilon_cycles() {
+        assert_eq!(cycle.len(), 1);
+    }
+
+    // an accidental cycle through two states, next to a self-loop
+    let epsilon = |dest: State| -> HashMap<Symbol, HashSet<(State, Rules)>> { HashMap::from([(Symbol::Epsilon, HashSet::from([(dest, Vec::new())]))]) };
+    let mut transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::from([(0, epsilon(2)), (2, epsilon(3)), (3, epsilon(2)), (4, epsilon(4))]);
+    transitions.get_mut(&3).unwrap().get_mut(&Symbol::Epsilon).unwrap().insert((1, Vec::new()));
+    let fsa = FiniteStateAutomaton::from_parts(HashSet::from([0, 1, 2, 3, 4]), HashSet::from([1]), 0, transitions, HashMap::new()).unwrap();
+    assert_eq!(fsa.epsilon_cycles(), vec![vec![2, 3], vec![4]]);
+}
+
+#[test]
+fn startable_nonterminals_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' A B | 'a' C ; A : 'x' ; B : 'y' ; C : 'z' ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    let (start, _) = fsa.get_start();
+    assert_eq!(fsa.startable_nonterminals(&start), BTreeSet::from(['S']));
+    let (atomic, _, _) = fsa.get_atomic(Symbol::Nonterminal('S'), 'a').unwrap();
+    assert_eq!(fsa.startable_nonterminals(atomic), BTreeSet::from(['A', 'C']));
+    let (epsilon, _, _) = fsa.get_atomic(Symbol::Terminal('a'), 'a').unwrap();
+    assert!(fsa.startable_nonterminals(epsilon).is_empty());
+    assert!(fsa.startable_nonterminals(&1000).is_empty());
+}
+
+#[test]
+fn automaton_follow_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : 'c' a 'x' ; a : 'a' | 'b' a ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    assert_eq!(fsa.automaton_follow('a'), HashSet::from(['x']));
+    assert_eq!(fsa.automaton_follow('s'), HashSet::new());
+
+    // b is followed by ')' within parentheses and by 'x' or 'y' outside of them
+    let grammar = relational_parsing::parse_grammar_yacc_like("s : 'c' b 'x' | 'd' b 'y' ; b : 'a' | '(' b ')' ;").unwrap();
+    assert_eq!(grammar.finite_state_automaton.automaton_follow('b'), HashSet::from([')', 'x', 'y']));
+}
+
+#[test]
+fn lookahead_table_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'c' A 'x' ; A : 'a' | 'b' A ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    let table: HashMap<State, HashSet<Terminal>> = fsa.lookahead_table();
+    let (start, _) = fsa.get_start();
+    assert_eq!(table[&start], HashSet::from(['c']));
+    assert_eq!(table.len(), fsa.stats().states);
+    assert_eq!(fsa.lookahead_table(), table);
+
+    // the configurations after a prefix are closed, so a terminal which can be consumed next is in the table of one of
+    // their current states
+    let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+    for input in all_strings(&alphabet, 4) {
+        let states: HashSet<State> = fsa.states_after(&input);
+        for terminal in &alphabet {
+            let extended: Vec<Terminal> = [&input[..], &[*terminal]].concat();
+            assert_eq!(!fsa.states_after(&extended).is_empty(), states.iter().any(|state| table[state].contains(terminal)), "{:?}", extended);
+        }
+    }
+}
+
+#[test]
+fn is_deterministic_test() {
+    assert!(relational_parsing::parse_grammar_yacc_like("S : 'a' 'b' 'c' ;").unwrap().finite_state_automaton.is_deterministic());
+    assert!(relational_parsing::parse_grammar_yacc_like("S : 'a' S | 'b' ;").unwrap().finite_state_automaton.is_deterministic());
+    assert!(relational_parsing::parse_grammar_yacc_like("S : 'c' A 'x' ; A : 'a' | 'b' A ;").unwrap().finite_state_automaton.is_deterministic());
+
+    // ambiguous
+    assert!(!common::basic_relational_parsing_example_grammar().finite_state_automaton.is_deterministic());
+    assert!(!relational_parsing::parse_grammar_yacc_like("S : A | B ; A : 'a' ; B : 'a' ;").unwrap().finite_state_automaton.is_deterministic());
+    // once S is complete, its atomic language can both return and continue with an 'a'
+    assert!(!relational_parsing::parse_grammar_yacc_like("S : S 'a' | 'b' ;").unwrap().finite_state_automaton.is_deterministic());
+}
+
+#[test]
+fn recognize_deterministic_test() {
+    for input in ["S : 'a' 'b' 'c' ;", "S : 'a' S | 'b' ;", "S : 'c' A 'x' ; A : 'a' | 'b' A ;", "S : 'a' S 'b' | 'c' ;"] {
+        let grammar = relational_parsing::parse_grammar_yacc_like(input).unwrap();
+        let fsa = &grammar.finite_state_automaton;
+        assert!(fsa.is_deterministic(), "{}", input);
+        let deterministic: DeterministicFsa = fsa.as_deterministic().unwrap();
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        assert_same_language(|input| deterministic.recognize(input), |input| fsa.recognize(input), &alphabet, 6);
+    }
+    assert!(common::basic_relational_parsing_example_grammar().finite_state_automaton.as_deterministic().is_none());
+}
+
+// Compares the single-configuration and the set-based recognition, run with `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn recognize_deterministic_benchmark_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("S : 'a' S | 'b' 'c' S | 'd' ;").unwrap();
+    let fsa = &grammar.finite_state_automaton;
+    let input: Vec<Terminal> = "abc".repeat(3000).chars().chain(['d']).collect();
+
+    let start = std::time::Instant::now();
+    assert!(fsa.recognize(&input));
+    let set_based = start.elapsed();
+
+    let checked: DeterministicFsa = fsa.as_deterministic().unwrap();
+    let start = std::time::Instant::now();
+    assert!(checked.recognize(&input));
+    let deterministic = start.elapsed();
+
+    println!("{} terminals: set-based {:?}, deterministic {:?}", input.len(), set_based, deterministic);
+}
+
+#[test]
+fn bisimilar_test() {
+    let first: Rules = vec![build_rule(('A', "a"))];
+    let second: Rules = vec![build_rule(('B', "a"))];
+    let transition = |dests: Vec<(State, &Rules)>| -> HashMap<Symbol, HashSet<(State, Rules)>> {
+        HashMap::from([(Symbol::Terminal('a'), dests.into_iter().map(|(dest, rules)| (dest, rules.clone())).collect())])
+    };
+    // 0 and 2 accept the same language with different rules, 3 mirrors 0, and 5 reaches two accepting states
+    let transitions: HashMap<State, HashMap<Symbol, HashSet<(State, Rules)>>> = HashMap::from([
+        (0, transition(vec![(1, &first)])),
+        (2, transition(vec![(1, &second)])),
+        (3, transition(vec![(4, &first)])),
+        (5, transition(vec![(1, &first), (4, &first)])),
+        (6, transition(vec![(7, &first)])),
+    ]);
+    let fsa = FiniteStateAutomaton::from_parts((0..8).collect(), HashSet::from([1, 4]), 0, transitions, HashMap::new()).unwrap();
+
+    assert!(fsa.states_equivalent(&0, &2, 3));
+    assert!(!fsa.bisimilar(&0, &2));
+    assert!(fsa.bisimilar(&0, &3));
+    assert!(fsa.bisimilar(&0, &5));
+    assert!(fsa.bisimilar(&1, &4));
+    assert!(!fsa.bisimilar(&0, &6));
+    assert!(!fsa.bisimilar(&1, &7));
+    assert!(fsa.bisimilar(&2, &2));
+    assert!(!fsa.bisimilar(&0, &100));
+
+    // bisimilar states have the same language
+    let grammar = common::e_rule_relational_parsing_example_grammar();
+    let fsa = &grammar.finite_state_automaton;
+    let states: Vec<State> = fsa.sccs().into_iter().flatten().collect();
+    for a in &states {
+        for b in &states {
+            if fsa.bisimilar(a, b) {
+                assert!(fsa.states_equivalent(a, b, 4));
+            }
+        }
+    }
+}
+
+#[test]
+fn parse_prioritized_test() {
+    let grammar = relational_parsing::parse_grammar_yacc_like("e : e '+' e | 'a' ;").unwrap();
+    let input: Vec<Terminal> = vec!['a', '+', 'a', '+', 'a'];
+    let plus: Rule = (grammar.start, vec![Symbol::Nonterminal(grammar.start), Symbol::Terminal('+'), Symbol::Nonterminal(grammar.start)]);
+    let a: Rule = (grammar.start, vec![Symbol::Terminal('a')]);
+    // the rules of a path, in the order in which the automaton collects them
+    let flat = |trail: Vec<Rules>| -> Rules { trail.into_iter().flatten().collect() };
+
+    // preferring the plus rule completes the first addition before the second 'a' is followed by '+', nesting the
+    // additions to the left
+    let mut priorities: HashMap<(Nonterminal, Rule), i32> = HashMap::new();
+    priorities.insert((grammar.start, plus.clone()), 1);
+    let plus_first: Rules = flat(grammar.parse_prioritized(&input, &priorities).unwrap());
+    assert_eq!(plus_first, vec![a.clone(), a.clone(), plus.clone(), a.clone(), plus.clone()]);
+
+    // giving the plus rule a lower priority than the move consuming the '+' nests them to the right instead
+    priorities.insert((grammar.start, plus.clone()), -1);
+    let a_first: Rules = flat(grammar.parse_prioritized(&input, &priorities).unwrap());
+    assert_eq!(a_first, vec![a.clone(), a.clone(), a.clone(), plus.clone(), plus.clone()]);
+
+    assert_eq!(grammar.parse_prioritized(&['a', '+'], &priorities), None);
+}
+
+#[test]
+fn share_atomic_subautomata_test() {
+    let grammar = common::indirect_left_recursive_grammar();
+    let alphabet: Vec<Terminal> = grammar.finite_state_automaton.alphabet().into_iter().collect();
+    // the product with a two state automaton accepting everything duplicates the atomic languages, cycles included
+    let product = grammar.finite_state_automaton.restrict_by(0, &HashSet::from([0, 1]), |state: &State, _| Some(1 - state));
+
+    let mut fsa = product.restrict_by(0, &HashSet::from([0]), |_: &State, _| Some(0));
+    let before: FsaStats = fsa.stats();
+    let saved: usize = fsa.share_atomic_subautomata();
+    assert!(saved > 0);
+    assert_eq!(fsa.stats().states, before.states - saved);
+    assert_eq!(fsa.stats().atomic_entries, before.atomic_entries);
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 6);
+    assert_eq!(fsa.share_atomic_subautomata(), 0);
+
+    // dedup_atomic leaves the copies of cycles apart
+    let mut fsa = product.restrict_by(0, &HashSet::from([0]), |_: &State, _| Some(0));
+    fsa.dedup_atomic();
+    assert!(fsa.share_atomic_subautomata() > 0);
+    assert_same_language(|input| fsa.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 6);
+}
+
+#[test]
+fn build_fsa_canonical_test() {
+    let grammars: Vec<Grammar> = vec![common::basic_relational_parsing_example_grammar(), common::e_rule_relational_parsing_example_grammar(), common::difficult_bottom_up_grammar(), common::even_more_indirect_left_recursive_grammar(), common::nullable_recursive_grammar(), common::keyword_grammar()];
+    for grammar in grammars {
+        let alphabet: Vec<Terminal> = grammar.terminals.iter().cloned().collect();
+        let canonical = FiniteStateAutomaton::build_fsa_canonical(&grammar.terminals, grammar.start, &grammar.rules);
+        assert_eq!(canonical.get_start().0, 0);
+        let mut states: Vec<State> = canonical.sccs().into_iter().flatten().collect();
+        states.sort();
+        assert_eq!(states, (0..states.len()).collect::<Vec<State>>());
+        assert_eq!(canonical.stats(), grammar.finite_state_automaton.stats());
+        assert_same_language(|input| canonical.recognize(input), |input| grammar.finite_state_automaton.recognize(input), &alphabet, 5);
+
+        // a separate build iterates its hash maps in a different order, but is numbered the same
+        let mut rules: Vec<(Nonterminal, HashSet<Word>)> = grammar.rules.clone().into_iter().collect();
+        rules.reverse();
+        let rebuilt = FiniteStateAutomaton::build_fsa_canonical(&grammar.terminals, grammar.start, &rules.into_iter().collect());
+        let (mut csv, mut rebuilt_csv): (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
+        canonical.write_csv(&mut csv).unwrap();
+        rebuilt.write_csv(&mut rebuilt_csv).unwrap();
+        assert_eq!(csv, rebuilt_csv);
+    }
+
+    let grammar = common::direct_left_recursive_grammar();
+    let canonical = FiniteStateAutomaton::build_fsa_canonical(&grammar.terminals, grammar.start, &grammar.rules);
+    let mut csv: Vec<u8> = Vec::new();
+    canonical.write_csv(&mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "src,symbol,dest,rules,accepting_dest\n0,A,1,,true\n2,a,2,[A -> Aa],true\n");
+}
+